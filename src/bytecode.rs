@@ -0,0 +1,598 @@
+// 字节码编译器：把抽象语法树编译成紧凑的指令序列，由vm模块中的栈式虚拟机执行
+// 每个函数编译成独立的指令块，常量统一存放在常量池中，局部变量通过槽位编号访问
+use crate::interp::Value;
+use crate::lexer::Span;
+use crate::parser::{DataType, Expr, ExprKind, Operator, Statement, StatementKind};
+use std::collections::HashMap;
+use std::fmt::Write as _;
+
+// 字节码指令
+#[derive(Debug, Clone, PartialEq)]
+pub enum Instruction {
+    // 把常量池中的第n个常量压栈
+    Constant(usize),
+    // 把局部变量槽位n的值压栈
+    LoadLocal(usize),
+    // 弹出栈顶值并存入局部变量槽位n
+    StoreLocal(usize),
+    // 弹出并丢弃栈顶值
+    Pop,
+    // 弹出两个操作数，计算二元运算后压栈
+    Binary(Operator),
+    // 对栈顶值取负
+    Negate,
+    // 对栈顶的布尔值取反
+    Not,
+    // 把栈顶的整数转换为浮点数（用于float类型的变量、参数和返回值）
+    ToFloat,
+    // 弹出字符串，压入其字节长度
+    StrLen,
+    // 弹出字节下标和字符串，压入该下标处的字符和下一个字符的字节下标
+    StrChar,
+    // 无条件跳转到指令地址
+    Jump(usize),
+    // 弹出条件值，为false时跳转到指令地址
+    JumpIfFalse(usize),
+    // 弹出n个值，用空格连接后输出（不换行）
+    Print(usize),
+    // 弹出一个值并输出一行
+    Println,
+    // 调用第n个函数，参数个数为argc
+    Call(usize, usize),
+    // 弹出返回值并返回调用者
+    Return,
+}
+
+// 编译后的函数
+#[derive(Debug, Clone)]
+pub struct FunctionChunk {
+    // 函数名（顶层代码为"main"）
+    pub name: String,
+    // 参数个数，参数占用前arity个局部变量槽位
+    pub arity: usize,
+    // 每个局部变量槽位对应的变量名，用于反汇编
+    pub slot_names: Vec<String>,
+    // 指令序列
+    pub code: Vec<Instruction>,
+    // 每条指令对应的源代码位置，用于运行时错误信息
+    pub spans: Vec<Span>,
+}
+
+// 编译后的程序：常量池和函数表，第0个函数是顶层代码
+#[derive(Debug, Clone)]
+pub struct Program {
+    pub constants: Vec<Value>,
+    pub functions: Vec<FunctionChunk>,
+}
+
+// 正在编译的循环中等待回填的break和continue跳转指令地址
+#[derive(Default)]
+struct LoopJumps {
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+// 函数编译状态：当前函数的指令块和局部变量作用域
+struct FunctionState {
+    chunk: FunctionChunk,
+    // 作用域栈：每层记录变量名到槽位的映射
+    scopes: Vec<HashMap<String, usize>>,
+    // 每个槽位的声明类型，赋值给float槽位时需要把整数转换为浮点数
+    slot_types: Vec<Option<DataType>>,
+    // 函数声明的返回类型
+    return_type: Option<DataType>,
+    // 是否是顶层代码
+    is_main: bool,
+    // 当前函数内正在编译的循环，栈顶为最内层循环
+    loops: Vec<LoopJumps>,
+}
+
+// 字节码编译器
+struct Compiler {
+    // 常量池
+    constants: Vec<Value>,
+    // 函数名到函数编号的映射（包括嵌套定义的函数）
+    function_ids: HashMap<String, usize>,
+    // 函数名到声明的返回类型的映射，用于推断变量的类型
+    return_types: HashMap<String, Option<DataType>>,
+    // 已编译的函数，按编号存放
+    functions: Vec<Option<FunctionChunk>>,
+    // 当前正在编译的函数
+    state: FunctionState,
+}
+
+// 生成带位置的编译错误信息
+fn compile_error(span: Span, message: String) -> String {
+    format!("{}: 字节码编译错误: {}", span, message)
+}
+
+impl Compiler {
+    // 创建编译器，当前函数为顶层代码
+    fn new() -> Self {
+        Compiler {
+            constants: Vec::new(),
+            function_ids: HashMap::new(),
+            return_types: HashMap::new(),
+            functions: vec![None],
+            state: FunctionState::main(),
+        }
+    }
+
+    // 追加一条指令，返回指令地址
+    fn emit(&mut self, instruction: Instruction, span: Span) -> usize {
+        self.state.chunk.code.push(instruction);
+        self.state.chunk.spans.push(span);
+        self.state.chunk.code.len() - 1
+    }
+
+    // 把值加入常量池并生成压栈指令，相同的常量只存一份
+    fn emit_constant(&mut self, value: Value, span: Span) {
+        let index = match self.constants.iter().position(|c| c == &value) {
+            Some(index) => index,
+            None => {
+                self.constants.push(value);
+                self.constants.len() - 1
+            },
+        };
+        self.emit(Instruction::Constant(index), span);
+    }
+
+    // 回填跳转指令的目标地址为当前位置
+    fn patch_jump(&mut self, at: usize) {
+        let target = self.state.chunk.code.len();
+        match &mut self.state.chunk.code[at] {
+            Instruction::Jump(t) | Instruction::JumpIfFalse(t) => *t = target,
+            other => unreachable!("回填的不是跳转指令: {:?}", other),
+        }
+    }
+
+    // 当前指令地址（下一条指令的位置）
+    fn current_address(&self) -> usize {
+        self.state.chunk.code.len()
+    }
+
+    // 在当前作用域中分配新的局部变量槽位，同时记录它的声明类型
+    fn declare_local(&mut self, name: &str, data_type: Option<DataType>) -> usize {
+        let slot = self.state.chunk.slot_names.len();
+        self.state.chunk.slot_names.push(name.to_string());
+        self.state.slot_types.push(data_type);
+        self.state.scopes.last_mut().unwrap().insert(name.to_string(), slot);
+        slot
+    }
+
+    // 查找变量对应的槽位，从最内层作用域开始
+    fn resolve_local(&self, name: &str) -> Option<usize> {
+        self.state.scopes.iter().rev().find_map(|scope| scope.get(name).copied())
+    }
+
+    // 静态判断表达式的值是否是浮点数，用于确定没有类型标注的变量的类型
+    fn is_float(&self, expr: &Expr) -> bool {
+        match &expr.kind {
+            ExprKind::Float(_) => true,
+            ExprKind::Identifier(name) => self
+                .resolve_local(name)
+                .is_some_and(|slot| self.state.slot_types[slot] == Some(DataType::Float)),
+            ExprKind::BinaryOp(left, op, right) => {
+                matches!(op, Operator::Add | Operator::Sub | Operator::Mul | Operator::Div | Operator::Mod | Operator::FloorDiv | Operator::Pow)
+                    && (self.is_float(left) || self.is_float(right))
+            },
+            ExprKind::UnaryOp(Operator::Sub, operand) => self.is_float(operand),
+            ExprKind::Call(name, _) => matches!(self.return_types.get(name), Some(Some(DataType::Float))),
+            _ => false,
+        }
+    }
+
+    // 递归为所有函数定义分配编号
+    fn collect_functions(&mut self, statements: &[Statement]) {
+        for stmt in statements {
+            match &stmt.kind {
+                StatementKind::Function(name, _, return_type, body) => {
+                    if !self.function_ids.contains_key(name) {
+                        self.function_ids.insert(name.clone(), self.functions.len());
+                        self.return_types.insert(name.clone(), return_type.clone());
+                        self.functions.push(None);
+                    }
+                    self.collect_functions(body);
+                },
+                StatementKind::If(_, then_branch, else_branch) => {
+                    self.collect_functions(then_branch);
+                    if let Some(else_branch) = else_branch {
+                        self.collect_functions(else_branch);
+                    }
+                },
+                StatementKind::For(_, _, body) | StatementKind::While(_, body) => {
+                    self.collect_functions(body);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    // 在新的块作用域中编译语句列表
+    fn compile_block(&mut self, statements: &[Statement]) -> Result<(), String> {
+        self.state.scopes.push(HashMap::new());
+        let result = statements.iter().try_for_each(|stmt| self.compile_statement(stmt));
+        self.state.scopes.pop();
+        result
+    }
+
+    // 编译循环体，返回循环体中break的跳转地址，由调用者回填到循环之后
+    // continue跳转回填到循环体之后紧接的位置，即计数器递增或跳回条件的指令
+    fn compile_loop_body(&mut self, body: &[Statement]) -> Result<Vec<usize>, String> {
+        self.state.loops.push(LoopJumps::default());
+        let result = self.compile_block(body);
+        let jumps = self.state.loops.pop().unwrap();
+        result?;
+        for at in jumps.continues {
+            self.patch_jump(at);
+        }
+        Ok(jumps.breaks)
+    }
+
+    // 为break或continue生成待回填的跳转指令，不在循环体内时报告错误
+    fn emit_loop_jump(&mut self, is_break: bool, span: Span) -> Result<(), String> {
+        if self.state.loops.is_empty() {
+            let keyword = if is_break { "break" } else { "continue" };
+            return Err(compile_error(span, format!("{}语句只能出现在循环体内", keyword)));
+        }
+        let at = self.emit(Instruction::Jump(0), span);
+        let jumps = self.state.loops.last_mut().unwrap();
+        if is_break {
+            jumps.breaks.push(at);
+        } else {
+            jumps.continues.push(at);
+        }
+        Ok(())
+    }
+
+    // 编译单条语句
+    fn compile_statement(&mut self, stmt: &Statement) -> Result<(), String> {
+        let span = stmt.span;
+        match &stmt.kind {
+            StatementKind::Var(name, data_type, expr) => {
+                self.compile_expression(expr)?;
+                if let Some(DataType::Float) = data_type {
+                    self.emit(Instruction::ToFloat, span);
+                }
+                // 没有类型标注时，初始值是浮点数的变量也按float处理
+                let data_type = data_type.clone().or_else(|| self.is_float(expr).then_some(DataType::Float));
+                // 先计算初始值再声明，初始值表达式中不能引用正在声明的变量
+                let slot = self.declare_local(name, data_type);
+                self.emit(Instruction::StoreLocal(slot), span);
+            },
+            StatementKind::Assign(name, expr) => {
+                let slot = self
+                    .resolve_local(name)
+                    .ok_or_else(|| compile_error(span, format!("未定义的变量: {}", name)))?;
+                self.compile_expression(expr)?;
+                if let Some(DataType::Float) = self.state.slot_types[slot] {
+                    self.emit(Instruction::ToFloat, span);
+                }
+                self.emit(Instruction::StoreLocal(slot), span);
+            },
+            StatementKind::Print(exprs) => {
+                for expr in exprs {
+                    self.compile_expression(expr)?;
+                }
+                self.emit(Instruction::Print(exprs.len()), span);
+            },
+            StatementKind::Println(exprs) => {
+                for expr in exprs {
+                    self.compile_expression(expr)?;
+                    self.emit(Instruction::Println, expr.span);
+                }
+            },
+            StatementKind::If(condition, then_branch, else_branch) => {
+                self.compile_expression(condition)?;
+                let to_else = self.emit(Instruction::JumpIfFalse(0), condition.span);
+                self.compile_block(then_branch)?;
+                match else_branch {
+                    Some(else_branch) => {
+                        let to_end = self.emit(Instruction::Jump(0), span);
+                        self.patch_jump(to_else);
+                        self.compile_block(else_branch)?;
+                        self.patch_jump(to_end);
+                    },
+                    None => self.patch_jump(to_else),
+                }
+            },
+            StatementKind::While(condition, body) => {
+                let loop_start = self.current_address();
+                self.compile_expression(condition)?;
+                let to_end = self.emit(Instruction::JumpIfFalse(0), condition.span);
+                let breaks = self.compile_loop_body(body)?;
+                self.emit(Instruction::Jump(loop_start), span);
+                self.patch_jump(to_end);
+                breaks.into_iter().for_each(|at| self.patch_jump(at));
+            },
+            StatementKind::For(var_name, iterable, body) => self.compile_for(var_name, iterable, body, span)?,
+            StatementKind::Expression(expr) => {
+                self.compile_expression(expr)?;
+                self.emit(Instruction::Pop, span);
+            },
+            StatementKind::Function(name, params, return_type, body) => {
+                let id = self.function_ids[name];
+                // 切换到新的函数编译状态，函数体不能访问外层的局部变量
+                let outer = std::mem::replace(&mut self.state, FunctionState::new(name, return_type.clone()));
+                for param in params {
+                    self.declare_local(&param.name, Some(param.data_type.clone()));
+                }
+                self.state.chunk.arity = params.len();
+                // float参数需要把整数实参转换为浮点数
+                for (slot, param) in params.iter().enumerate() {
+                    if param.data_type == DataType::Float {
+                        self.emit(Instruction::LoadLocal(slot), param.span);
+                        self.emit(Instruction::ToFloat, param.span);
+                        self.emit(Instruction::StoreLocal(slot), param.span);
+                    }
+                }
+                let result = body.iter().try_for_each(|stmt| self.compile_statement(stmt));
+                // 函数体末尾隐式返回空值
+                self.emit_constant(Value::Unit, span);
+                self.emit(Instruction::Return, span);
+                let finished = std::mem::replace(&mut self.state, outer);
+                result?;
+                self.functions[id] = Some(finished.chunk);
+            },
+            StatementKind::Return(value) => {
+                if self.state.is_main {
+                    return Err(compile_error(span, "return语句只能出现在函数体内".to_string()));
+                }
+                match value {
+                    Some(expr) => self.compile_expression(expr)?,
+                    None => self.emit_constant(Value::Unit, span),
+                }
+                if let Some(DataType::Float) = self.state.return_type {
+                    self.emit(Instruction::ToFloat, span);
+                }
+                self.emit(Instruction::Return, span);
+            },
+            StatementKind::Break => self.emit_loop_jump(true, span)?,
+            StatementKind::Continue => self.emit_loop_jump(false, span)?,
+        }
+        Ok(())
+    }
+
+    // 编译for循环
+    // 范围循环使用隐藏的计数器和终点槽位；字符串循环使用隐藏的字符串、下标和长度槽位
+    // 隐藏槽位的名字以$开头，不会与用户变量冲突
+    fn compile_for(&mut self, var_name: &str, iterable: &Expr, body: &[Statement], span: Span) -> Result<(), String> {
+        self.state.scopes.push(HashMap::new());
+        match &iterable.kind {
+            ExprKind::Range(start, end) => {
+                self.compile_expression(start)?;
+                let counter = self.declare_local("$counter", None);
+                self.emit(Instruction::StoreLocal(counter), span);
+                self.compile_expression(end)?;
+                let limit = self.declare_local("$end", None);
+                self.emit(Instruction::StoreLocal(limit), span);
+
+                // 循环条件：counter <= end
+                let loop_start = self.current_address();
+                self.emit(Instruction::LoadLocal(counter), span);
+                self.emit(Instruction::LoadLocal(limit), span);
+                self.emit(Instruction::Binary(Operator::Le), span);
+                let to_end = self.emit(Instruction::JumpIfFalse(0), span);
+
+                // 每次迭代把计数器复制给循环变量，循环体修改循环变量不影响迭代
+                self.state.scopes.push(HashMap::new());
+                let var = self.declare_local(var_name, None);
+                self.emit(Instruction::LoadLocal(counter), span);
+                self.emit(Instruction::StoreLocal(var), span);
+                let result = self.compile_loop_body(body);
+                self.state.scopes.pop();
+                let breaks = result?;
+
+                // counter += 1
+                self.emit(Instruction::LoadLocal(counter), span);
+                self.emit_constant(Value::Int(1), span);
+                self.emit(Instruction::Binary(Operator::Add), span);
+                self.emit(Instruction::StoreLocal(counter), span);
+                self.emit(Instruction::Jump(loop_start), span);
+                self.patch_jump(to_end);
+                breaks.into_iter().for_each(|at| self.patch_jump(at));
+            },
+            _ => {
+                self.compile_expression(iterable)?;
+                let string = self.declare_local("$string", None);
+                self.emit(Instruction::StoreLocal(string), span);
+                self.emit_constant(Value::Int(0), span);
+                let index = self.declare_local("$index", None);
+                self.emit(Instruction::StoreLocal(index), span);
+                self.emit(Instruction::LoadLocal(string), iterable.span);
+                self.emit(Instruction::StrLen, iterable.span);
+                let length = self.declare_local("$length", None);
+                self.emit(Instruction::StoreLocal(length), span);
+
+                // 循环条件：index < length
+                let loop_start = self.current_address();
+                self.emit(Instruction::LoadLocal(index), span);
+                self.emit(Instruction::LoadLocal(length), span);
+                self.emit(Instruction::Binary(Operator::Lt), span);
+                let to_end = self.emit(Instruction::JumpIfFalse(0), span);
+
+                // 取出当前字符作为循环变量，index按字节前进到下一个字符
+                // 每次迭代只访问一个字符，遍历整个字符串是线性时间
+                self.state.scopes.push(HashMap::new());
+                let var = self.declare_local(var_name, None);
+                self.emit(Instruction::LoadLocal(string), span);
+                self.emit(Instruction::LoadLocal(index), span);
+                self.emit(Instruction::StrChar, span);
+                self.emit(Instruction::StoreLocal(index), span);
+                self.emit(Instruction::StoreLocal(var), span);
+                let result = self.compile_loop_body(body);
+                self.state.scopes.pop();
+                let breaks = result?;
+                self.emit(Instruction::Jump(loop_start), span);
+                self.patch_jump(to_end);
+                breaks.into_iter().for_each(|at| self.patch_jump(at));
+            },
+        }
+        self.state.scopes.pop();
+        Ok(())
+    }
+
+    // 编译表达式，结果留在栈顶
+    fn compile_expression(&mut self, expr: &Expr) -> Result<(), String> {
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Number(n) => self.emit_constant(Value::Int(*n), span),
+            ExprKind::Float(x) => self.emit_constant(Value::Float(*x), span),
+            ExprKind::String(s) => self.emit_constant(Value::Str(s.clone()), span),
+            ExprKind::BoolLiteral(b) => self.emit_constant(Value::Bool(*b), span),
+            ExprKind::Identifier(name) => {
+                let slot = self
+                    .resolve_local(name)
+                    .ok_or_else(|| compile_error(span, format!("未定义的变量: {}", name)))?;
+                self.emit(Instruction::LoadLocal(slot), span);
+            },
+            // 逻辑与短路：左侧为false时跳过右侧，结果为false
+            ExprKind::BinaryOp(left, Operator::And, right) => {
+                self.compile_expression(left)?;
+                let to_false = self.emit(Instruction::JumpIfFalse(0), span);
+                self.compile_expression(right)?;
+                let to_end = self.emit(Instruction::Jump(0), span);
+                self.patch_jump(to_false);
+                self.emit_constant(Value::Bool(false), span);
+                self.patch_jump(to_end);
+            },
+            // 逻辑或短路：左侧为true时跳过右侧，结果为true
+            ExprKind::BinaryOp(left, Operator::Or, right) => {
+                self.compile_expression(left)?;
+                let to_right = self.emit(Instruction::JumpIfFalse(0), span);
+                self.emit_constant(Value::Bool(true), span);
+                let to_end = self.emit(Instruction::Jump(0), span);
+                self.patch_jump(to_right);
+                self.compile_expression(right)?;
+                self.patch_jump(to_end);
+            },
+            ExprKind::BinaryOp(left, op, right) => {
+                self.compile_expression(left)?;
+                self.compile_expression(right)?;
+                self.emit(Instruction::Binary(op.clone()), span);
+            },
+            ExprKind::UnaryOp(op, operand) => {
+                self.compile_expression(operand)?;
+                match op {
+                    Operator::Sub => {
+                        self.emit(Instruction::Negate, span);
+                    },
+                    Operator::Not => {
+                        self.emit(Instruction::Not, span);
+                    },
+                    _ => return Err(compile_error(span, format!("不支持的一元运算符: {:?}", op))),
+                }
+            },
+            ExprKind::Range(..) => return Err(compile_error(span, "范围表达式只能用于for循环".to_string())),
+            ExprKind::Call(name, arguments) => {
+                let id = *self
+                    .function_ids
+                    .get(name)
+                    .ok_or_else(|| compile_error(span, format!("未定义的函数: {}", name)))?;
+                for argument in arguments {
+                    self.compile_expression(argument)?;
+                }
+                self.emit(Instruction::Call(id, arguments.len()), span);
+            },
+        }
+        Ok(())
+    }
+}
+
+impl FunctionState {
+    // 创建函数编译状态，包含一层函数作用域
+    fn new(name: &str, return_type: Option<DataType>) -> Self {
+        FunctionState {
+            chunk: FunctionChunk {
+                name: name.to_string(),
+                arity: 0,
+                slot_names: Vec::new(),
+                code: Vec::new(),
+                spans: Vec::new(),
+            },
+            scopes: vec![HashMap::new()],
+            slot_types: Vec::new(),
+            return_type,
+            is_main: false,
+            loops: Vec::new(),
+        }
+    }
+
+    // 创建顶层代码的编译状态
+    fn main() -> Self {
+        FunctionState {
+            is_main: true,
+            ..FunctionState::new("main", None)
+        }
+    }
+}
+
+// 把语句列表编译成字节码程序
+pub fn compile(statements: &[Statement]) -> Result<Program, String> {
+    let mut compiler = Compiler::new();
+    compiler.collect_functions(statements);
+    for stmt in statements {
+        compiler.compile_statement(stmt)?;
+    }
+    let end = statements.last().map(|s| s.span).unwrap_or_default();
+    compiler.emit_constant(Value::Unit, end);
+    compiler.emit(Instruction::Return, end);
+
+    let main = std::mem::replace(&mut compiler.state, FunctionState::main()).chunk;
+    compiler.functions[0] = Some(main);
+    let functions = compiler
+        .functions
+        .into_iter()
+        .map(|f| f.expect("所有函数都应已编译"))
+        .collect();
+    Ok(Program {
+        constants: compiler.constants,
+        functions,
+    })
+}
+
+// 生成程序的反汇编清单
+pub fn disassemble(program: &Program) -> String {
+    let mut listing = String::new();
+    let _ = writeln!(listing, "== 常量池 ({}) ==", program.constants.len());
+    for (i, constant) in program.constants.iter().enumerate() {
+        let _ = writeln!(listing, "{:04}  {:<8} {:?}", i, constant.type_name(), constant.to_string());
+    }
+    for (id, function) in program.functions.iter().enumerate() {
+        let _ = writeln!(
+            listing,
+            "\n== 函数 #{} {} (参数: {}, 局部变量: {}) ==",
+            id,
+            function.name,
+            function.arity,
+            function.slot_names.len()
+        );
+        let mut last_line = 0;
+        for (address, instruction) in function.code.iter().enumerate() {
+            // 与上一条指令在同一源代码行时用"|"代替行号
+            let line = function.spans[address].line;
+            let line_column = if line == last_line { "   |".to_string() } else { format!("{:4}", line) };
+            last_line = line;
+            let detail = match instruction {
+                Instruction::Constant(i) => format!("CONST        {:<4} ({})", i, program.constants[*i]),
+                Instruction::LoadLocal(slot) => format!("LOAD_LOCAL   {:<4} ({})", slot, function.slot_names[*slot]),
+                Instruction::StoreLocal(slot) => format!("STORE_LOCAL  {:<4} ({})", slot, function.slot_names[*slot]),
+                Instruction::Pop => "POP".to_string(),
+                Instruction::Binary(op) => format!("BINARY       {:?}", op),
+                Instruction::Negate => "NEGATE".to_string(),
+                Instruction::Not => "NOT".to_string(),
+                Instruction::ToFloat => "TO_FLOAT".to_string(),
+                Instruction::StrLen => "STR_LEN".to_string(),
+                Instruction::StrChar => "STR_CHAR".to_string(),
+                Instruction::Jump(target) => format!("JUMP         -> {:04}", target),
+                Instruction::JumpIfFalse(target) => format!("JUMP_IF_FALSE -> {:04}", target),
+                Instruction::Print(n) => format!("PRINT        {}", n),
+                Instruction::Println => "PRINTLN".to_string(),
+                Instruction::Call(id, argc) => format!("CALL         #{} {} ({}个参数)", id, program.functions[*id].name, argc),
+                Instruction::Return => "RETURN".to_string(),
+            };
+            let _ = writeln!(listing, "{:04} {}  {}", address, line_column, detail);
+        }
+    }
+    listing
+}
@@ -0,0 +1,224 @@
+// 编译缓存：按生成的Rust代码、rustc版本和编译参数的哈希保存编译好的可执行文件
+// 重新运行没有修改的程序时直接使用缓存，跳过rustc编译；缓存总大小超过上限时删除最久没有使用的文件
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::OnceLock;
+use std::time::SystemTime;
+use tracing::debug;
+
+// 指定缓存目录的环境变量
+pub const CACHE_DIR_ENV: &str = "NEXA_CACHE_DIR";
+// 默认的缓存大小上限（字节）
+pub const DEFAULT_MAX_SIZE: u64 = 256 * 1024 * 1024;
+
+// 写入缓存时临时文件的序号，避免并发写入同一个临时文件
+static NEXT_TEMP_FILE: AtomicU64 = AtomicU64::new(0);
+
+// FNV-1a 128位哈希，结果在不同平台和Rust版本之间保持不变
+fn fnv1a_128(parts: &[&[u8]]) -> u128 {
+    const OFFSET: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+    let mut hash = OFFSET;
+    for part in parts {
+        // 每部分前面加上长度，避免不同的拆分方式得到相同的哈希
+        for byte in (part.len() as u64).to_le_bytes().iter().chain(part.iter()) {
+            hash ^= *byte as u128;
+            hash = hash.wrapping_mul(PRIME);
+        }
+    }
+    hash
+}
+
+// 缓存键：生成的代码、rustc版本和编译参数共同决定
+pub fn cache_key(code: &str, rustc_version: &str, flags: &[&str]) -> String {
+    let flags = flags.join("\0");
+    format!("{:032x}", fnv1a_128(&[code.as_bytes(), rustc_version.as_bytes(), flags.as_bytes()]))
+}
+
+// 当前rustc的完整版本信息（rustc -vV），只查询一次
+pub fn rustc_version() -> Result<&'static str, String> {
+    static VERSION: OnceLock<Result<String, String>> = OnceLock::new();
+    VERSION
+        .get_or_init(|| {
+            let output = Command::new("rustc")
+                .arg("-vV")
+                .output()
+                .map_err(|e| format!("编译命令执行错误: {}", e))?;
+            if !output.status.success() {
+                return Err(format!("查询rustc版本错误: {}", String::from_utf8_lossy(&output.stderr)));
+            }
+            Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+        })
+        .as_deref()
+        .map_err(Clone::clone)
+}
+
+// 默认的缓存目录：NEXA_CACHE_DIR，其次是用户缓存目录下的nexa，最后是系统临时目录
+pub fn default_dir() -> PathBuf {
+    let non_empty = |name| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    if let Some(dir) = non_empty(CACHE_DIR_ENV) {
+        return dir;
+    }
+    if let Some(dir) = non_empty("XDG_CACHE_HOME") {
+        return dir.join("nexa");
+    }
+    if let Some(home) = non_empty("HOME") {
+        return home.join(".cache").join("nexa");
+    }
+    std::env::temp_dir().join("nexa-cache")
+}
+
+// 清理缓存的结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CleanStats {
+    // 删除的文件数
+    pub files: usize,
+    // 释放的字节数
+    pub bytes: u64,
+}
+
+// 编译缓存
+#[derive(Debug, Clone)]
+pub struct CompileCache {
+    dir: PathBuf,
+    max_size: u64,
+}
+
+impl Default for CompileCache {
+    fn default() -> Self {
+        CompileCache::new(default_dir(), DEFAULT_MAX_SIZE)
+    }
+}
+
+impl CompileCache {
+    // 使用指定目录和大小上限创建缓存，目录在第一次写入时创建
+    pub fn new(dir: impl Into<PathBuf>, max_size: u64) -> Self {
+        CompileCache { dir: dir.into(), max_size }
+    }
+
+    // 缓存目录
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    // 取得key对应的可执行文件，不存在时调用compile编译到给定路径并加入缓存
+    // 返回缓存中可执行文件的路径
+    pub fn get_or_compile<F>(&self, key: &str, compile: F) -> Result<PathBuf, String>
+    where
+        F: FnOnce(&Path) -> Result<(), String>,
+    {
+        let path = self.dir.join(key);
+        if path.is_file() {
+            debug!(target: "exec", "编译缓存命中: {}", key);
+            // 更新修改时间，淘汰时按最近使用排序
+            if let Ok(file) = File::options().append(true).open(&path) {
+                let _ = file.set_modified(SystemTime::now());
+            }
+            return Ok(path);
+        }
+
+        debug!(target: "exec", "编译缓存未命中: {}", key);
+        fs::create_dir_all(&self.dir).map_err(|e| format!("创建缓存目录错误: {}", e))?;
+        // 先编译到临时文件再重命名，其他进程不会看到写了一半的文件
+        let sequence = NEXT_TEMP_FILE.fetch_add(1, Ordering::Relaxed);
+        let temp = self.dir.join(format!("{}.tmp-{}-{}", key, std::process::id(), sequence));
+        let compiled = compile(&temp).and_then(|_| {
+            fs::rename(&temp, &path).map_err(|e| format!("写入编译缓存错误: {}", e))
+        });
+        if let Err(e) = compiled {
+            let _ = fs::remove_file(&temp);
+            return Err(e);
+        }
+        self.evict(&path);
+        Ok(path)
+    }
+
+    // 缓存中的文件及其大小和修改时间
+    fn entries(&self) -> Vec<(PathBuf, u64, SystemTime)> {
+        let Ok(read_dir) = fs::read_dir(&self.dir) else {
+            return Vec::new();
+        };
+        read_dir
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+                let modified = metadata.modified().unwrap_or(SystemTime::UNIX_EPOCH);
+                Some((entry.path(), metadata.len(), modified))
+            })
+            .collect()
+    }
+
+    // 缓存总大小超过上限时，从最久没有使用的文件开始删除，刚写入的keep保留
+    fn evict(&self, keep: &Path) {
+        let mut entries = self.entries();
+        let mut total: u64 = entries.iter().map(|(_, size, _)| size).sum();
+        entries.sort_by_key(|(_, _, modified)| *modified);
+        for (path, size, _) in entries {
+            if total <= self.max_size {
+                break;
+            }
+            if path != keep && fs::remove_file(&path).is_ok() {
+                debug!(target: "exec", "淘汰编译缓存: {}", path.display());
+                total -= size;
+            }
+        }
+    }
+
+    // 删除缓存中的所有文件
+    pub fn clean(&self) -> Result<CleanStats, String> {
+        let mut stats = CleanStats::default();
+        for (path, size, _) in self.entries() {
+            fs::remove_file(&path).map_err(|e| format!("删除缓存文件 {} 错误: {}", path.display(), e))?;
+            stats.files += 1;
+            stats.bytes += size;
+        }
+        Ok(stats)
+    }
+}
+
+// 测试模块：编译缓存单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::WorkDir;
+    use std::cell::Cell;
+
+    // 测试缓存键由代码、rustc版本和编译参数共同决定
+    #[test]
+    fn test_cache_key() {
+        let key = cache_key("fn main() {}", "rustc 1.0", &["-O"]);
+        assert_eq!(key.len(), 32);
+        assert_eq!(key, cache_key("fn main() {}", "rustc 1.0", &["-O"]));
+        assert_ne!(key, cache_key("fn main() { }", "rustc 1.0", &["-O"]));
+        assert_ne!(key, cache_key("fn main() {}", "rustc 1.1", &["-O"]));
+        assert_ne!(key, cache_key("fn main() {}", "rustc 1.0", &[]));
+    }
+
+    // 测试命中时不重新编译、超过大小上限时淘汰最旧的文件以及清理缓存
+    #[test]
+    fn test_get_or_compile_evict_and_clean() {
+        let dir = WorkDir::new().unwrap();
+        let cache = CompileCache::new(dir.path.join("cache"), 10);
+        let compiles = Cell::new(0);
+        let compile = |content: &'static str| {
+            let compiles = &compiles;
+            move |path: &Path| {
+                compiles.set(compiles.get() + 1);
+                fs::write(path, content).map_err(|e| e.to_string())
+            }
+        };
+
+        let first = cache.get_or_compile("a", compile("123456")).unwrap();
+        assert_eq!(cache.get_or_compile("a", compile("123456")).unwrap(), first);
+        assert_eq!(compiles.get(), 1);
+        assert!(cache.get_or_compile("bad", |_| Err("编译错误".to_string())).is_err());
+
+        // 加入b后总大小超过10字节，最旧的a被淘汰
+        let second = cache.get_or_compile("b", compile("abcdef")).unwrap();
+        assert!(!first.exists() && second.exists());
+        assert_eq!(cache.clean().unwrap(), CleanStats { files: 1, bytes: 6 });
+        assert!(!second.exists());
+    }
+}
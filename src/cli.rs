@@ -0,0 +1,213 @@
+// 命令行参数解析：把命令行参数转换为要执行的子命令
+// 没有子命令时启动交互式环境（REPL），子命令可以在脚本和Makefile中使用
+use nexa_lang::Backend;
+use std::path::{Path, PathBuf};
+
+// Web编辑器的默认端口
+pub const DEFAULT_PORT: u16 = 8080;
+
+// 用法说明，在 nexa help 和参数错误时显示
+pub const USAGE: &str = "用法: nexa [选项] [子命令]
+
+子命令:
+  run <文件> [--backend interp|vm|rustc]  运行Nexa程序（默认使用解释器）
+  build <文件> [-o <输出文件>]            编译为可执行文件（默认去掉源文件的扩展名）
+  check <文件>                           只进行语法、名称和类型检查
+  tokens <文件>                          打印词法单元
+  ast <文件>                             打印抽象语法树
+  emit-rust <文件>                       打印生成的Rust代码
+  emit-bytecode <文件>                   打印字节码清单
+  web [--port <端口>]                    启动Web编辑器（默认端口8080）
+  cache clean                            清空编译缓存（目录可用NEXA_CACHE_DIR指定）
+  lsp                                    通过标准输入输出启动语言服务器
+  help                                   显示此帮助信息
+不带子命令时启动交互式环境
+
+选项:
+  --log <规则>    日志过滤规则，例如 lexer=trace,codegen=debug（也可以用NEXA_LOG环境变量）
+  -v, -vv         打开所有阶段的调试日志 / 跟踪日志
+
+退出码: 0 成功，1 编译或运行错误，2 命令行参数错误";
+
+// 子命令
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    // 启动交互式环境
+    Repl,
+    // 显示用法说明
+    Help,
+    // 运行程序
+    Run { file: String, backend: Backend },
+    // 编译为可执行文件
+    Build { file: String, output: PathBuf },
+    // 只检查程序
+    Check { file: String },
+    // 打印词法单元
+    Tokens { file: String },
+    // 打印抽象语法树
+    Ast { file: String },
+    // 打印生成的Rust代码
+    EmitRust { file: String },
+    // 打印字节码清单
+    EmitBytecode { file: String },
+    // 启动Web编辑器
+    Web { port: u16 },
+    // 清空编译缓存
+    CacheClean,
+    // 启动语言服务器
+    Lsp,
+}
+
+// 判断两个路径是否指向同一个已存在的文件
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+// 取出子命令的文件参数，文件参数必须正好有一个
+fn single_file(command: &str, rest: &[String]) -> Result<String, String> {
+    match rest {
+        [file] if !file.starts_with('-') => Ok(file.clone()),
+        [] => Err(format!("{} 需要一个文件参数", command)),
+        _ => Err(format!("{} 的参数无效: {}", command, rest.join(" "))),
+    }
+}
+
+// 解析--backend选项的值
+fn parse_backend(name: &str) -> Result<Backend, String> {
+    match name {
+        "interp" | "interpreter" => Ok(Backend::Interpreter),
+        "vm" => Ok(Backend::Vm),
+        "rustc" => Ok(Backend::Rustc),
+        _ => Err(format!("未知的后端: {}（可选 interp、vm、rustc）", name)),
+    }
+}
+
+// 解析命令行参数（不含程序名和日志参数），参数错误时返回错误信息
+pub fn parse_args(args: &[String]) -> Result<Command, String> {
+    let Some((command, rest)) = args.split_first() else {
+        return Ok(Command::Repl);
+    };
+    match command.as_str() {
+        "help" | "--help" | "-h" => Ok(Command::Help),
+        "run" => {
+            let mut backend = Backend::Interpreter;
+            let mut files = Vec::new();
+            let mut i = 0;
+            while i < rest.len() {
+                if rest[i] == "--backend" {
+                    let name = rest.get(i + 1).ok_or("--backend 需要一个值")?;
+                    backend = parse_backend(name)?;
+                    i += 2;
+                } else {
+                    files.push(rest[i].clone());
+                    i += 1;
+                }
+            }
+            Ok(Command::Run { file: single_file("run", &files)?, backend })
+        },
+        "build" => {
+            let mut output = None;
+            let mut files = Vec::new();
+            let mut i = 0;
+            while i < rest.len() {
+                if rest[i] == "-o" {
+                    let path = rest.get(i + 1).ok_or("-o 需要一个输出文件")?;
+                    output = Some(PathBuf::from(path));
+                    i += 2;
+                } else {
+                    files.push(rest[i].clone());
+                    i += 1;
+                }
+            }
+            let file = single_file("build", &files)?;
+            // 默认输出到当前目录下与源文件同名（去掉扩展名）的可执行文件
+            // 源文件没有扩展名时加上.out，避免覆盖源文件
+            let output = match output {
+                Some(output) => output,
+                None => {
+                    let source = Path::new(&file);
+                    let stem = source.file_stem().ok_or("无效的文件名")?;
+                    match source.extension() {
+                        Some(_) => PathBuf::from(stem),
+                        None => PathBuf::from(stem).with_extension("out"),
+                    }
+                },
+            };
+            if same_file(Path::new(&file), &output) {
+                return Err(format!("输出文件 {} 与源文件相同", output.display()));
+            }
+            Ok(Command::Build { file, output })
+        },
+        "check" => Ok(Command::Check { file: single_file("check", rest)? }),
+        "tokens" => Ok(Command::Tokens { file: single_file("tokens", rest)? }),
+        "ast" => Ok(Command::Ast { file: single_file("ast", rest)? }),
+        "emit-rust" => Ok(Command::EmitRust { file: single_file("emit-rust", rest)? }),
+        "emit-bytecode" => Ok(Command::EmitBytecode { file: single_file("emit-bytecode", rest)? }),
+        // 兼容旧的 --emit bytecode <文件> 写法
+        "--emit" => match rest {
+            [kind, file] if kind == "bytecode" => Ok(Command::EmitBytecode { file: file.clone() }),
+            _ => Err("--emit 只支持 bytecode".to_string()),
+        },
+        "web" => match rest {
+            [] => Ok(Command::Web { port: DEFAULT_PORT }),
+            [flag, port] if flag == "--port" => {
+                let port = port.parse().map_err(|_| format!("无效的端口: {}", port))?;
+                Ok(Command::Web { port })
+            },
+            _ => Err(format!("web 的参数无效: {}", rest.join(" "))),
+        },
+        "lsp" => match rest {
+            [] => Ok(Command::Lsp),
+            _ => Err(format!("lsp 的参数无效: {}", rest.join(" "))),
+        },
+        "cache" => match rest {
+            [action] if action == "clean" => Ok(Command::CacheClean),
+            _ => Err("cache 只支持 clean".to_string()),
+        },
+        _ => Err(format!("未知的子命令: {}", command)),
+    }
+}
+
+// 测试模块：命令行参数解析单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 辅助函数：解析空格分隔的参数
+    fn parse(line: &str) -> Result<Command, String> {
+        let args: Vec<String> = line.split_whitespace().map(String::from).collect();
+        parse_args(&args)
+    }
+
+    // 测试各个子命令
+    #[test]
+    fn test_subcommands() {
+        assert_eq!(parse(""), Ok(Command::Repl));
+        assert_eq!(parse("run a.nexa"), Ok(Command::Run { file: "a.nexa".into(), backend: Backend::Interpreter }));
+        assert_eq!(parse("run --backend vm a.nexa"), Ok(Command::Run { file: "a.nexa".into(), backend: Backend::Vm }));
+        assert_eq!(parse("build src/a.nexa"), Ok(Command::Build { file: "src/a.nexa".into(), output: PathBuf::from("a") }));
+        assert_eq!(parse("build a.nexa -o out/app"), Ok(Command::Build { file: "a.nexa".into(), output: PathBuf::from("out/app") }));
+        assert_eq!(parse("build bin/prog"), Ok(Command::Build { file: "bin/prog".into(), output: PathBuf::from("prog.out") }));
+        assert_eq!(parse("emit-rust a.nexa"), Ok(Command::EmitRust { file: "a.nexa".into() }));
+        assert_eq!(parse("--emit bytecode a.nexa"), Ok(Command::EmitBytecode { file: "a.nexa".into() }));
+        assert_eq!(parse("web --port 3000"), Ok(Command::Web { port: 3000 }));
+        assert_eq!(parse("cache clean"), Ok(Command::CacheClean));
+        assert_eq!(parse("lsp"), Ok(Command::Lsp));
+    }
+
+    // 测试参数错误
+    #[test]
+    fn test_invalid_arguments() {
+        assert!(parse("check").is_err());
+        assert!(parse("tokens a.nexa b.nexa").is_err());
+        assert!(parse("run --backend jit a.nexa").is_err());
+        assert!(parse("web --port http").is_err());
+        assert!(parse("compile a.nexa").is_err());
+        assert!(parse("cache").is_err());
+        // 输出文件不能覆盖源文件
+        assert!(parse("build Cargo.toml -o ./Cargo.toml").is_err());
+    }
+}
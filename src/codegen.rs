@@ -1,5 +1,153 @@
 // 导入解析器模块中的类型定义
-use crate::parser::{DataType, Expr, Operator, Statement};
+use crate::parser::{DataType, Expr, ExprKind, Operator, Statement, StatementKind};
+// 导入诊断信息和位置类型，用于报告代码生成错误
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Span;
+// 导入HashMap，用于记录函数签名
+use std::collections::HashMap;
+use std::fmt;
+use tracing::{debug, trace};
+
+// 代码生成错误：每种错误带有出错位置和稳定的错误代码（E02xx）
+#[derive(Debug, Clone, PartialEq)]
+pub enum CodegenError {
+    // E0201 无法生成Rust代码的运算符
+    UnsupportedOperator { op: Operator, span: Span },
+}
+
+impl CodegenError {
+    // 稳定的错误代码，工具可以据此匹配错误种类
+    pub fn code(&self) -> &'static str {
+        match self {
+            CodegenError::UnsupportedOperator { .. } => "E0201",
+        }
+    }
+
+    // 出错的源代码位置
+    pub fn span(&self) -> Span {
+        match self {
+            CodegenError::UnsupportedOperator { span, .. } => *span,
+        }
+    }
+
+    // 错误消息（不含位置）
+    pub fn message(&self) -> String {
+        match self {
+            CodegenError::UnsupportedOperator { op, .. } => format!("不支持的一元运算符: {:?}", op),
+        }
+    }
+}
+
+impl From<CodegenError> for Diagnostic {
+    fn from(error: CodegenError) -> Self {
+        Diagnostic::error(error.message(), error.span()).with_code(error.code())
+    }
+}
+
+// 以诊断的单行格式显示
+impl fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Diagnostic::from(self.clone()))
+    }
+}
+
+// 将Nexa数据类型映射为对应的Rust类型
+fn rust_type(data_type: &DataType) -> &'static str {
+    match data_type {
+        DataType::Int => "i64",
+        DataType::Float => "f64",
+        DataType::Bool => "bool",
+        DataType::String => "String",
+    }
+}
+
+// 函数签名：参数类型和返回类型
+struct FunctionSignature {
+    params: Vec<DataType>,
+    return_type: Option<DataType>,
+}
+
+// 判断运算符是否是算术或比较运算（两侧需要统一为相同的数值类型）
+fn is_numeric_operator(op: &Operator) -> bool {
+    matches!(
+        op,
+        Operator::Add
+            | Operator::Sub
+            | Operator::Mul
+            | Operator::Div
+            | Operator::Mod
+            | Operator::FloorDiv
+            | Operator::Pow
+            | Operator::Eq
+            | Operator::Ne
+            | Operator::Lt
+            | Operator::Le
+            | Operator::Gt
+            | Operator::Ge
+    )
+}
+
+// 生成浮点数字面量：Debug格式总是保留小数点或指数（如1.0、0.001、1e-7），是合法的Rust f64字面量
+fn float_literal(x: f64) -> String {
+    format!("{:?}", x)
+}
+
+// 生成代码使用的运行时辅助函数，只在程序用到时输出到代码开头
+// 除数为零时输出错误并以退出码1结束程序
+const ZERO_DIVISION_HELPER: &str = "fn nexa_zero_division() -> ! {
+    eprintln!(\"运行时错误: 除数不能为零\");
+    std::process::exit(1)
+}
+";
+// 整数除法：除数为零时报告错误，而不是让Rust程序panic
+const DIV_I64_HELPER: &str = "fn nexa_div_i64(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        nexa_zero_division()
+    }
+    a / b
+}
+";
+// 取余和整除向负无穷取整（与Rust的%和/不同）：-7 // 2 为 -4，-7 % 2 为 1
+const MOD_I64_HELPER: &str = "fn nexa_mod_i64(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        nexa_zero_division()
+    }
+    let r = a % b;
+    if r != 0 && (r < 0) != (b < 0) { r + b } else { r }
+}
+";
+const FLOOR_DIV_I64_HELPER: &str = "fn nexa_floor_div_i64(a: i64, b: i64) -> i64 {
+    if b == 0 {
+        nexa_zero_division()
+    }
+    let q = a / b;
+    if a % b != 0 && (a < 0) != (b < 0) { q - 1 } else { q }
+}
+";
+const MOD_F64_HELPER: &str = "fn nexa_mod_f64(a: f64, b: f64) -> f64 {
+    if b == 0.0 {
+        nexa_zero_division()
+    }
+    let r = a % b;
+    if r != 0.0 && (r < 0.0) != (b < 0.0) { r + b } else { r }
+}
+";
+const FLOOR_DIV_F64_HELPER: &str = "fn nexa_floor_div_f64(a: f64, b: f64) -> f64 {
+    if b == 0.0 {
+        nexa_zero_division()
+    }
+    (a / b).floor()
+}
+";
+// 整数乘方：指数为负数时是运行时错误
+const POW_I64_HELPER: &str = "fn nexa_pow_i64(a: i64, b: i64) -> i64 {
+    if b < 0 {
+        eprintln!(\"运行时错误: 整数的乘方指数不能为负数\");
+        std::process::exit(1)
+    }
+    a.pow(b as u32)
+}
+";
 
 // 代码生成器结构体：将抽象语法树转换为Rust代码
 pub struct CodeGenerator {
@@ -7,15 +155,33 @@ pub struct CodeGenerator {
     code: String,
     // 当前的缩进级别，用于格式化输出
     indent_level: usize,
+    // 已定义函数的签名，用于在调用处转换实参和推断调用结果的类型
+    functions: HashMap<String, FunctionSignature>,
+    // 变量类型作用域栈：每个代码块一层，用于推断表达式类型
+    scopes: Vec<HashMap<String, DataType>>,
+    // 当前正在生成的函数的返回类型，用于转换return的值
+    return_type: Option<DataType>,
+    // 用到的运行时辅助函数，按首次使用的顺序输出
+    helpers: Vec<&'static str>,
+}
+
+impl Default for CodeGenerator {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // 代码生成器实现块
 impl CodeGenerator {
     // 创建新的代码生成器实例
-    fn new() -> Self {
+    pub fn new() -> Self {
         CodeGenerator {
             code: String::new(),    // 初始化空字符串用于存储代码
             indent_level: 0,        // 初始缩进级别为0
+            functions: HashMap::new(), // 函数签名在生成前统一收集
+            scopes: vec![HashMap::new()], // 顶层代码的变量作用域
+            return_type: None,      // 顶层代码不在任何函数内
+            helpers: Vec::new(),    // 用到时再加入运行时辅助函数
         }
     }
     
@@ -36,221 +202,324 @@ impl CodeGenerator {
         }
     }
     
+    // 记录程序用到的运行时辅助函数
+    fn use_helper(&mut self, helper: &'static str) {
+        if !self.helpers.contains(&helper) {
+            self.helpers.push(helper);
+        }
+    }
+    
+    // 记录变量的类型（类型未知时不记录）
+    fn declare_var(&mut self, name: &str, data_type: Option<DataType>) {
+        let scope = self.scopes.last_mut().unwrap();
+        match data_type {
+            Some(dt) => {
+                scope.insert(name.to_string(), dt);
+            },
+            None => {
+                scope.remove(name);
+            },
+        }
+    }
+    
+    // 查找变量的类型，从最内层作用域开始
+    fn lookup_var(&self, name: &str) -> Option<DataType> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name).cloned())
+    }
+    
+    // 推断表达式的类型，无法确定时返回None
+    // 用于在整数和浮点数混合运算时插入类型转换
+    fn infer_type(&self, expr: &Expr) -> Option<DataType> {
+        match &expr.kind {
+            ExprKind::Number(_) => Some(DataType::Int),
+            ExprKind::Float(_) => Some(DataType::Float),
+            ExprKind::String(_) => Some(DataType::String),
+            ExprKind::BoolLiteral(_) => Some(DataType::Bool),
+            ExprKind::Identifier(name) => self.lookup_var(name),
+            ExprKind::BinaryOp(left, op, right) => match op {
+                Operator::Eq | Operator::Ne | Operator::Lt | Operator::Le | Operator::Gt | Operator::Ge => Some(DataType::Bool),
+                Operator::And | Operator::Or | Operator::Not => Some(DataType::Bool),
+                _ => match (self.infer_type(left), self.infer_type(right)) {
+                    (Some(DataType::Float), _) | (_, Some(DataType::Float)) => Some(DataType::Float),
+                    (left, _) => left,
+                },
+            },
+            ExprKind::UnaryOp(_, operand) => self.infer_type(operand),
+            ExprKind::Range(..) => None,
+            ExprKind::Call(name, _) => self.functions.get(name).and_then(|f| f.return_type.clone()),
+        }
+    }
+    
+    // 在新的变量作用域中生成代码块内的语句
+    fn generate_block(&mut self, statements: &[Statement]) -> Result<(), CodegenError> {
+        self.increment_indent();
+        self.scopes.push(HashMap::new());
+        let result = statements.iter().try_for_each(|stmt| self.generate_statement(stmt));
+        self.scopes.pop();
+        self.decrement_indent();
+        result
+    }
+    
+    // 按目标类型生成表达式代码
+    // 用于变量初始化、赋值、函数实参、返回值和混合运算，使字面量和字符串符合Rust的类型要求
+    fn generate_value(&mut self, expr: &Expr, target: Option<&DataType>) -> Result<(), CodegenError> {
+        match (target, &expr.kind) {
+            // 整数赋值给Float类型时，需要添加.0使其成为浮点数字面量
+            (Some(DataType::Float), &ExprKind::Number(n)) => {
+                self.code.push_str(&n.to_string());
+                self.code.push_str(".0");
+            },
+            // 其他整数表达式赋值给Float类型时，用as f64转换
+            (Some(DataType::Float), _) if self.infer_type(expr) == Some(DataType::Int) => {
+                self.code.push('(');
+                self.generate_expression(expr)?;
+                self.code.push_str(" as f64)");
+            },
+            // 赋值给String类型时，需要添加.to_string()（&str和String都适用）
+            (Some(DataType::String), _) => {
+                self.code.push('(');
+                self.generate_expression(expr)?;
+                self.code.push_str(").to_string()");
+            },
+            // 其他情况直接生成表达式
+            _ => {
+                self.generate_expression(expr)?;
+            }
+        }
+        Ok(())
+    }
+    
     // 生成表达式代码
-    // 根据表达式类型生成对应的Rust代码
-    fn generate_expression(&mut self, expr: &Expr) {
-        match expr {
+    // 根据表达式类型生成对应的Rust代码，无法生成时返回带位置的错误信息
+    fn generate_expression(&mut self, expr: &Expr) -> Result<(), CodegenError> {
+        match &expr.kind {
             // 数字字面量：直接转换为字符串
-            Expr::Number(n) => {
+            ExprKind::Number(n) => {
                 self.code.push_str(&n.to_string());
             },
-            // 字符串字面量：直接使用双引号
-            Expr::String(s) => {
-                self.code.push('"');
-                self.code.push_str(s);
-                self.code.push('"');
+            // 浮点数字面量
+            ExprKind::Float(x) => {
+                self.code.push_str(&float_literal(*x));
+            },
+            // 字符串字面量：转义序列已经由词法分析器处理，按Rust字符串字面量的格式重新转义
+            ExprKind::String(s) => {
+                self.code.push_str(&format!("{:?}", s));
             },
             // 布尔字面量：直接生成 true 或 false
-            Expr::BoolLiteral(b) => {
+            ExprKind::BoolLiteral(b) => {
                 self.code.push_str(if *b { "true" } else { "false" });
             },
             // 标识符：直接使用变量名
-            Expr::Identifier(id) => {
+            ExprKind::Identifier(id) => {
                 self.code.push_str(id);
             },
             // 二元运算表达式：生成 (left op right) 格式
-            Expr::BinaryOp(left, op, right) => {
+            // 整数与浮点数混合运算时，整数一侧转换为f64
+            ExprKind::BinaryOp(left, op, right) => {
+                let operand_type = match (self.infer_type(left), self.infer_type(right)) {
+                    (Some(DataType::Float), Some(DataType::Int)) | (Some(DataType::Int), Some(DataType::Float))
+                        if is_numeric_operator(op) =>
+                    {
+                        Some(DataType::Float)
+                    },
+                    _ => None,
+                };
+                // 任意一侧是浮点数时按浮点数运算
+                let float = self.infer_type(left) == Some(DataType::Float) || self.infer_type(right) == Some(DataType::Float);
+                if matches!(op, Operator::Mod | Operator::FloorDiv | Operator::Pow) {
+                    return self.generate_floor_operation(op, left, right, float);
+                }
+                // 整数除法通过辅助函数检查除数为零
+                if *op == Operator::Div && !float {
+                    return self.generate_floor_operation(op, left, right, false);
+                }
                 self.code.push('(');
-                self.generate_expression(left);
+                self.generate_value(left, operand_type.as_ref())?;
                 self.code.push(' ');
                 
                 // 根据运算符类型生成对应的Rust运算符
                 match op {
-                    Operator::Add => self.code.push_str("+"),
-                    Operator::Sub => self.code.push_str("-"),
-                    Operator::Mul => self.code.push_str("*"),
-                    Operator::Div => self.code.push_str("/"),
+                    Operator::Add => self.code.push('+'),
+                    Operator::Sub => self.code.push('-'),
+                    Operator::Mul => self.code.push('*'),
+                    Operator::Div => self.code.push('/'),
+                    Operator::Mod | Operator::FloorDiv | Operator::Pow => unreachable!("由generate_floor_operation生成"),
                     Operator::Eq => self.code.push_str("=="),
-                    Operator::Lt => self.code.push_str("<"),
+                    Operator::Lt => self.code.push('<'),
                     Operator::Le => self.code.push_str("<="),
-                    Operator::Gt => self.code.push_str(">"),
+                    Operator::Gt => self.code.push('>'),
                     Operator::Ge => self.code.push_str(">="),
+                    Operator::Ne => self.code.push_str("!="),
+                    // Rust的&&和||本身就是短路求值
+                    Operator::And => self.code.push_str("&&"),
+                    Operator::Or => self.code.push_str("||"),
+                    Operator::Not => return Err(CodegenError::UnsupportedOperator { op: op.clone(), span: expr.span }),
                 }
                 
                 self.code.push(' ');
-                self.generate_expression(right);
+                self.generate_value(right, operand_type.as_ref())?;
                 self.code.push(')');
             },
-            // 一元运算表达式：目前只支持负号
-            Expr::UnaryOp(op, expr) => {
+            // 一元运算表达式：负号和逻辑非
+            // 操作数也是一元运算时加括号，- -a 生成 -(-a) 而不是 --a
+            ExprKind::UnaryOp(op, operand) => {
                 match op {
                     Operator::Sub => self.code.push('-'),
-                    _ => panic!("不支持的一元运算符: {:?}", op),
+                    Operator::Not => self.code.push('!'),
+                    _ => return Err(CodegenError::UnsupportedOperator { op: op.clone(), span: expr.span }),
+                }
+                if let ExprKind::UnaryOp(..) = operand.kind {
+                    self.code.push('(');
+                    self.generate_expression(operand)?;
+                    self.code.push(')');
+                } else {
+                    self.generate_expression(operand)?;
                 }
-                self.generate_expression(expr);
             },
             // 范围表达式：生成start..=end格式
-            Expr::Range(start, end) => {
-                self.generate_expression(&**start);
+            ExprKind::Range(start, end) => {
+                self.generate_expression(start)?;
                 self.code.push_str("..=");
-                self.generate_expression(&**end);
+                self.generate_expression(end)?;
             },
+            // 函数调用：name(arg1, arg2, ...)，实参按形参类型转换
+            ExprKind::Call(name, arguments) => {
+                let param_types = self.functions.get(name).map(|f| f.params.clone()).unwrap_or_default();
+                self.code.push_str(name);
+                self.code.push('(');
+                for (i, argument) in arguments.iter().enumerate() {
+                    if i > 0 {
+                        self.code.push_str(", ");
+                    }
+                    self.generate_value(argument, param_types.get(i))?;
+                }
+                self.code.push(')');
+            },
+        }
+        Ok(())
+    }
+    
+    // 生成整数除法、取余、整除和乘方：调用运行时辅助函数，浮点数乘方使用f64::powf
+    // float为true时两侧都按f64生成
+    fn generate_floor_operation(&mut self, op: &Operator, left: &Expr, right: &Expr, float: bool) -> Result<(), CodegenError> {
+        let (function, helper) = match (op, float) {
+            (Operator::Div, _) => ("nexa_div_i64", Some(DIV_I64_HELPER)),
+            (Operator::Mod, false) => ("nexa_mod_i64", Some(MOD_I64_HELPER)),
+            (Operator::Mod, true) => ("nexa_mod_f64", Some(MOD_F64_HELPER)),
+            (Operator::FloorDiv, false) => ("nexa_floor_div_i64", Some(FLOOR_DIV_I64_HELPER)),
+            (Operator::FloorDiv, true) => ("nexa_floor_div_f64", Some(FLOOR_DIV_F64_HELPER)),
+            (Operator::Pow, false) => ("nexa_pow_i64", Some(POW_I64_HELPER)),
+            _ => ("f64::powf", None),
+        };
+        if matches!(op, Operator::Div | Operator::Mod | Operator::FloorDiv) {
+            self.use_helper(ZERO_DIVISION_HELPER);
+        }
+        if let Some(helper) = helper {
+            self.use_helper(helper);
         }
+        let operand_type = if float { Some(DataType::Float) } else { None };
+        self.code.push_str(function);
+        self.code.push('(');
+        self.generate_value(left, operand_type.as_ref())?;
+        self.code.push_str(", ");
+        self.generate_value(right, operand_type.as_ref())?;
+        self.code.push(')');
+        Ok(())
     }
     
     // 生成语句代码
     // 根据语句类型生成对应的Rust代码
-    fn generate_statement(&mut self, stmt: &Statement) {
-        match stmt {
+    fn generate_statement(&mut self, stmt: &Statement) -> Result<(), CodegenError> {
+        match &stmt.kind {
             // 变量声明语句：var name [: type] = expression;
-            Statement::Var(name, data_type, expr) => {
+            StatementKind::Var(name, data_type, expr) => {
                 self.indent();
                 self.code.push_str("let mut ");
                 self.code.push_str(name);
                 if let Some(dt) = data_type {
                     self.code.push_str(": ");
-                    let type_str = match dt {
-                        DataType::Int => "i32",
-                        DataType::Float => "f64",
-                        DataType::Bool => "bool",
-                        DataType::String => "String",
-                    };
-                    self.code.push_str(type_str);
+                    self.code.push_str(rust_type(dt));
                 }
                 self.code.push_str(" = ");
                 
                 // 根据表达式类型和目标类型进行适当的转换
-                match (data_type, expr.as_ref()) {
-                    // 整数赋值给Float类型时，需要添加.0使其成为浮点数字面量
-                    (Some(DataType::Float), &Expr::Number(n)) => {
-                        self.code.push_str(&n.to_string());
-                        self.code.push_str(".0");
-                    },
-                    // 字符串字面量赋值给String类型时，需要添加.to_string()
-                    (Some(DataType::String), &Expr::String(_)) => {
-                        self.code.push_str("(");
-                        self.generate_expression(expr);
-                        self.code.push_str(").to_string()");
-                    },
-                    // 其他情况直接生成表达式
-                    _ => {
-                        self.generate_expression(expr);
-                    }
-                }
+                self.generate_value(expr, data_type.as_ref())?;
                 
                 self.code.push_str(";\n");
+                
+                // 记录变量类型：有类型注解时使用注解，否则根据初始值推断
+                let var_type = data_type.clone().or_else(|| self.infer_type(expr));
+                self.declare_var(name, var_type);
             },
             // 变量赋值语句：name = expression;
-            Statement::Assign(name, expr) => {
+            StatementKind::Assign(name, expr) => {
                 self.indent();
                 self.code.push_str(name);
                 self.code.push_str(" = ");
-                self.generate_expression(expr);
+                let var_type = self.lookup_var(name);
+                self.generate_value(expr, var_type.as_ref())?;
                 self.code.push_str(";\n");
             },
             // 表达式语句：expression;
-            Statement::Expression(expr) => {
+            StatementKind::Expression(expr) => {
                 self.indent();
-                self.generate_expression(expr);
+                self.generate_expression(expr)?;
                 self.code.push_str(";\n");
             },
-            // 打印语句：print!(...) 或 print!("{}", ...) - 不换行输出
-            Statement::Print(expr) => {
+            // 打印语句：print!("{} {}", ...) - 不换行输出，多个参数用空格分隔
+            StatementKind::Print(exprs) => {
                 self.indent();
-                self.code.push_str("print!");
                 
-                // 检查是否是二元操作表达式（多参数合并的结果）
-                match &**expr {
-                    // 如果是二元操作表达式，生成多个参数的格式
-                    Expr::BinaryOp(_, _, _) => {
-                        // 收集所有参数
-                        fn collect_params<'a>(expr: &'a Expr, params: &mut Vec<&'a Expr>) {
-                            match expr {
-                                Expr::BinaryOp(left, _, right) => {
-                                    collect_params(left, params);
-                                    collect_params(right, params);
-                                },
-                                _ => params.push(expr),
-                            }
-                        }
-                        
-                        let mut params = Vec::new();
-                        collect_params(expr, &mut params);
-                        
-                        // 生成格式字符串（用空格分隔的{}）
-                        self.code.push_str("(\"");
-                        for i in 0..params.len() {
-                            if i > 0 {
-                                self.code.push_str(" ");
-                            }
-                            self.code.push_str("{}");
-                        }
-                        self.code.push_str("\"");
-                        
-                        // 生成所有参数，用逗号分隔
-                        for (_i, param) in params.iter().enumerate() {
-                            self.code.push_str(", ");
-                            self.generate_expression(param);
-                        }
-                        self.code.push(')');
-                    },
-                    // 如果是字符串字面量或字符串变量，直接使用括号形式
-                    Expr::String(_) => {
-                        self.code.push('(');
-                        self.generate_expression(expr);
-                        self.code.push(')');
-                    },
-                    // 数字字面量使用默认格式
-                    Expr::Number(_) => {
-                        self.code.push_str("(\"{}\", ");
-                        self.generate_expression(expr);
-                        self.code.push(')');
-                    },
-                    // 其他情况（主要是变量）使用默认格式
-                    _ => {
-                        self.code.push_str("(\"{}\", ");
-                        self.generate_expression(expr);
-                        self.code.push(')');
-                    }
+                // 生成格式字符串（用空格分隔的{}）
+                let placeholders = vec!["{}"; exprs.len()].join(" ");
+                self.code.push_str(&format!("print!(\"{}\"", placeholders));
+                
+                // 生成所有参数，用逗号分隔
+                for expr in exprs {
+                    self.code.push_str(", ");
+                    self.generate_expression(expr)?;
                 }
                 
-                self.code.push_str(";\n");
+                self.code.push_str(");\n");
             },
             // 换行打印语句：println!(expr1, expr2, ...)
             // 每个参数单独一行输出
-            Statement::Println(exprs) => {
+            StatementKind::Println(exprs) => {
                 for expr in exprs {
                     self.indent();
                     self.code.push_str("println!(\"{}\", ");
-                    self.generate_expression(expr);
+                    self.generate_expression(expr)?;
                     self.code.push_str(");\n");
                 }
             },
             // 条件语句：if condition { statements } [else { statements }]
-            Statement::If(condition, then_branch, else_branch) => {
+            StatementKind::If(condition, then_branch, else_branch) => {
                 // 生成if条件
                 self.indent();
                 self.code.push_str("if ");
-                self.generate_expression(condition);
+                self.generate_expression(condition)?;
                 self.code.push_str(" {\n");
                 
                 // 生成then分支
-                self.increment_indent();
-                for stmt in then_branch {
-                    self.generate_statement(stmt);
+                self.generate_block(then_branch)?;
+                
+                // else分支只有一个if语句（else if或elif）时生成平铺的else if链
+                let mut else_branch = else_branch.as_ref();
+                while let Some([Statement { kind: StatementKind::If(condition, then_branch, next), .. }]) = else_branch.map(Vec::as_slice) {
+                    self.indent();
+                    self.code.push_str("} else if ");
+                    self.generate_expression(condition)?;
+                    self.code.push_str(" {\n");
+                    self.generate_block(then_branch)?;
+                    else_branch = next.as_ref();
                 }
-                self.decrement_indent();
                 
                 // 生成可选的else分支
                 if let Some(else_branch) = else_branch {
                     self.indent();
                     self.code.push_str("} else {\n");
                     
-                    self.increment_indent();
-                    for stmt in else_branch {
-                        self.generate_statement(stmt);
-                    }
-                    self.decrement_indent();
+                    self.generate_block(else_branch)?;
                 }
                 
                 // 结束if语句
@@ -258,93 +527,275 @@ impl CodeGenerator {
                 self.code.push_str("}\n");
             },
             // For循环：for 变量 in 表达式 then statements end
-            Statement::For(var_name, iterable, body) => {
+            StatementKind::For(var_name, iterable, body) => {
                 // 根据迭代表达式类型生成不同的循环代码
                 self.indent();
                 self.code.push_str(&format!("for {} in ", var_name));
                 
-                match &**iterable {
+                match &iterable.kind {
                     // 范围表达式：生成start..=end格式
-                    Expr::Range(start, end) => {
-                        self.generate_expression(&**start);
+                    ExprKind::Range(start, end) => {
+                        self.generate_expression(start)?;
                         self.code.push_str("..=");
-                        self.generate_expression(&**end);
+                        self.generate_expression(end)?;
                     },
                     // 字符串表达式：生成.chars()迭代器
-                    Expr::String(_) | Expr::Identifier(_) => {
+                    ExprKind::String(_) | ExprKind::Identifier(_) => {
                         self.code.push('(');
-                        self.generate_expression(&**iterable);
+                        self.generate_expression(iterable)?;
                         self.code.push_str(").chars()");
                     },
                     // 其他表达式：直接使用（可能需要进一步处理）
                     _ => {
-                        self.generate_expression(&**iterable);
+                        self.generate_expression(iterable)?;
                     }
                 }
                 
                 self.code.push_str(" {\n");
                 
-                // 生成循环体
-                self.increment_indent();
-                for stmt in body {
-                    self.generate_statement(stmt);
-                }
-                self.decrement_indent();
+                // 生成循环体，范围循环的循环变量是整数
+                let var_type = matches!(iterable.kind, ExprKind::Range(..)).then_some(DataType::Int);
+                self.scopes.push(HashMap::new());
+                self.declare_var(var_name, var_type);
+                let result = self.generate_block(body);
+                self.scopes.pop();
+                result?;
                 
                 // 结束for循环
                 self.indent();
                 self.code.push_str("}\n");
             },
             // While循环：while condition { statements }
-            Statement::While(condition, body) => {
+            StatementKind::While(condition, body) => {
                 // 生成while条件
                 self.indent();
                 self.code.push_str("while ");
-                self.generate_expression(&**condition);
+                self.generate_expression(condition)?;
                 self.code.push_str(" {\n");
                 
                 // 生成循环体
-                self.increment_indent();
-                for stmt in body {
-                    self.generate_statement(stmt);
-                }
-                self.decrement_indent();
+                self.generate_block(body)?;
                 
                 // 结束while循环
                 self.indent();
                 self.code.push_str("}\n");
             },
+            // 函数定义：fn name(a: i64, b: String) -> i64 { statements }
+            // 嵌套在代码块中的函数会生成Rust的内部函数项，同样支持递归
+            StatementKind::Function(name, params, return_type, body) => {
+                self.indent();
+                self.code.push_str(&format!("fn {}(", name));
+                for (i, param) in params.iter().enumerate() {
+                    if i > 0 {
+                        self.code.push_str(", ");
+                    }
+                    self.code.push_str(&format!("{}: {}", param.name, rust_type(&param.data_type)));
+                }
+                self.code.push(')');
+                if let Some(dt) = return_type {
+                    self.code.push_str(" -> ");
+                    self.code.push_str(rust_type(dt));
+                }
+                self.code.push_str(" {\n");
+                
+                // 生成函数体，期间记录返回类型；函数体只能看到自己的参数
+                let outer_return_type = std::mem::replace(&mut self.return_type, return_type.clone());
+                let param_scope = params.iter().map(|p| (p.name.clone(), p.data_type.clone())).collect();
+                let outer_scopes = std::mem::replace(&mut self.scopes, vec![param_scope]);
+                let result = self.generate_block(body);
+                self.scopes = outer_scopes;
+                self.return_type = outer_return_type;
+                result?;
+                
+                // 结束函数定义
+                self.indent();
+                self.code.push_str("}\n");
+            },
+            // 返回语句：return [expression];
+            StatementKind::Return(value) => {
+                self.indent();
+                self.code.push_str("return");
+                if let Some(value) = value {
+                    self.code.push(' ');
+                    let return_type = self.return_type.clone();
+                    self.generate_value(value, return_type.as_ref())?;
+                }
+                self.code.push_str(";\n");
+            },
+            // 循环控制语句：直接对应Rust的break和continue
+            StatementKind::Break => {
+                self.indent();
+                self.code.push_str("break;\n");
+            },
+            StatementKind::Continue => {
+                self.indent();
+                self.code.push_str("continue;\n");
+            },
         }
+        Ok(())
     }
     
-
+    // 递归收集所有函数的参数类型（包括嵌套在代码块中的函数）
+    fn collect_functions(&mut self, statements: &[Statement]) {
+        for stmt in statements {
+            match &stmt.kind {
+                StatementKind::Function(name, params, return_type, body) => {
+                    let signature = FunctionSignature {
+                        params: params.iter().map(|p| p.data_type.clone()).collect(),
+                        return_type: return_type.clone(),
+                    };
+                    self.functions.insert(name.clone(), signature);
+                    self.collect_functions(body);
+                },
+                StatementKind::If(_, then_branch, else_branch) => {
+                    self.collect_functions(then_branch);
+                    if let Some(else_branch) = else_branch {
+                        self.collect_functions(else_branch);
+                    }
+                },
+                StatementKind::For(_, _, body) | StatementKind::While(_, body) => {
+                    self.collect_functions(body);
+                },
+                _ => {},
+            }
+        }
+    }
     
     // 生成完整的Rust程序
-    // 接收语句列表，生成包含main函数的完整Rust代码
-    fn generate(&mut self, statements: &[Statement]) -> String {
+    // 接收语句列表，顶层函数生成为main之外的Rust函数，其余语句放入main函数
+    pub fn generate(&mut self, statements: &[Statement]) -> Result<String, CodegenError> {
+        // 先收集函数签名，使调用可以出现在定义之前
+        self.collect_functions(statements);
+        
+        // 生成顶层函数定义
+        for stmt in statements {
+            if let StatementKind::Function(..) = stmt.kind {
+                self.generate_statement(stmt)?;
+                self.code.push('\n');
+            }
+        }
+        
         // 生成main函数头部
         self.code.push_str("fn main() {\n");
         self.increment_indent();
         
-        // 生成所有语句
+        // 生成除函数定义以外的所有语句
         for stmt in statements {
-            self.generate_statement(stmt);
+            if !matches!(stmt.kind, StatementKind::Function(..)) {
+                self.generate_statement(stmt)?;
+            }
         }
         
         // 结束main函数
         self.decrement_indent();
         self.indent();
-        self.code.push_str("}");
+        self.code.push('}');
+        // 用到的运行时辅助函数放在代码开头
+        let mut code: String = self.helpers.iter().map(|helper| format!("{}\n", helper)).collect();
+        code.push_str(&self.code);
         // 返回生成的代码
-        self.code.clone()
+        Ok(code)
     }
 }
 
 // 公开的代码生成函数
 // 接收语句列表，返回生成的Rust代码或错误
-pub fn generate_code(statements: &Vec<Statement>) -> Result<String, String> {
+pub fn generate_code(statements: &[Statement]) -> Result<String, CodegenError> {
     // 创建代码生成器实例
     let mut generator = CodeGenerator::new();
     // 执行代码生成
-    Ok(generator.generate(statements))
-}
\ No newline at end of file
+    let code = generator.generate(statements)?;
+    debug!(target: "codegen", "代码生成完成，共 {} 行Rust代码", code.lines().count());
+    trace!(target: "codegen", "生成的Rust代码:\n{}", code);
+    Ok(code)
+}
+
+// 测试模块：代码生成器单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_source;
+
+    // 辅助函数：把源代码转换为Rust代码
+    fn generate(source: &str) -> String {
+        generate_code(&parse_source(source).unwrap()).unwrap()
+    }
+
+    // 测试浮点数字面量和整数浮点数混合运算的类型转换
+    #[test]
+    fn test_float_literals_and_mixed_arithmetic() {
+        let code = generate("var r = 2.5\nvar n = 3\nvar area = r * n\nvar small = 1e-3\nvar f: float = n + 1\nprintln(area > 1)\n");
+        assert!(code.contains("let mut r = 2.5;"), "{}", code);
+        assert!(code.contains("let mut area = (r * (n as f64));"), "{}", code);
+        assert!(code.contains("let mut small = 0.001;"), "{}", code);
+        assert!(code.contains("let mut f: f64 = ((n + 1) as f64);"), "{}", code);
+        assert!(code.contains("println!(\"{}\", (area > 1.0));"), "{}", code);
+    }
+
+    // 测试逻辑运算符生成Rust的短路运算符
+    #[test]
+    fn test_logical_operators() {
+        let code = generate("var x = 5\nvar ok = x > 0 and x < 10 or not x != 5\nprintln(!ok)\n");
+        assert!(code.contains("let mut ok = (((x > 0) && (x < 10)) || !(x != 5));"), "{}", code);
+        assert!(code.contains("println!(\"{}\", !ok);"), "{}", code);
+    }
+
+    // 测试取余、整除和乘方调用运行时辅助函数，辅助函数只输出一次
+    #[test]
+    fn test_floor_operators() {
+        let code = generate("var n = 7\nvar f = 2.5\nprintln(n % 2, n // 2, n ** 2, f // n, n ** f)\nn %= 3\n");
+        assert!(code.contains("println!(\"{}\", nexa_mod_i64(n, 2));"), "{}", code);
+        assert!(code.contains("println!(\"{}\", nexa_floor_div_f64(f, (n as f64)));"), "{}", code);
+        assert!(code.contains("println!(\"{}\", f64::powf((n as f64), f));"), "{}", code);
+        assert_eq!(code.matches("fn nexa_zero_division").count(), 1);
+        assert_eq!(code.matches("fn nexa_mod_i64").count(), 1);
+        assert!(code.contains("fn nexa_pow_i64") && !code.contains("fn nexa_mod_f64"), "{}", code);
+        assert!(!generate("println(1 + 2)").contains("fn nexa_"));
+        // 左侧类型无法推断（如x未声明）而右侧是浮点数时，也使用浮点数版本
+        let code = generate("println(x % 2.5, x // 0.5, x ** 0.5, x / 2.0)\n");
+        assert!(code.contains("nexa_mod_f64(x, 2.5)") && code.contains("nexa_floor_div_f64(x, 0.5)"), "{}", code);
+        assert!(code.contains("f64::powf(x, 0.5)") && code.contains("(x / 2.0)"), "{}", code);
+        assert!(!code.contains("fn nexa_mod_i64"), "{}", code);
+    }
+
+    // 测试整数除法通过辅助函数检查除数为零，浮点数除法直接使用/
+    #[test]
+    fn test_integer_division() {
+        let code = generate("fn d(a: int, b: int) -> int:\n    return a / b\nvar f = 2.5\nprintln(d(7, 0), f / 2)\n");
+        assert!(code.contains("return nexa_div_i64(a, b);"), "{}", code);
+        assert!(code.contains("println!(\"{}\", (f / 2.0));"), "{}", code);
+        assert_eq!(code.matches("fn nexa_zero_division").count(), 1);
+    }
+
+    // 测试else if和elif链生成平铺的Rust else if
+    #[test]
+    fn test_else_if_chain() {
+        let code = generate("var x = 3\nif x == 1:\n    println(1)\nelif x == 2:\n    println(2)\nelse if x == 3 {\n    println(3)\n} else {\n    println(0)\n}\n");
+        let expected = "    if (x == 1) {\n        println!(\"{}\", 1);\n    } else if (x == 2) {\n        println!(\"{}\", 2);\n    } else if (x == 3) {\n        println!(\"{}\", 3);\n    } else {\n        println!(\"{}\", 0);\n    }\n";
+        assert!(code.contains(expected), "{}", code);
+    }
+
+    // 测试break和continue生成Rust的break和continue
+    #[test]
+    fn test_break_continue() {
+        let code = generate("var i = 0\nwhile true:\n    i += 1\n    if i > 3:\n        break\n    continue\n");
+        assert!(code.contains("            break;\n        }\n        continue;\n    }\n"), "{}", code);
+    }
+
+    // 测试一元负号和逻辑非
+    #[test]
+    fn test_unary_operators() {
+        let code = generate("var x = -5\nvar y: float = -x\nprintln(-x * 2, -(x + 1), - -x, not !true, -2 ** 2)\n");
+        assert!(code.contains("let mut x = -5;"), "{}", code);
+        assert!(code.contains("let mut y: f64 = (-x as f64);"), "{}", code);
+        assert!(code.contains("println!(\"{}\", (-x * 2));"), "{}", code);
+        assert!(code.contains("println!(\"{}\", -(x + 1));"), "{}", code);
+        assert!(code.contains("println!(\"{}\", -(-x));"), "{}", code);
+        assert!(code.contains("println!(\"{}\", !(!true));"), "{}", code);
+        assert!(code.contains("println!(\"{}\", -nexa_pow_i64(2, 2));"), "{}", code);
+        // 无法生成的一元运算符报告错误而不是panic
+        let unary_mul = Expr::new(ExprKind::UnaryOp(Operator::Mul, Box::new(Expr::new(ExprKind::Number(1), Span::default()))), Span::default());
+        let statement = Statement::new(StatementKind::Expression(Box::new(unary_mul)), Span::default());
+        assert_eq!(generate_code(&[statement]).unwrap_err().code(), "E0201");
+    }
+}
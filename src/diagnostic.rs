@@ -0,0 +1,269 @@
+// 诊断信息：编译各阶段报告的错误和警告
+// 每条诊断包含严重程度、可选的错误代码、消息、源代码位置和附加说明，
+// 词法分析和语法分析在出错后继续，一次报告所有诊断
+// 命令行中诊断按rustc的格式渲染：文件位置、出错的源代码行、下划线标记和帮助信息
+use crate::lexer::Span;
+use colored::Color;
+use std::fmt;
+
+// 诊断的严重程度
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl Severity {
+    // 渲染时使用的颜色
+    fn color(&self) -> Color {
+        match self {
+            Severity::Error => Color::Red,
+            Severity::Warning => Color::Yellow,
+            Severity::Note => Color::Cyan,
+        }
+    }
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Severity::Error => "错误",
+            Severity::Warning => "警告",
+            Severity::Note => "提示",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// 诊断的附加信息：下划线标签、附加说明和修改建议
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Details {
+    // 显示在下划线旁边的标签
+    pub label: Option<String>,
+    // 附加说明
+    pub notes: Vec<String>,
+    // 修改建议
+    pub help: Option<String>,
+}
+
+// 诊断信息
+// 附加信息放在堆上，使各阶段以Result<_, Diagnostic>传递错误时Err的体积保持较小
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    // 严重程度
+    pub severity: Severity,
+    // 稳定的错误代码，如E0001
+    pub code: Option<&'static str>,
+    // 诊断消息（不含位置）
+    pub message: String,
+    // 出错的源代码位置
+    pub span: Span,
+    // 标签、附加说明和修改建议
+    pub details: Box<Details>,
+}
+
+impl Diagnostic {
+    // 创建错误诊断
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Diagnostic {
+            severity: Severity::Error,
+            code: None,
+            message: message.into(),
+            span,
+            details: Box::default(),
+        }
+    }
+
+    // 设置错误代码
+    pub fn with_code(mut self, code: &'static str) -> Self {
+        self.code = Some(code);
+        self
+    }
+
+    // 设置下划线旁边的标签
+    pub fn with_label(mut self, label: impl Into<String>) -> Self {
+        self.details.label = Some(label.into());
+        self
+    }
+
+    // 添加一条附加说明
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.details.notes.push(note.into());
+        self
+    }
+
+    // 设置修改建议
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.details.help = Some(help.into());
+        self
+    }
+
+    // 是否是错误（错误会阻止后续阶段执行）
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+// 单行格式："第2行第5列: 错误[E0001]: 消息"，附加说明各占一行
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.severity)?;
+        if let Some(code) = self.code {
+            write!(f, "[{}]", code)?;
+        }
+        write!(f, ": {}", self.message)?;
+        for note in &self.details.notes {
+            write!(f, "\n  = 注: {}", note)?;
+        }
+        if let Some(help) = &self.details.help {
+            write!(f, "\n  = 帮助: {}", help)?;
+        }
+        Ok(())
+    }
+}
+
+// 字符在终端中占用的列数：中日韩等宽字符占两列，其余占一列
+fn display_width(c: char) -> usize {
+    match c as u32 {
+        0x1100..=0x115F | 0x2E80..=0xA4CF | 0xAC00..=0xD7A3 | 0xF900..=0xFAFF | 0xFE30..=0xFE4F | 0xFF00..=0xFF60
+        | 0xFFE0..=0xFFE6 => 2,
+        _ => 1,
+    }
+}
+
+// 按需着色：color为false时原样返回文本
+// 是否着色只由enabled决定，不受colored库按标准输出设置的全局开关影响，
+// 因此写到标准错误的文本可以按标准错误是否是终端单独决定
+pub fn paint(text: &str, color: Option<Color>, bold: bool, enabled: bool) -> String {
+    let mut codes = Vec::new();
+    if bold {
+        codes.push("1".into());
+    }
+    if let Some(color) = color {
+        codes.push(color.to_fg_str());
+    }
+    if !enabled || codes.is_empty() {
+        return text.to_string();
+    }
+    format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text)
+}
+
+impl Diagnostic {
+    // 按rustc的格式渲染诊断，例如：
+    //
+    // 错误[E0002]: 期望表达式，但得到 Star
+    //  --> main.nexa:3:5
+    //   |
+    // 3 | b = * 2
+    //   |     ^ 这里需要一个表达式
+    //   |
+    //   = 帮助: ...
+    //
+    // color为true时使用终端颜色
+    pub fn render(&self, file_name: &str, source: &str, color: bool) -> String {
+        let severity_color = Some(self.severity.color());
+        let gutter_color = Some(Color::Blue);
+        let line_number = self.span.line.to_string();
+        let pad = " ".repeat(line_number.len());
+        let gutter = paint("|", gutter_color, true, color);
+
+        // 标题行：严重程度、错误代码和消息
+        let mut title = self.severity.to_string();
+        if let Some(code) = self.code {
+            title.push_str(&format!("[{}]", code));
+        }
+        let mut out = format!(
+            "{}{}\n",
+            paint(&title, severity_color, true, color),
+            paint(&format!(": {}", self.message), None, true, color)
+        );
+        out.push_str(&format!(
+            "{}{} {}:{}:{}\n",
+            pad,
+            paint("-->", gutter_color, true, color),
+            file_name,
+            self.span.line,
+            self.span.column
+        ));
+
+        // 出错的源代码行和下划线
+        if let Some(text) = source.lines().nth(self.span.line.saturating_sub(1)) {
+            let text = text.trim_end_matches('\r');
+            out.push_str(&format!("{} {}\n", pad, gutter));
+            out.push_str(&format!("{} {} {}\n", paint(&line_number, gutter_color, true, color), gutter, text));
+
+            // 下划线前的空白：制表符保持不变，宽字符占两列
+            let before: String = text
+                .chars()
+                .take(self.span.column.saturating_sub(1))
+                .map(|c| if c == '\t' { "\t".to_string() } else { " ".repeat(display_width(c)) })
+                .collect();
+            // 下划线长度：span在本行内的显示宽度，至少为1
+            let underlined = source
+                .get(self.span.start..self.span.end)
+                .map(|s| s.lines().next().unwrap_or("").chars().map(display_width).sum::<usize>())
+                .unwrap_or(0)
+                .max(1);
+            let mut marker = "^".repeat(underlined);
+            if let Some(label) = &self.details.label {
+                marker.push(' ');
+                marker.push_str(label);
+            }
+            out.push_str(&format!("{} {} {}{}\n", pad, gutter, before, paint(&marker, severity_color, true, color)));
+        }
+
+        // 附加说明和修改建议
+        if !self.details.notes.is_empty() || self.details.help.is_some() {
+            out.push_str(&format!("{} {}\n", pad, gutter));
+        }
+        let equals = paint("=", gutter_color, true, color);
+        for note in &self.details.notes {
+            out.push_str(&format!("{} {} {}: {}\n", pad, equals, paint("注", None, true, color), note));
+        }
+        if let Some(help) = &self.details.help {
+            out.push_str(&format!("{} {} {}: {}\n", pad, equals, paint("帮助", None, true, color), help));
+        }
+        out
+    }
+}
+
+// 把多条诊断（或可以显示为诊断的错误）格式化为文本，每条诊断占一段
+pub fn format_all<T: fmt::Display>(diagnostics: &[T]) -> String {
+    diagnostics.iter().map(|d| d.to_string()).collect::<Vec<_>>().join("\n")
+}
+
+// 测试模块：诊断渲染单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 测试不着色时的rustc风格渲染
+    #[test]
+    fn test_render_plain() {
+        let source = "var a = 1\nvar b = a +* 2\n";
+        let diagnostic = Diagnostic::error("期望表达式，但得到 Star", Span::new(2, 12, 21, 22))
+            .with_code("E0002")
+            .with_label("这里需要一个表达式")
+            .with_help("删除多余的运算符");
+        let expected = "错误[E0002]: 期望表达式，但得到 Star\n --> main.nexa:2:12\n  |\n2 | var b = a +* 2\n  |            ^ 这里需要一个表达式\n  |\n  = 帮助: 删除多余的运算符\n";
+        assert_eq!(diagnostic.render("main.nexa", source, false), expected);
+    }
+
+    // 测试多字符span的下划线和宽字符的对齐
+    #[test]
+    fn test_render_underline_width() {
+        let source = "print \"你好\" + count\n";
+        let diagnostic = Diagnostic::error("未声明的变量: count", Span::new(1, 14, 17, 22));
+        let rendered = diagnostic.render("a.nexa", source, false);
+        assert!(rendered.ends_with("1 | print \"你好\" + count\n  |                ^^^^^\n"), "{}", rendered);
+    }
+
+    // 测试着色只由参数决定，不依赖标准输出是否是终端
+    #[test]
+    fn test_paint() {
+        assert_eq!(paint("错误", Some(Color::Red), true, true), "\x1b[1;31m错误\x1b[0m");
+        assert_eq!(paint("|", Some(Color::Blue), false, true), "\x1b[34m|\x1b[0m");
+        assert_eq!(paint("错误", Some(Color::Red), true, false), "错误");
+    }
+}
@@ -0,0 +1,331 @@
+// 执行生成的Rust代码：在独立的工作目录中调用rustc编译后运行
+// 每次运行使用唯一的临时目录，并发运行（例如多个Web编辑器用户）互不影响；
+// 程序在清空的环境变量中运行，有运行时间和输出大小的限制，超时会被终止
+// 编译结果保存在编译缓存中，代码没有变化时不再调用rustc
+use crate::cache::{self, CompileCache};
+use std::fs;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+// 默认的运行时间限制
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+// 默认的标准输出和标准错误大小限制（字节）
+pub const DEFAULT_MAX_OUTPUT: usize = 64 * 1024;
+// rustc编译的时间限制，超过后终止编译
+pub const COMPILE_TIMEOUT: Duration = Duration::from_secs(60);
+// 编译失败时保留的rustc错误输出的最大字节数
+const MAX_COMPILER_OUTPUT: usize = 256 * 1024;
+
+// 传给rustc的编译参数，也是编译缓存键的一部分
+// 生成的代码会触发unused_parens等警告，关闭警告使编译失败时只显示真正的错误
+pub const RUSTC_FLAGS: &[&str] = &["--edition", "2021", "-A", "warnings"];
+
+// 等待程序退出时检查状态的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// 进程内工作目录的序号，和进程号、时间一起保证目录名唯一
+static NEXT_WORK_DIR: AtomicU64 = AtomicU64::new(0);
+
+// 运行程序的限制
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExecutionLimits {
+    // 运行时间上限，超过后终止程序
+    pub timeout: Duration,
+    // 标准输出和标准错误各自保留的最大字节数，超出部分被丢弃
+    pub max_output: usize,
+}
+
+impl Default for ExecutionLimits {
+    fn default() -> Self {
+        ExecutionLimits {
+            timeout: DEFAULT_TIMEOUT,
+            max_output: DEFAULT_MAX_OUTPUT,
+        }
+    }
+}
+
+// 运行结果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionResult {
+    // 标准输出（可能被截断）
+    pub stdout: String,
+    // 标准错误（可能被截断）
+    pub stderr: String,
+    // 退出码，被信号终止（包括超时）时为None
+    pub exit_code: Option<i32>,
+    // 运行时间，不含编译时间
+    pub duration: Duration,
+    // 是否因为超时被终止
+    pub timed_out: bool,
+    // 输出是否超过大小限制被截断
+    pub truncated: bool,
+}
+
+impl ExecutionResult {
+    // 程序是否在限制内正常退出且退出码为0
+    pub fn success(&self) -> bool {
+        !self.timed_out && self.exit_code == Some(0)
+    }
+
+    // 失败时的错误说明
+    pub fn error_message(&self) -> String {
+        if self.timed_out {
+            format!("执行超时: 程序运行超过 {:?}，已被终止", self.duration)
+        } else {
+            match self.exit_code {
+                Some(code) => format!("执行错误（退出码 {}）: {}", code, self.stderr),
+                None => format!("执行错误（程序被信号终止）: {}", self.stderr),
+            }
+        }
+    }
+}
+
+// 独立的临时工作目录，离开作用域时连同其中的文件一起删除
+pub(crate) struct WorkDir {
+    pub(crate) path: PathBuf,
+}
+
+impl WorkDir {
+    // 在系统临时目录下创建一个新的唯一目录
+    pub(crate) fn new() -> Result<Self, String> {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.subsec_nanos()).unwrap_or(0);
+        let sequence = NEXT_WORK_DIR.fetch_add(1, Ordering::Relaxed);
+        let name = format!("nexa-{}-{}-{}", std::process::id(), sequence, nanos);
+        let path = std::env::temp_dir().join(name);
+        // create_dir在目录已存在时失败，不会和其他运行共用目录
+        fs::create_dir(&path).map_err(|e| format!("创建工作目录错误: {}", e))?;
+        Ok(WorkDir { path })
+    }
+}
+
+impl Drop for WorkDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+// 在工作目录中用rustc把代码编译为output
+fn compile_in(dir: &Path, code: &str, output: &Path) -> Result<(), String> {
+    // 写入代码到工作目录中的源文件
+    let source = dir.join("main.rs");
+    fs::write(&source, code).map_err(|e| format!("写入临时文件错误: {}", e))?;
+
+    // 编译源文件
+    debug!(target: "exec", "用rustc编译 {}", source.display());
+    let started = Instant::now();
+    let mut command = Command::new("rustc");
+    command.args(RUSTC_FLAGS).arg(&source).arg("-o").arg(output);
+    let (status, stderr) = run_compiler(&mut command, COMPILE_TIMEOUT)?;
+
+    // 检查编译是否成功
+    if !status.success() {
+        // 编译失败，返回错误信息
+        return Err(format!("编译错误: {}", stderr));
+    }
+
+    debug!(target: "exec", "编译完成，输出 {}，用时 {:?}", output.display(), started.elapsed());
+    Ok(())
+}
+
+// 运行编译命令，超过timeout时终止编译，返回退出状态和标准错误输出
+// 编译的代码可能来自不可信的输入（例如Web编辑器），编译本身也必须有时间限制
+fn run_compiler(command: &mut Command, timeout: Duration) -> Result<(ExitStatus, String), String> {
+    let mut child = command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("编译命令执行错误: {}", e))?;
+    let stderr = read_limited(child.stderr.take().expect("标准错误已设置为管道"), MAX_COMPILER_OUTPUT);
+    let waited = wait_with_timeout(&mut child, timeout);
+    let (stderr, _) = stderr.join().unwrap_or_default();
+    let (status, timed_out) = waited.map_err(|e| format!("等待编译命令退出错误: {}", e))?;
+    if timed_out {
+        return Err(format!("编译超时：超过 {} 秒", timeout.as_secs_f64()));
+    }
+    Ok((status, String::from_utf8_lossy(&stderr).into_owned()))
+}
+
+// 取得代码编译后的可执行文件：编译缓存cache中没有时在临时目录中编译并加入缓存
+fn cached_executable(code: &str, cache: &CompileCache) -> Result<PathBuf, String> {
+    let key = cache::cache_key(code, cache::rustc_version()?, RUSTC_FLAGS);
+    cache.get_or_compile(&key, |output| {
+        let dir = WorkDir::new()?;
+        compile_in(&dir.path, code, output)
+    })
+}
+
+// 用rustc把Rust代码编译为可执行文件output
+// 代码先写入独立的临时目录中的源文件，编译失败时返回rustc的错误输出
+pub fn compile_rust_code(code: &str, output: &Path, cache: &CompileCache) -> Result<(), String> {
+    let executable = cached_executable(code, cache)?;
+    fs::copy(&executable, output).map_err(|e| format!("写入可执行文件 {} 错误: {}", output.display(), e))?;
+    Ok(())
+}
+
+// 在后台线程中读取管道，最多保留limit字节，超出部分继续读取并丢弃，避免程序因管道写满而阻塞
+// 线程返回保留的内容和是否被截断
+fn read_limited<R: Read + Send + 'static>(mut pipe: R, limit: usize) -> JoinHandle<(Vec<u8>, bool)> {
+    thread::spawn(move || {
+        let mut kept = Vec::new();
+        let mut truncated = false;
+        let mut buffer = [0u8; 8192];
+        loop {
+            match pipe.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(n) => {
+                    let room = limit.saturating_sub(kept.len());
+                    if n > room {
+                        truncated = true;
+                    }
+                    kept.extend_from_slice(&buffer[..n.min(room)]);
+                },
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => break,
+            }
+        }
+        (kept, truncated)
+    })
+}
+
+// 等待程序退出，超过timeout时终止程序，返回退出状态和是否超时
+fn wait_with_timeout(child: &mut Child, timeout: Duration) -> io::Result<(ExitStatus, bool)> {
+    let deadline = Instant::now() + timeout;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            return Ok((status, false));
+        }
+        if Instant::now() >= deadline {
+            // 程序可能恰好在此时退出，终止失败可以忽略
+            let _ = child.kill();
+            return Ok((child.wait()?, true));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+// 在限制下运行可执行文件：工作目录为dir，环境变量被清空，没有标准输入
+fn run_sandboxed(executable: &Path, dir: &Path, limits: &ExecutionLimits) -> Result<ExecutionResult, String> {
+    let started = Instant::now();
+    let mut child = Command::new(executable)
+        .current_dir(dir)
+        .env_clear()
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("执行命令错误: {}", e))?;
+
+    let stdout = read_limited(child.stdout.take().expect("标准输出已设置为管道"), limits.max_output);
+    let stderr = read_limited(child.stderr.take().expect("标准错误已设置为管道"), limits.max_output);
+    let waited = wait_with_timeout(&mut child, limits.timeout);
+    let duration = started.elapsed();
+    // 程序已经退出，管道会关闭，读取线程随之结束
+    let (stdout, stdout_truncated) = stdout.join().unwrap_or_default();
+    let (stderr, stderr_truncated) = stderr.join().unwrap_or_default();
+    let (status, timed_out) = waited.map_err(|e| format!("等待程序退出错误: {}", e))?;
+    debug!(target: "exec", "程序退出，状态: {}，用时 {:?}，超时: {}", status, duration, timed_out);
+
+    Ok(ExecutionResult {
+        stdout: String::from_utf8_lossy(&stdout).into_owned(),
+        stderr: String::from_utf8_lossy(&stderr).into_owned(),
+        exit_code: if timed_out { None } else { status.code() },
+        duration,
+        timed_out,
+        truncated: stdout_truncated || stderr_truncated,
+    })
+}
+
+// 执行Rust代码的函数，使用默认限制和默认的编译缓存目录
+pub fn execute_rust_code(code: &str) -> Result<ExecutionResult, String> {
+    execute_rust_code_with(code, &ExecutionLimits::default(), &CompileCache::default())
+}
+
+// 将生成的Rust代码编译为可执行文件（优先使用编译缓存cache），并在独立的工作目录中受限执行
+// 编译失败或无法启动程序时返回错误；程序运行失败、超时等情况记录在运行结果中
+pub fn execute_rust_code_with(
+    code: &str,
+    limits: &ExecutionLimits,
+    cache: &CompileCache,
+) -> Result<ExecutionResult, String> {
+    let executable = cached_executable(code, cache)?;
+    let dir = WorkDir::new()?;
+    run_sandboxed(&executable, &dir.path, limits)
+}
+
+// 测试模块：受限执行单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 测试每次运行使用不同的工作目录，结束后删除
+    #[test]
+    fn test_unique_work_dirs() {
+        let first = WorkDir::new().unwrap();
+        let second = WorkDir::new().unwrap();
+        assert_ne!(first.path, second.path);
+        let path = first.path.clone();
+        drop(first);
+        assert!(!path.exists());
+    }
+
+    // 测试超时终止、输出截断和清空的环境变量（使用系统shell代替编译好的程序）
+    #[test]
+    #[cfg(unix)]
+    fn test_run_sandboxed_limits() {
+        let dir = WorkDir::new().unwrap();
+        let script = dir.path.join("program");
+        let sh = |body: &str| {
+            fs::write(&script, format!("#!/bin/sh\n{}\n", body)).unwrap();
+            let mut perms = fs::metadata(&script).unwrap().permissions();
+            std::os::unix::fs::PermissionsExt::set_mode(&mut perms, 0o755);
+            fs::set_permissions(&script, perms).unwrap();
+        };
+        let limits = ExecutionLimits { timeout: Duration::from_millis(300), max_output: 16 };
+
+        sh("[ -z \"$HOME\" ] && echo clean; echo oops >&2; exit 3");
+        let result = run_sandboxed(&script, &dir.path, &limits).unwrap();
+        assert_eq!(result.stdout, "clean\n");
+        assert_eq!(result.stderr, "oops\n");
+        assert_eq!(result.exit_code, Some(3));
+        assert!(!result.success() && !result.timed_out && !result.truncated);
+
+        sh("echo 0123456789abcdefghijklmnop");
+        let result = run_sandboxed(&script, &dir.path, &limits).unwrap();
+        assert_eq!(result.stdout, "0123456789abcdef");
+        assert!(result.success() && result.truncated);
+
+        sh("exec /bin/sleep 5");
+        let result = run_sandboxed(&script, &dir.path, &limits).unwrap();
+        assert!(result.timed_out && !result.success());
+        assert!(result.duration < Duration::from_secs(5));
+    }
+
+    // 测试编译失败时只返回错误，不包含生成代码引起的警告
+    #[test]
+    fn test_compile_error_without_warnings() {
+        let dir = WorkDir::new().unwrap();
+        let code = "fn main() {\n    let mut x = (1);\n    let y: i64 = \"a\";\n}\n";
+        let err = compile_in(&dir.path, code, &dir.path.join("program")).unwrap_err();
+        assert!(err.contains("mismatched types"), "{}", err);
+        assert!(!err.contains("warning"), "{}", err);
+    }
+
+    // 测试编译命令超时被终止
+    #[test]
+    #[cfg(unix)]
+    fn test_compiler_timeout() {
+        let started = Instant::now();
+        let err = run_compiler(Command::new("/bin/sleep").arg("5"), Duration::from_millis(300)).unwrap_err();
+        assert!(err.starts_with("编译超时"), "{}", err);
+        assert!(started.elapsed() < Duration::from_secs(5));
+        let (status, stderr) = run_compiler(Command::new("/bin/sh").args(["-c", "echo bad >&2; exit 1"]), COMPILE_TIMEOUT).unwrap();
+        assert!(!status.success());
+        assert_eq!(stderr, "bad\n");
+    }
+}
@@ -0,0 +1,618 @@
+// 树遍历解释器：直接执行语法分析得到的抽象语法树
+// 不需要生成Rust代码，也不需要调用rustc，适合快速运行Nexa程序
+use crate::lexer::Span;
+use crate::parser::{DataType, Expr, ExprKind, Operator, Param, Statement, StatementKind};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::Write;
+use std::sync::Arc;
+use std::thread;
+
+// 函数调用的最大嵌套深度，解释器和虚拟机共用，超过时报告无限递归错误
+pub(crate) const MAX_CALL_DEPTH: usize = 10_000;
+
+// 解释器线程的栈大小，足够容纳MAX_CALL_DEPTH层嵌套调用
+// 只是保留的地址空间，实际使用多少才占用多少内存
+const CALL_STACK_SIZE: usize = 1 << 30;
+
+// 运行时值枚举：表示Nexa程序运行时的所有值
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    // 整数
+    Int(i64),
+    // 浮点数
+    Float(f64),
+    // 布尔值
+    Bool(bool),
+    // 字符串
+    Str(String),
+    // 空值：没有返回值的函数调用结果
+    Unit,
+}
+
+impl Value {
+    // 返回值的类型名称，用于运行时错误信息
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Int(_) => "int",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::Str(_) => "string",
+            Value::Unit => "()",
+        }
+    }
+}
+
+// 值的显示格式与生成的Rust代码中println!("{}")的输出保持一致
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Str(s) => write!(f, "{}", s),
+            Value::Unit => write!(f, "()"),
+        }
+    }
+}
+
+// 用户定义的函数：参数列表、返回类型和函数体
+#[derive(Debug)]
+struct Function {
+    params: Vec<Param>,
+    return_type: Option<DataType>,
+    body: Vec<Statement>,
+}
+
+// 语句执行后的控制流状态
+enum Flow {
+    // 正常执行下一条语句
+    Normal,
+    // 遇到return语句，携带返回值
+    Return(Value),
+    // 遇到break语句，携带语句位置（用于报告循环外的break）
+    Break(Span),
+    // 遇到continue语句，携带语句位置
+    Continue(Span),
+}
+
+// 出现在循环体外的break或continue语句的错误
+fn outside_loop_error(span: Span, keyword: &str) -> String {
+    runtime_error(span, format!("{}语句只能出现在循环体内", keyword))
+}
+
+// 生成带位置的运行时错误信息
+pub(crate) fn runtime_error(span: Span, message: String) -> String {
+    format!("{}: 运行时错误: {}", span, message)
+}
+
+// 函数调用嵌套过深的错误信息
+pub(crate) fn call_depth_error(span: Span) -> String {
+    runtime_error(span, format!("函数调用嵌套超过 {} 层，可能是无限递归", MAX_CALL_DEPTH))
+}
+
+// 在栈足够大的线程上运行解释器，深层递归不会耗尽宿主程序的栈
+pub(crate) fn with_call_stack<T: Send>(f: impl FnOnce() -> T + Send) -> T {
+    thread::scope(|scope| {
+        let handle = thread::Builder::new()
+            .name("nexa-interp".to_string())
+            .stack_size(CALL_STACK_SIZE)
+            .spawn_scoped(scope, f)
+            .expect("无法创建解释器线程");
+        handle.join().unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    })
+}
+
+// 按声明的类型转换值：整数赋值给float类型时自动转换为浮点数
+pub(crate) fn coerce(value: Value, data_type: Option<&DataType>) -> Value {
+    match (data_type, value) {
+        (Some(DataType::Float), Value::Int(n)) => Value::Float(n as f64),
+        (_, value) => value,
+    }
+}
+
+// 变量：当前值和变量的类型
+// 给类型为float的变量赋整数值时转换为浮点数
+struct Variable {
+    value: Value,
+    data_type: Option<DataType>,
+}
+
+// 解释器结构体：保存变量作用域、函数表和输出目标
+// 同一个解释器可以多次执行语句，变量和函数在多次执行之间保留
+pub struct Interpreter<W: Write> {
+    // 程序输出的目标（标准输出或内存缓冲区）
+    out: W,
+    // 变量作用域栈：每个代码块对应一层，栈顶为最内层作用域
+    scopes: Vec<HashMap<String, Variable>>,
+    // 已定义的函数
+    functions: HashMap<String, Arc<Function>>,
+    // 当前函数调用深度
+    call_depth: usize,
+}
+
+impl<W: Write> Interpreter<W> {
+    // 创建新的解释器，程序输出写入out
+    pub fn new(out: W) -> Self {
+        Interpreter {
+            out,
+            scopes: vec![HashMap::new()],
+            functions: HashMap::new(),
+            call_depth: 0,
+        }
+    }
+
+    // 清空所有变量和函数，回到刚创建时的状态
+    pub fn reset(&mut self) {
+        self.scopes = vec![HashMap::new()];
+        self.functions.clear();
+        self.call_depth = 0;
+    }
+
+    // 顶层作用域中的变量及其当前值
+    pub fn globals(&self) -> impl Iterator<Item = (&String, &Value)> {
+        self.scopes[0].iter().map(|(name, variable)| (name, &variable.value))
+    }
+
+    // 执行一组顶层语句
+    // 函数定义先被登记，因此调用可以出现在定义之前
+    pub fn execute(&mut self, statements: &[Statement]) -> Result<(), String> {
+        self.collect_functions(statements);
+        for stmt in statements {
+            match self.execute_statement(stmt)? {
+                Flow::Normal => {},
+                Flow::Return(_) => return Err(runtime_error(stmt.span, "return语句只能出现在函数体内".to_string())),
+                Flow::Break(span) => return Err(outside_loop_error(span, "break")),
+                Flow::Continue(span) => return Err(outside_loop_error(span, "continue")),
+            }
+        }
+        self.flush()
+    }
+
+    // 把缓冲的输出写出
+    pub(crate) fn flush(&mut self) -> Result<(), String> {
+        self.out.flush().map_err(|e| format!("写入输出失败: {}", e))
+    }
+
+    // 递归登记所有函数定义（包括嵌套在代码块中的函数）
+    fn collect_functions(&mut self, statements: &[Statement]) {
+        for stmt in statements {
+            match &stmt.kind {
+                StatementKind::Function(name, params, return_type, body) => {
+                    let function = Function {
+                        params: params.clone(),
+                        return_type: return_type.clone(),
+                        body: body.clone(),
+                    };
+                    self.functions.insert(name.clone(), Arc::new(function));
+                    self.collect_functions(body);
+                },
+                StatementKind::If(_, then_branch, else_branch) => {
+                    self.collect_functions(then_branch);
+                    if let Some(else_branch) = else_branch {
+                        self.collect_functions(else_branch);
+                    }
+                },
+                StatementKind::For(_, _, body) | StatementKind::While(_, body) => {
+                    self.collect_functions(body);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    // 查找变量的值，从最内层作用域开始
+    fn lookup(&self, name: &str) -> Option<&Value> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name)).map(|variable| &variable.value)
+    }
+
+    // 在当前作用域中声明变量，值按变量的类型转换
+    fn declare(&mut self, name: &str, value: Value, data_type: Option<DataType>) {
+        let value = coerce(value, data_type.as_ref());
+        self.scopes.last_mut().unwrap().insert(name.to_string(), Variable { value, data_type });
+    }
+
+    // 给已声明的变量赋值，值按声明时的类型转换，返回变量是否存在
+    fn assign(&mut self, name: &str, value: Value) -> bool {
+        for scope in self.scopes.iter_mut().rev() {
+            if let Some(variable) = scope.get_mut(name) {
+                variable.value = coerce(value, variable.data_type.as_ref());
+                return true;
+            }
+        }
+        false
+    }
+
+    // 写入程序输出
+    pub(crate) fn write_output(&mut self, text: &str) -> Result<(), String> {
+        self.out.write_all(text.as_bytes()).map_err(|e| format!("写入输出失败: {}", e))
+    }
+
+    // 在新的块作用域中执行语句列表
+    fn execute_block(&mut self, statements: &[Statement]) -> Result<Flow, String> {
+        self.scopes.push(HashMap::new());
+        let result = self.execute_statements(statements);
+        self.scopes.pop();
+        result
+    }
+
+    // 依次执行语句，遇到return、break或continue时提前结束
+    fn execute_statements(&mut self, statements: &[Statement]) -> Result<Flow, String> {
+        for stmt in statements {
+            match self.execute_statement(stmt)? {
+                Flow::Normal => {},
+                flow => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    // 执行单条语句
+    fn execute_statement(&mut self, stmt: &Statement) -> Result<Flow, String> {
+        match &stmt.kind {
+            // 变量声明：在当前作用域中创建变量
+            StatementKind::Var(name, data_type, expr) => {
+                let value = self.evaluate(expr)?;
+                // 没有类型注解时由初始值决定变量的类型（与代码生成器推断变量类型的方式一致）
+                let data_type = data_type.clone().or_else(|| matches!(value, Value::Float(_)).then_some(DataType::Float));
+                self.declare(name, value, data_type);
+            },
+            // 变量赋值：变量必须已经声明
+            StatementKind::Assign(name, expr) => {
+                let value = self.evaluate(expr)?;
+                if !self.assign(name, value) {
+                    return Err(runtime_error(stmt.span, format!("未定义的变量: {}", name)));
+                }
+            },
+            // 打印语句：多个值之间用空格分隔，不换行
+            StatementKind::Print(exprs) => {
+                let mut parts = Vec::new();
+                for expr in exprs {
+                    parts.push(self.evaluate(expr)?.to_string());
+                }
+                self.write_output(&parts.join(" "))?;
+            },
+            // 换行打印语句：每个值单独输出一行
+            StatementKind::Println(exprs) => {
+                for expr in exprs {
+                    let value = self.evaluate(expr)?;
+                    self.write_output(&format!("{}\n", value))?;
+                }
+            },
+            // 条件语句
+            StatementKind::If(condition, then_branch, else_branch) => {
+                if self.evaluate_condition(condition)? {
+                    return self.execute_block(then_branch);
+                } else if let Some(else_branch) = else_branch {
+                    return self.execute_block(else_branch);
+                }
+            },
+            StatementKind::For(var_name, iterable, body) => return self.execute_for(var_name, iterable, body),
+            StatementKind::While(condition, body) => return self.execute_while(condition, body),
+            // 表达式语句：计算后丢弃结果
+            StatementKind::Expression(expr) => {
+                self.evaluate(expr)?;
+            },
+            // 函数定义在执行前已经登记
+            StatementKind::Function(..) => {},
+            // 返回语句
+            StatementKind::Return(value) => {
+                let value = match value {
+                    Some(expr) => self.evaluate(expr)?,
+                    None => Value::Unit,
+                };
+                return Ok(Flow::Return(value));
+            },
+            // 循环控制语句：由所在的循环处理
+            StatementKind::Break => return Ok(Flow::Break(stmt.span)),
+            StatementKind::Continue => return Ok(Flow::Continue(stmt.span)),
+        }
+        Ok(Flow::Normal)
+    }
+
+    // while循环
+    fn execute_while(&mut self, condition: &Expr, body: &[Statement]) -> Result<Flow, String> {
+        while self.evaluate_condition(condition)? {
+            match self.execute_block(body)? {
+                Flow::Normal | Flow::Continue(_) => {},
+                Flow::Break(_) => break,
+                flow @ Flow::Return(_) => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    // for循环：遍历范围（包含终点）或字符串中的字符
+    fn execute_for(&mut self, var_name: &str, iterable: &Expr, body: &[Statement]) -> Result<Flow, String> {
+        let items: Vec<Value> = match &iterable.kind {
+            ExprKind::Range(start, end) => {
+                let start = self.evaluate_int(start)?;
+                let end = self.evaluate_int(end)?;
+                (start..=end).map(Value::Int).collect()
+            },
+            _ => match self.evaluate(iterable)? {
+                Value::Str(s) => s.chars().map(|c| Value::Str(c.to_string())).collect(),
+                other => {
+                    return Err(runtime_error(iterable.span, format!("无法遍历 {} 类型的值", other.type_name())));
+                },
+            },
+        };
+        for item in items {
+            // 每次迭代使用新的作用域，循环变量只在循环体内可见
+            self.scopes.push(HashMap::new());
+            self.declare(var_name, item, None);
+            let flow = self.execute_statements(body);
+            self.scopes.pop();
+            match flow? {
+                Flow::Normal | Flow::Continue(_) => {},
+                Flow::Break(_) => break,
+                flow @ Flow::Return(_) => return Ok(flow),
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    // 计算条件表达式，结果必须是布尔值
+    fn evaluate_condition(&mut self, expr: &Expr) -> Result<bool, String> {
+        match self.evaluate(expr)? {
+            Value::Bool(b) => Ok(b),
+            other => Err(runtime_error(expr.span, format!("条件必须是bool类型，但得到 {}", other.type_name()))),
+        }
+    }
+
+    // 计算整数表达式（用于范围的起点和终点）
+    fn evaluate_int(&mut self, expr: &Expr) -> Result<i64, String> {
+        match self.evaluate(expr)? {
+            Value::Int(n) => Ok(n),
+            other => Err(runtime_error(expr.span, format!("期望int类型，但得到 {}", other.type_name()))),
+        }
+    }
+
+    // 计算表达式的值
+    pub fn evaluate(&mut self, expr: &Expr) -> Result<Value, String> {
+        match &expr.kind {
+            ExprKind::Number(n) => Ok(Value::Int(*n)),
+            ExprKind::Float(x) => Ok(Value::Float(*x)),
+            ExprKind::String(s) => Ok(Value::Str(s.clone())),
+            ExprKind::BoolLiteral(b) => Ok(Value::Bool(*b)),
+            ExprKind::Identifier(name) => match self.lookup(name) {
+                Some(value) => Ok(value.clone()),
+                None => Err(runtime_error(expr.span, format!("未定义的变量: {}", name))),
+            },
+            ExprKind::BinaryOp(left, op @ (Operator::And | Operator::Or), right) => self.logical_op(op, left, right),
+            ExprKind::BinaryOp(left, op, right) => {
+                let left = self.evaluate(left)?;
+                let right = self.evaluate(right)?;
+                binary_op(op, left, right).map_err(|message| runtime_error(expr.span, message))
+            },
+            ExprKind::UnaryOp(op, operand) => {
+                let value = self.evaluate(operand)?;
+                match (op, value) {
+                    (Operator::Sub, Value::Int(n)) => n
+                        .checked_neg()
+                        .map(Value::Int)
+                        .ok_or_else(|| runtime_error(expr.span, "整数溢出".to_string())),
+                    (Operator::Sub, Value::Float(x)) => Ok(Value::Float(-x)),
+                    (Operator::Not, Value::Bool(b)) => Ok(Value::Bool(!b)),
+                    (op, value) => Err(runtime_error(expr.span, format!("不支持的一元运算: {:?} {}", op, value.type_name()))),
+                }
+            },
+            ExprKind::Range(..) => Err(runtime_error(expr.span, "范围表达式只能用于for循环".to_string())),
+            ExprKind::Call(name, arguments) => {
+                let mut values = Vec::new();
+                for argument in arguments {
+                    values.push(self.evaluate(argument)?);
+                }
+                self.call(name, values, expr.span)
+            },
+        }
+    }
+
+    // 逻辑与、逻辑或短路求值：左侧已经能决定结果时不计算右侧
+    fn logical_op(&mut self, op: &Operator, left: &Expr, right: &Expr) -> Result<Value, String> {
+        let left = self.evaluate_condition(left)?;
+        if left == (*op == Operator::Or) {
+            return Ok(Value::Bool(left));
+        }
+        self.evaluate_condition(right).map(Value::Bool)
+    }
+
+    // 调用用户定义的函数
+    // 函数体在只包含参数的新环境中执行，不能访问调用者的变量
+    fn call(&mut self, name: &str, arguments: Vec<Value>, span: Span) -> Result<Value, String> {
+        let function = match self.functions.get(name) {
+            Some(function) => Arc::clone(function),
+            None => return Err(runtime_error(span, format!("未定义的函数: {}", name))),
+        };
+        if arguments.len() != function.params.len() {
+            return Err(runtime_error(
+                span,
+                format!("函数 {} 需要 {} 个参数，但提供了 {} 个", name, function.params.len(), arguments.len()),
+            ));
+        }
+        if self.call_depth >= MAX_CALL_DEPTH {
+            return Err(call_depth_error(span));
+        }
+
+        // 为参数建立新的作用域栈
+        let mut frame = HashMap::new();
+        for (param, value) in function.params.iter().zip(arguments) {
+            let data_type = Some(param.data_type.clone());
+            frame.insert(param.name.clone(), Variable { value: coerce(value, data_type.as_ref()), data_type });
+        }
+        let caller_scopes = std::mem::replace(&mut self.scopes, vec![frame]);
+        self.call_depth += 1;
+        let result = self.execute_statements(&function.body);
+        self.call_depth -= 1;
+        self.scopes = caller_scopes;
+
+        let value = match result? {
+            Flow::Return(value) => value,
+            Flow::Normal => Value::Unit,
+            Flow::Break(span) => return Err(outside_loop_error(span, "break")),
+            Flow::Continue(span) => return Err(outside_loop_error(span, "continue")),
+        };
+        Ok(coerce(value, function.return_type.as_ref()))
+    }
+}
+
+// 计算二元运算，错误信息不含位置（由调用者补充）
+// 字节码虚拟机复用同一套运算语义
+pub(crate) fn binary_op(op: &Operator, left: Value, right: Value) -> Result<Value, String> {
+    use Value::*;
+    match (op, left, right) {
+        // 整数与浮点数混合运算时，整数先提升为浮点数
+        (op, Int(a), Float(b)) => binary_op(op, Float(a as f64), Float(b)),
+        (op, Float(a), Int(b)) => binary_op(op, Float(a), Float(b as f64)),
+        // 整数算术：溢出和除以零都是运行时错误
+        (Operator::Add, Int(a), Int(b)) => a.checked_add(b).map(Int).ok_or_else(|| "整数溢出".to_string()),
+        (Operator::Sub, Int(a), Int(b)) => a.checked_sub(b).map(Int).ok_or_else(|| "整数溢出".to_string()),
+        (Operator::Mul, Int(a), Int(b)) => a.checked_mul(b).map(Int).ok_or_else(|| "整数溢出".to_string()),
+        (Operator::Div, Int(_), Int(0)) => Err("除数不能为零".to_string()),
+        (Operator::Div, Int(a), Int(b)) => a.checked_div(b).map(Int).ok_or_else(|| "整数溢出".to_string()),
+        // 取余和整除向负无穷取整：-7 // 2 为 -4，-7 % 2 为 1
+        (Operator::Mod | Operator::FloorDiv, Int(_), Int(0)) => Err("除数不能为零".to_string()),
+        (Operator::Mod, Int(a), Int(b)) => a
+            .checked_rem(b)
+            .map(|r| if r != 0 && (r < 0) != (b < 0) { r + b } else { r })
+            .map(Int)
+            .ok_or_else(|| "整数溢出".to_string()),
+        (Operator::FloorDiv, Int(a), Int(b)) => a
+            .checked_div(b)
+            .map(|q| if a % b != 0 && (a < 0) != (b < 0) { q - 1 } else { q })
+            .map(Int)
+            .ok_or_else(|| "整数溢出".to_string()),
+        (Operator::Pow, Int(_), Int(b)) if b < 0 => Err("整数的乘方指数不能为负数".to_string()),
+        (Operator::Pow, Int(a), Int(b)) => u32::try_from(b)
+            .ok()
+            .and_then(|b| a.checked_pow(b))
+            .map(Int)
+            .ok_or_else(|| "整数溢出".to_string()),
+        // 浮点数算术
+        (Operator::Add, Float(a), Float(b)) => Ok(Float(a + b)),
+        (Operator::Sub, Float(a), Float(b)) => Ok(Float(a - b)),
+        (Operator::Mul, Float(a), Float(b)) => Ok(Float(a * b)),
+        (Operator::Div, Float(a), Float(b)) => Ok(Float(a / b)),
+        (Operator::Mod | Operator::FloorDiv, Float(_), Float(0.0)) => Err("除数不能为零".to_string()),
+        (Operator::Mod, Float(a), Float(b)) => {
+            let r = a % b;
+            Ok(Float(if r != 0.0 && (r < 0.0) != (b < 0.0) { r + b } else { r }))
+        },
+        (Operator::FloorDiv, Float(a), Float(b)) => Ok(Float((a / b).floor())),
+        (Operator::Pow, Float(a), Float(b)) => Ok(Float(a.powf(b))),
+        // 比较运算：相同类型的值之间比较
+        (Operator::Eq, a, b) if a.type_name() == b.type_name() => Ok(Bool(a == b)),
+        (Operator::Ne, a, b) if a.type_name() == b.type_name() => Ok(Bool(a != b)),
+        (Operator::Lt, Int(a), Int(b)) => Ok(Bool(a < b)),
+        (Operator::Le, Int(a), Int(b)) => Ok(Bool(a <= b)),
+        (Operator::Gt, Int(a), Int(b)) => Ok(Bool(a > b)),
+        (Operator::Ge, Int(a), Int(b)) => Ok(Bool(a >= b)),
+        (Operator::Lt, Float(a), Float(b)) => Ok(Bool(a < b)),
+        (Operator::Le, Float(a), Float(b)) => Ok(Bool(a <= b)),
+        (Operator::Gt, Float(a), Float(b)) => Ok(Bool(a > b)),
+        (Operator::Ge, Float(a), Float(b)) => Ok(Bool(a >= b)),
+        (Operator::Lt, Str(a), Str(b)) => Ok(Bool(a < b)),
+        (Operator::Le, Str(a), Str(b)) => Ok(Bool(a <= b)),
+        (Operator::Gt, Str(a), Str(b)) => Ok(Bool(a > b)),
+        (Operator::Ge, Str(a), Str(b)) => Ok(Bool(a >= b)),
+        (op, a, b) => Err(format!("不支持的运算: {} {:?} {}", a.type_name(), op, b.type_name())),
+    }
+}
+
+// 执行程序并把输出写入out
+pub fn run<W: Write + Send>(statements: &[Statement], out: W) -> Result<(), String> {
+    with_call_stack(|| Interpreter::new(out).execute(statements))
+}
+
+// 测试模块：解释器单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::format_all;
+    use crate::parser::parse_source;
+
+    // 辅助函数：运行源代码并返回输出
+    fn run_source(source: &str) -> Result<String, String> {
+        let ast = parse_source(source).map_err(|d| format_all(&d))?;
+        let mut out = Vec::new();
+        run(&ast, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    // 测试变量、循环和打印
+    #[test]
+    fn test_loops_and_print() {
+        let output = run_source("var i = 1\nwhile i < 4:\n    println(i)\n    i += 1\nfor c in \"ab\" {\n    print c\n}\nprint (2 + 3)").unwrap();
+        assert_eq!(output, "1\n2\n3\nab5");
+    }
+
+    // 测试递归函数和块作用域
+    #[test]
+    fn test_recursive_function() {
+        let source = "fn fib(n: int) -> int:\n    if n < 2:\n        return n\n    return fib(n - 1) + fib(n - 2)\nvar total = 0\nfor i in (0, 10):\n    total = total + fib(i)\nprintln(total)";
+        assert_eq!(run_source(source).unwrap(), "143\n");
+    }
+
+    // 测试浮点数字面量和整数浮点数混合运算
+    #[test]
+    fn test_float_arithmetic() {
+        let output = run_source("var x = 1.5e1\nprintln(x / 4)\nprintln(2 * 0.25)\nprintln(3 eq 3.0)").unwrap();
+        assert_eq!(output, "3.75\n0.5\ntrue\n");
+    }
+
+    // 测试赋值按变量声明的类型转换：浮点变量被赋整数后仍然是浮点数
+    #[test]
+    fn test_assign_keeps_declared_type() {
+        let source = "var f: float = 1\nf = 2\nprintln(f / 4)\nf += 1\nprintln(f / 4)\nvar g = 1.5\ng = 3\nprintln(g / 2)\nfn half(x: float) -> float:\n    x = 1\n    return x / 2\nprintln(half(3))";
+        assert_eq!(run_source(source).unwrap(), "0.5\n0.75\n1.5\n0.5\n");
+    }
+
+    // 测试逻辑运算符短路求值：右侧的除以零不会被计算
+    #[test]
+    fn test_logical_short_circuit() {
+        let source = "var x = 0\nprintln(x != 0 and 10 / x > 1)\nprintln(x == 0 or 10 / x > 1)\nprintln(not x > 1, !true || x < 1)";
+        assert_eq!(run_source(source).unwrap(), "false\ntrue\ntrue\ntrue\n");
+        let err = run_source("var x = 0\nprintln(x == 0 and 10 / x > 1)").unwrap_err();
+        assert!(err.contains("除数不能为零"), "{}", err);
+    }
+
+    // 测试取余和整除向负无穷取整、乘方和除以零错误
+    #[test]
+    fn test_floor_arithmetic() {
+        let source = "var a = 0 - 7\nprintln(a % 2, a // 2, 7 % (0 - 2), 7 // (0 - 2), 2 ** 3 ** 2, 7.5 % 2, 2 ** 0.5)";
+        assert_eq!(run_source(source).unwrap(), "1\n-4\n-1\n-4\n512\n1.5\n1.4142135623730951\n");
+        let err = run_source("var z = 0\nprintln(1 % z)").unwrap_err();
+        assert!(err.starts_with("第2行第9列") && err.contains("除数不能为零"), "{}", err);
+        let err = run_source("println(2 ** (0 - 1))").unwrap_err();
+        assert!(err.contains("指数不能为负数"), "{}", err);
+    }
+
+    // 测试一元负号和逻辑非
+    #[test]
+    fn test_unary_operators() {
+        let source = "var x = -5\nvar flag = false\nprint -x\nprintln(\"\", -x * 2, -(x + 1), - -x, not flag, -2 ** 2, -2.5)";
+        assert_eq!(run_source(source).unwrap(), "5\n10\n4\n-5\ntrue\n-4\n-2.5\n");
+    }
+
+    // 测试while和for循环中的break和continue，以及循环体外的break
+    #[test]
+    fn test_break_continue() {
+        let source = "var i = 0\nwhile true:\n    i += 1\n    if i % 2 == 0:\n        continue\n    if i > 7:\n        break\n    print i\nfor n in (1, 10):\n    if n == 3 { continue }\n    for m in (1, 10):\n        if m > 2: break\n        print n * m\n    if n >= 5: break\nfor c in \"hello\":\n    if c == \"l\": continue\n    print c\nfn first_over(limit: int) -> int:\n    for k in (1, 100):\n        if k * k > limit:\n            return k\n    return 0\nprint first_over(50)";
+        assert_eq!(run_source(source).unwrap(), "1357122448510heo8");
+        let err = run_source("var x = 1\nif x > 0:\n    break").unwrap_err();
+        assert!(err.starts_with("第3行第5列") && err.contains("break语句只能出现在循环体内"), "{}", err);
+        let err = run_source("fn f():\n    continue\nwhile true:\n    f()").unwrap_err();
+        assert!(err.contains("continue语句只能出现在循环体内"), "{}", err);
+    }
+
+    // 测试运行时错误带有位置信息
+    #[test]
+    fn test_runtime_errors() {
+        let err = run_source("var x = 1\nprint x / 0").unwrap_err();
+        assert!(err.starts_with("第2行第7列"), "{}", err);
+        let err = run_source("y = 1").unwrap_err();
+        assert!(err.contains("未定义的变量: y"), "{}", err);
+        let err = run_source("fn f() -> int:\n    return f()\nf()").unwrap_err();
+        assert!(err.contains("嵌套超过"), "{}", err);
+    }
+}
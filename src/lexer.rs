@@ -1,3 +1,10 @@
+// 导入格式化模块，用于实现Span的Display
+use std::fmt;
+// 导入诊断信息类型，用于报告词法错误
+use crate::diagnostic::Diagnostic;
+// 导入日志宏，调试信息输出到lexer目标，默认不显示
+use tracing::{debug, trace};
+
 // Token枚举：定义了Nexa语言中所有可能的词法单元（token）类型
 // Debug: 支持调试打印  PartialEq: 支持相等比较  Clone: 支持克隆
 #[derive(Debug, PartialEq, Clone)]
@@ -13,12 +20,22 @@ pub enum Token {
     If,
     Then,
     Else,
+    // else if的简写：elif
+    Elif,
     End,
     // 循环语句关键字
     For,
     In,
     // While循环关键字
     While,
+    // 函数定义关键字
+    Fn,
+    // 函数返回关键字
+    Return,
+    // 跳出循环关键字
+    Break,
+    // 跳到下一次循环关键字
+    Continue,
     
     // ===== 数据类型关键字 =====
     // 整数类型
@@ -39,6 +56,12 @@ pub enum Token {
     Star,      // *
     // 除法运算符：/
     Slash,     // /
+    // 取余运算符：%
+    Percent,   // %
+    // 整除运算符：//
+    SlashSlash, // //
+    // 乘方运算符：**
+    StarStar,  // **
     // 赋值/相等运算符：=
     Equal,     // =
     // 小于比较运算符：<
@@ -49,6 +72,8 @@ pub enum Token {
     GreaterThan, // >
     // 大于等于比较运算符：>=
     GreaterThanOrEqual, // >=
+    // 返回类型箭头：->
+    Arrow,     // ->
     
     // ===== 关键字形式的运算符 =====
     // 加法运算（关键字形式）：add
@@ -66,6 +91,21 @@ pub enum Token {
     // 大于比较（关键字形式）：gt
     Gt,        // gt
     
+    // ===== 逻辑运算符 =====
+    // 逻辑与：and 或 &&
+    And,       // and
+    AmpAmp,    // &&
+    // 逻辑或：or 或 ||
+    Or,        // or
+    PipePipe,  // ||
+    // 逻辑非：not（优先级低于比较）或 !（紧跟操作数）
+    Not,       // not
+    Bang,      // !
+    // 不等比较：!=
+    NotEqual,  // !=
+    // 相等比较：==
+    EqualEqual, // ==
+    
     // ===== 标识符部分 =====
     // 用户定义的标识符（变量名、函数名等）
     // 包含字符串数据：标识符的名称
@@ -74,6 +114,8 @@ pub enum Token {
     // ===== 字面量部分 =====
     // 整数字面量：64位有符号整数
     Number(i64),
+    // 浮点数字面量：带小数点或指数部分的数字，如3.14、1e-3
+    FloatLiteral(f64),
     // 字符串字面量：文本内容
     StringLiteral(String),
     // 布尔字面量：true/false
@@ -96,16 +138,19 @@ pub enum Token {
     Semicolon, // ;
     // 换行符
     Newline,   // 换行
+    // 缩进增加：冒号代码块开始
+    Indent,    // 缩进
+    // 缩进减少：冒号代码块结束
+    Dedent,    // 取消缩进
 }
 
 // Token实现块：为Token枚举添加方法
-#[allow(dead_code)]
 impl Token {
     // 判断当前token是否为关键字
-    // 返回true如果是Var, Print, Println, If, Else, Int, Float, Bool, StringType, For, In中的任意一个
+    // 返回true如果是Var, Print, Println, If, Else, Elif, Int, Float, Bool, StringType, For, In中的任意一个
     // 注意：Then和End不再是关键字（移除then-end语法支持）
     pub fn is_keyword(&self) -> bool {
-        matches!(self, Token::Var | Token::Print | Token::Println | Token::If | Token::Else | Token::Int | Token::Float | Token::Bool | Token::StringType | Token::For | Token::In)
+        matches!(self, Token::Var | Token::Print | Token::Println | Token::If | Token::Else | Token::Elif | Token::Int | Token::Float | Token::Bool | Token::StringType | Token::For | Token::In | Token::While | Token::Fn | Token::Return | Token::Break | Token::Continue)
     }
     
     // 返回true如果是BoolLiteral
@@ -114,111 +159,286 @@ impl Token {
     }
     
     // 判断是否为运算符（符号形式）
-    // 返回true如果是Plus, Minus, Star, Slash, Equal, LessThan, LessThanOrEqual, GreaterThan, GreaterThanOrEqual中的任意一个
+    // 返回true如果是Plus, Minus, Star, Slash, Percent, SlashSlash, StarStar, Equal, LessThan, LessThanOrEqual, GreaterThan, GreaterThanOrEqual,
+    // EqualEqual, NotEqual, AmpAmp, PipePipe, Bang中的任意一个
     pub fn is_operator(&self) -> bool {
-        matches!(self, Token::Plus | Token::Minus | Token::Star | Token::Slash | Token::Equal | Token::LessThan | Token::LessThanOrEqual | Token::GreaterThan | Token::GreaterThanOrEqual)
+        matches!(self, Token::Plus | Token::Minus | Token::Star | Token::Slash | Token::Percent | Token::SlashSlash | Token::StarStar | Token::Equal | Token::LessThan | Token::LessThanOrEqual | Token::GreaterThan | Token::GreaterThanOrEqual | Token::EqualEqual | Token::NotEqual | Token::AmpAmp | Token::PipePipe | Token::Bang)
     }
     
     // 判断当前token是否为关键字形式的运算符
-    // 返回true如果是Add, Sub, Mul, Div, Eq, Lt, Gt中的任意一个
+    // 返回true如果是Add, Sub, Mul, Div, Eq, Lt, Gt, And, Or, Not中的任意一个
     pub fn is_keyword_operator(&self) -> bool {
-        matches!(self, Token::Add | Token::Sub | Token::Mul | Token::Div | Token::Eq | Token::Lt | Token::Gt)
+        matches!(self, Token::Add | Token::Sub | Token::Mul | Token::Div | Token::Eq | Token::Lt | Token::Gt | Token::And | Token::Or | Token::Not)
     }
 }
 
-// 测试模块：包含单元测试函数
-// 仅在测试模式下编译（#[cfg(test)]）
-#[cfg(test)]
-mod tests {
-    // 导入父模块的所有内容
-    use super::*;
-    
-    // 测试完整的var语句词法分析
-    #[test]
-    fn test_tokenize_var() {
-        // 定义测试输入：变量声明语句
-        let input = "var x=10";
-        // 调用词法分析函数
-        let result = tokenize(input);
-        // 打印输入和结果，用于调试
-        println!("Input: {:?}, Result: {:?}", input, result);
-        // 断言结果必须是Ok（成功）
-        assert!(result.is_ok());
+// Span结构体：记录一个token或语法节点在源文件中的位置
+// 行号和列号从1开始（列号按字符计数），start/end为字节偏移（end不包含）
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Span {
+    // 起始行号
+    pub line: usize,
+    // 起始列号
+    pub column: usize,
+    // 起始字节偏移
+    pub start: usize,
+    // 结束字节偏移
+    pub end: usize,
+}
+
+impl Span {
+    // 创建新的Span
+    pub fn new(line: usize, column: usize, start: usize, end: usize) -> Self {
+        Span { line, column, start, end }
     }
-    
-    // 测试简单词法分析功能
-    #[test]
-    fn test_tokenize_simple() {
-        // 测试单个关键字"var"
-        let result = tokenize("var");
-        println!("测试 'var': {:?}", result);
-        // 断言必须成功
-        assert!(result.is_ok());
-        
-        // 测试关键字+标识符"var x"
-        let result = tokenize("var x");
-        println!("测试 'var x': {:?}", result);
-        // 断言必须成功
-        assert!(result.is_ok());
-        
-        // 测试完整的变量声明语句"var x=10"
-        let result = tokenize("var x=10");
-        println!("测试 'var x=10': {:?}", result);
-        // 断言必须成功
-        assert!(result.is_ok());
+
+    // 合并两个Span：从self的起点一直覆盖到other的终点
+    // 用于由多个token组成的表达式和语句
+    pub fn to(self, other: Span) -> Span {
+        if other.end < self.start {
+            return self;
+        }
+        Span {
+            line: self.line,
+            column: self.column,
+            start: self.start,
+            end: other.end.max(self.end),
+        }
+    }
+}
+
+// 以"第X行第Y列"的形式显示位置，用于错误信息
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "第{}行第{}列", self.line, self.column)
+    }
+}
+
+// 带位置信息的token：词法分析器的输出单元
+#[derive(Debug, PartialEq, Clone)]
+pub struct SpannedToken {
+    // token本身
+    pub token: Token,
+    // token在源文件中的位置
+    pub span: Span,
+}
+
+impl SpannedToken {
+    // 创建新的带位置token
+    pub fn new(token: Token, span: Span) -> Self {
+        SpannedToken { token, span }
+    }
+}
+
+// 词法错误：每种错误带有出错位置和稳定的错误代码（E00xx）
+#[derive(Debug, Clone, PartialEq)]
+pub enum LexError {
+    // E0001 无法识别的字符
+    UnexpectedChar { ch: char, span: Span },
+    // E0002 字符串缺少结束的双引号
+    UnterminatedString { span: Span },
+    // E0003 数字字面量无效，例如超出i64范围或浮点数溢出
+    InvalidNumber { text: String, span: Span },
+    // E0004 缩进减少后没有回到任何一层已有的缩进
+    InconsistentIndent { span: Span },
+    // E0005 字符串中无效的转义序列
+    InvalidEscape { ch: char, span: Span },
+}
+
+impl LexError {
+    // 稳定的错误代码，工具可以据此匹配错误种类
+    pub fn code(&self) -> &'static str {
+        match self {
+            LexError::UnexpectedChar { .. } => "E0001",
+            LexError::UnterminatedString { .. } => "E0002",
+            LexError::InvalidNumber { .. } => "E0003",
+            LexError::InconsistentIndent { .. } => "E0004",
+            LexError::InvalidEscape { .. } => "E0005",
+        }
+    }
+
+    // 出错的源代码位置
+    pub fn span(&self) -> Span {
+        match self {
+            LexError::UnexpectedChar { span, .. }
+            | LexError::UnterminatedString { span }
+            | LexError::InvalidNumber { span, .. }
+            | LexError::InconsistentIndent { span }
+            | LexError::InvalidEscape { span, .. } => *span,
+        }
+    }
+
+    // 错误消息（不含位置）
+    pub fn message(&self) -> String {
+        match self {
+            LexError::UnexpectedChar { ch, .. } => format!("无法识别的字符: '{}'", ch),
+            LexError::UnterminatedString { .. } => "字符串缺少结束的双引号".to_string(),
+            LexError::InvalidNumber { text, .. } => format!("无效的数字: {}", text),
+            LexError::InconsistentIndent { .. } => "缩进不一致：没有与之匹配的外层缩进级别".to_string(),
+            LexError::InvalidEscape { ch, .. } => format!("无效的转义序列: '\\{}'", ch),
+        }
+    }
+}
+
+impl From<LexError> for Diagnostic {
+    fn from(error: LexError) -> Self {
+        Diagnostic::error(error.message(), error.span()).with_code(error.code())
+    }
+}
+
+// 以诊断的单行格式显示："第2行第9列: 错误[E0001]: 无法识别的字符: '@'"
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Diagnostic::from(self.clone()))
+    }
+}
+
+// 行号索引：记录每一行起始的字节偏移，用于把字节偏移换算成行列号
+struct LineIndex<'a> {
+    // 原始输入，用于按字符计算列号
+    input: &'a str,
+    // 每一行第一个字节的偏移
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    // 扫描输入中的换行符，建立行起始偏移表
+    fn new(input: &'a str) -> Self {
+        let mut line_starts = vec![0];
+        for (i, c) in input.char_indices() {
+            if c == '\n' {
+                line_starts.push(i + 1);
+            }
+        }
+        LineIndex { input, line_starts }
+    }
+
+    // 根据字节范围生成Span
+    fn span(&self, start: usize, end: usize) -> Span {
+        // 找到start所在的行（最后一个起始偏移不大于start的行）
+        let line = match self.line_starts.binary_search(&start) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        };
+        let line_start = self.line_starts[line];
+        let column = self.input[line_start..start].chars().count() + 1;
+        Span::new(line + 1, column, start, end)
     }
 }
 
 // 词法分析器主函数：将输入字符串转换为token序列
 // 参数：input - 输入的源代码字符串
-// 返回：成功时返回token向量，失败时返回错误字符串
+// 返回：成功时返回带位置信息的token向量，失败时返回所有词法错误
 // 版本：2024-12-19 更新 - 支持缩进风格语法
-pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
-    // 添加调试输出 - 打印原始输入信息
-    println!("DEBUG: 输入字符串: {:?}", input);
-    // 打印输入字符串的长度（字符数）
-    println!("DEBUG: 输入长度: {}", input.len());
-    // 将输入字符串分解为字符向量，便于调试分析
-    println!("DEBUG: 输入字符: {:?}", input.chars().collect::<Vec<_>>());
+pub fn tokenize(input: &str) -> Result<Vec<SpannedToken>, Vec<LexError>> {
+    let (tokens, errors) = tokenize_with_recovery(input);
+    if errors.is_empty() {
+        Ok(tokens)
+    } else {
+        Err(errors)
+    }
+}
+
+// 带错误恢复的词法分析：遇到错误时记录诊断并跳过出错的字符继续分析
+// 返回已识别的token和所有词法错误，供语法分析器在有词法错误时继续报告语法错误
+pub fn tokenize_with_recovery(input: &str) -> (Vec<SpannedToken>, Vec<LexError>) {
+    // 调试日志：打印原始输入信息
+    debug!(target: "lexer", "开始词法分析，输入长度: {} 字节", input.len());
+    trace!(target: "lexer", "输入字符串: {:?}", input);
     
     // 创建空的token向量，用于存储分析结果
     let mut tokens = Vec::new();
+    // 收集到的词法错误
+    let mut errors = Vec::new();
     // 创建字符迭代器，支持peek操作（查看下一个字符但不消耗）
     let mut chars = input.chars().peekable();
-    // 当前位置计数器，用于调试和错误报告
+    // 当前位置计数器（字节偏移），用于生成Span和错误报告
     let mut position = 0;
-    // 是否在行首
-    let mut _at_line_start = true;
+    // 行号索引，用于把字节偏移换算成行列号
+    let line_index = LineIndex::new(input);
+    // 是否在行首（行首需要计算缩进）
+    let mut at_line_start = true;
+    // 缩进栈：记录每一层代码块的缩进宽度，栈底始终为0
+    let mut indent_stack: Vec<usize> = vec![0];
+    // 圆括号嵌套深度：括号内的换行不参与缩进计算
+    let mut paren_depth = 0;
+    // 每个未闭合的花括号出现时缩进栈的深度
+    // 花括号代码块内的Indent由语法分析器跳过，遇到'}'时丢弃块内新增的缩进层，不再为它们生成Dedent
+    let mut brace_indent_depths: Vec<usize> = Vec::new();
     
     // 主循环：遍历输入字符串的每个字符
     while let Some(c) = chars.peek() {
-        // 打印当前位置和字符，用于调试
-        println!("DEBUG: 当前位置: {}, 当前字符: {:?}", position, c);
+        // 记录当前token的起始偏移
+        let start = position;
+        
+        // 行首处理：计算缩进宽度并生成Indent/Dedent token
+        if at_line_start {
+            at_line_start = false;
+            
+            // 统计行首空白的宽度（制表符按4个空格计算）
+            let mut width = 0;
+            while let Some(&ws) = chars.peek() {
+                match ws {
+                    ' ' => width += 1,
+                    '\t' => width += 4,
+                    _ => break,
+                }
+                chars.next();
+                position += 1;
+            }
+            
+            // 空行不影响缩进
+            let blank_line = matches!(chars.peek(), None | Some('\n') | Some('\r'));
+            if !blank_line && paren_depth == 0 {
+                let current = *indent_stack.last().unwrap();
+                if width > current {
+                    // 缩进增加：进入新的代码块
+                    indent_stack.push(width);
+                    tokens.push(SpannedToken::new(Token::Indent, line_index.span(start, position)));
+                    trace!(target: "lexer", "生成缩进token，宽度: {}", width);
+                } else if width < current {
+                    // 缩进减少：逐层退出代码块
+                    while width < *indent_stack.last().unwrap() {
+                        indent_stack.pop();
+                        tokens.push(SpannedToken::new(Token::Dedent, line_index.span(position, position)));
+                        trace!(target: "lexer", "生成取消缩进token");
+                    }
+                    // 退出后必须正好回到某一层已有的缩进
+                    if width != *indent_stack.last().unwrap() {
+                        errors.push(LexError::InconsistentIndent { span: line_index.span(start, position) });
+                    }
+                }
+            }
+            continue;
+        }
+        
+        // 跟踪日志：当前位置和字符
+        trace!(target: "lexer", "当前位置: {}, 当前字符: {:?}", position, c);
         
         // 根据当前字符的类型进行匹配处理
         match c {
             // 空白字符匹配分支：处理空格、制表符、换行符等空白字符
             ' ' | '\t' | '\n' | '\r' => {
-                // 打印调试信息，确认正在处理空白字符
-                println!("DEBUG: 处理空白字符: {:?}", c);
+                // 跟踪日志：确认正在处理空白字符
+                trace!(target: "lexer", "处理空白字符: {:?}", c);
                 
                 // 处理换行符
                 if *c == '\n' {
                     // 生成换行token
-                    tokens.push(Token::Newline);
-                    println!("DEBUG: 生成换行token");
+                    tokens.push(SpannedToken::new(Token::Newline, line_index.span(start, start + 1)));
+                    trace!(target: "lexer", "生成换行token");
                     
-                    // 重置行首状态
-                    _at_line_start = true;
+                    // 重置行首状态，下一行开始时计算缩进
+                    at_line_start = true;
                 } 
                 // 处理回车符（Windows风格换行符的一部分）
                 else if *c == '\r' {
                     // 只处理回车符，不生成token
-                    println!("DEBUG: 处理回车符");
+                    trace!(target: "lexer", "处理回车符");
                 }
                 // 忽略其他空白字符（空格和制表符）
                 else {
-                    println!("DEBUG: 跳过空白字符: {:?}", c);
+                    trace!(target: "lexer", "跳过空白字符: {:?}", c);
                 }
 
                 // 消耗当前字符（移动到下一个字符）
@@ -230,10 +450,10 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
             // ===== 运算符和特殊符号匹配分支 =====
             // 加法运算符匹配：遇到'+'字符
             '+' => {
-                // 打印调试信息，确认匹配到加号
-                println!("DEBUG: 匹配加号");
+                // 跟踪日志：确认匹配到加号
+                trace!(target: "lexer", "匹配加号");
                 // 创建Plus token并添加到结果向量
-                tokens.push(Token::Plus);
+                tokens.push(SpannedToken::new(Token::Plus, line_index.span(start, start + 1)));
                 // 消耗当前字符（移动到下一个字符）
                 chars.next();
                 // 位置计数器加1
@@ -241,32 +461,82 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
             }
             // 减法运算符匹配：遇到'-'字符
             '-' => {
-                // 打印调试信息，确认匹配到减号
-                println!("DEBUG: 匹配减号");
-                // 创建Minus token并添加到结果向量
-                tokens.push(Token::Minus);
                 // 消耗当前字符（移动到下一个字符）
                 chars.next();
                 // 位置计数器加1
                 position += 1;
+                
+                // 检查下一个字符是否为'>'，如果是则匹配返回类型箭头->
+                if let Some('>') = chars.peek() {
+                    // 跟踪日志：确认匹配到箭头
+                    trace!(target: "lexer", "匹配箭头");
+                    // 创建Arrow token并添加到结果向量
+                    tokens.push(SpannedToken::new(Token::Arrow, line_index.span(start, start + 2)));
+                    // 消耗'>'字符
+                    chars.next();
+                    // 位置计数器加1
+                    position += 1;
+                } else {
+                    // 跟踪日志：确认匹配到减号
+                    trace!(target: "lexer", "匹配减号");
+                    // 创建Minus token并添加到结果向量
+                    tokens.push(SpannedToken::new(Token::Minus, line_index.span(start, position)));
+                }
             }
             // 乘法运算符匹配：遇到'*'字符
             '*' => {
-                // 打印调试信息，确认匹配到乘号
-                println!("DEBUG: 匹配乘号");
-                // 创建Star token并添加到结果向量
-                tokens.push(Token::Star);
                 // 消耗当前字符（移动到下一个字符）
                 chars.next();
                 // 位置计数器加1
                 position += 1;
+                
+                // 检查下一个字符是否为'*'，如果是则匹配**乘方
+                if let Some('*') = chars.peek() {
+                    // 跟踪日志：确认匹配到乘方
+                    trace!(target: "lexer", "匹配乘方");
+                    // 创建StarStar token并添加到结果向量
+                    tokens.push(SpannedToken::new(Token::StarStar, line_index.span(start, start + 2)));
+                    // 消耗第二个'*'字符
+                    chars.next();
+                    // 位置计数器加1
+                    position += 1;
+                } else {
+                    // 跟踪日志：确认匹配到乘号
+                    trace!(target: "lexer", "匹配乘号");
+                    // 创建Star token并添加到结果向量
+                    tokens.push(SpannedToken::new(Token::Star, line_index.span(start, position)));
+                }
             }
             // 除法运算符匹配：遇到'/'字符
             '/' => {
-                // 打印调试信息，确认匹配到除号
-                println!("DEBUG: 匹配除号");
-                // 创建Slash token并添加到结果向量
-                tokens.push(Token::Slash);
+                // 消耗当前字符（移动到下一个字符）
+                chars.next();
+                // 位置计数器加1
+                position += 1;
+                
+                // 检查下一个字符是否为'/'，如果是则匹配//整除
+                if let Some('/') = chars.peek() {
+                    // 跟踪日志：确认匹配到整除
+                    trace!(target: "lexer", "匹配整除");
+                    // 创建SlashSlash token并添加到结果向量
+                    tokens.push(SpannedToken::new(Token::SlashSlash, line_index.span(start, start + 2)));
+                    // 消耗第二个'/'字符
+                    chars.next();
+                    // 位置计数器加1
+                    position += 1;
+                } else {
+                    // 跟踪日志：确认匹配到除号
+                    trace!(target: "lexer", "匹配除号");
+                    // 创建Slash token并添加到结果向量
+                    tokens.push(SpannedToken::new(Token::Slash, line_index.span(start, position)));
+                }
+            }
+            // 取余运算符匹配：遇到'%'字符
+            '%' => {
+                // 跟踪日志：确认匹配到取余
+                trace!(target: "lexer", "匹配取余");
+                // 创建Percent token并添加到结果向量
+                tokens.push(SpannedToken::new(Token::Percent, line_index.span(start, start + 1)));
                 // 消耗当前字符（移动到下一个字符）
                 chars.next();
                 // 位置计数器加1
@@ -274,14 +544,74 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
             }
             // 赋值运算符匹配：遇到'='字符
             '=' => {
-                // 打印调试信息，确认匹配到等号
-                println!("DEBUG: 匹配等号");
-                // 创建Equal token并添加到结果向量
-                tokens.push(Token::Equal);
                 // 消耗当前字符（移动到下一个字符）
                 chars.next();
                 // 位置计数器加1
                 position += 1;
+                
+                // 检查下一个字符是否为'='，如果是则匹配==相等比较
+                if let Some('=') = chars.peek() {
+                    // 跟踪日志：确认匹配到双等号
+                    trace!(target: "lexer", "匹配双等号");
+                    // 创建EqualEqual token并添加到结果向量
+                    tokens.push(SpannedToken::new(Token::EqualEqual, line_index.span(start, start + 2)));
+                    // 消耗第二个'='字符
+                    chars.next();
+                    // 位置计数器加1
+                    position += 1;
+                } else {
+                    // 跟踪日志：确认匹配到等号
+                    trace!(target: "lexer", "匹配等号");
+                    // 创建Equal token并添加到结果向量
+                    tokens.push(SpannedToken::new(Token::Equal, line_index.span(start, position)));
+                }
+            }
+            // 逻辑非运算符匹配：遇到'!'字符
+            '!' => {
+                // 消耗当前字符（移动到下一个字符）
+                chars.next();
+                // 位置计数器加1
+                position += 1;
+                
+                // 检查下一个字符是否为'='，如果是则匹配!=不等比较
+                if let Some('=') = chars.peek() {
+                    // 跟踪日志：确认匹配到不等号
+                    trace!(target: "lexer", "匹配不等号");
+                    // 创建NotEqual token并添加到结果向量
+                    tokens.push(SpannedToken::new(Token::NotEqual, line_index.span(start, start + 2)));
+                    // 消耗'='字符
+                    chars.next();
+                    // 位置计数器加1
+                    position += 1;
+                } else {
+                    // 跟踪日志：确认匹配到感叹号
+                    trace!(target: "lexer", "匹配感叹号");
+                    // 创建Bang token并添加到结果向量
+                    tokens.push(SpannedToken::new(Token::Bang, line_index.span(start, position)));
+                }
+            }
+            // 逻辑与、逻辑或运算符匹配：'&&'和'||'，单个'&'或'|'是无法识别的字符
+            '&' | '|' => {
+                let c = *c;
+                // 消耗当前字符（移动到下一个字符）
+                chars.next();
+                // 位置计数器加1
+                position += 1;
+                
+                if chars.peek() == Some(&c) {
+                    // 跟踪日志：确认匹配到逻辑运算符
+                    trace!(target: "lexer", "匹配逻辑运算符 {}{}", c, c);
+                    let token = if c == '&' { Token::AmpAmp } else { Token::PipePipe };
+                    tokens.push(SpannedToken::new(token, line_index.span(start, start + 2)));
+                    // 消耗第二个字符
+                    chars.next();
+                    // 位置计数器加1
+                    position += 1;
+                } else {
+                    // 跟踪日志：显示遇到的未知字符
+                    trace!(target: "lexer", "无法识别的字符: {:?}", c);
+                    errors.push(LexError::UnexpectedChar { ch: c, span: line_index.span(start, position) });
+                }
             }
             // 小于运算符匹配：遇到'<'字符
             '<' => {
@@ -292,19 +622,19 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
                 
                 // 检查下一个字符是否为'='，如果是则匹配<=复合运算符
                 if let Some('=') = chars.peek() {
-                    // 打印调试信息，确认匹配到小于等于号
-                    println!("DEBUG: 匹配小于等于号");
+                    // 跟踪日志：确认匹配到小于等于号
+                    trace!(target: "lexer", "匹配小于等于号");
                     // 创建LessThanOrEqual token并添加到结果向量
-                    tokens.push(Token::LessThanOrEqual);
+                    tokens.push(SpannedToken::new(Token::LessThanOrEqual, line_index.span(start, start + 2)));
                     // 消耗'='字符
                     chars.next();
                     // 位置计数器加1
                     position += 1;
                 } else {
-                    // 打印调试信息，确认匹配到小于号
-                    println!("DEBUG: 匹配小于号");
+                    // 跟踪日志：确认匹配到小于号
+                    trace!(target: "lexer", "匹配小于号");
                     // 创建LessThan token并添加到结果向量
-                    tokens.push(Token::LessThan);
+                    tokens.push(SpannedToken::new(Token::LessThan, line_index.span(start, position)));
                 }
             }
             // 大于运算符匹配：遇到'>'字符
@@ -316,27 +646,29 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
                 
                 // 检查下一个字符是否为'='，如果是则匹配>=复合运算符
                 if let Some('=') = chars.peek() {
-                    // 打印调试信息，确认匹配到大于等于号
-                    println!("DEBUG: 匹配大于等于号");
+                    // 跟踪日志：确认匹配到大于等于号
+                    trace!(target: "lexer", "匹配大于等于号");
                     // 创建GreaterThanOrEqual token并添加到结果向量
-                    tokens.push(Token::GreaterThanOrEqual);
+                    tokens.push(SpannedToken::new(Token::GreaterThanOrEqual, line_index.span(start, start + 2)));
                     // 消耗'='字符
                     chars.next();
                     // 位置计数器加1
                     position += 1;
                 } else {
-                    // 打印调试信息，确认匹配到大于号
-                    println!("DEBUG: 匹配大于号");
+                    // 跟踪日志：确认匹配到大于号
+                    trace!(target: "lexer", "匹配大于号");
                     // 创建GreaterThan token并添加到结果向量
-                    tokens.push(Token::GreaterThan);
+                    tokens.push(SpannedToken::new(Token::GreaterThan, line_index.span(start, position)));
                 }
             }
             // 左括号匹配：遇到'('字符
             '(' => {
-                // 打印调试信息，确认匹配到左括号
-                println!("DEBUG: 匹配左括号");
+                // 跟踪日志：确认匹配到左括号
+                trace!(target: "lexer", "匹配左括号");
                 // 创建LParen token并添加到结果向量
-                tokens.push(Token::LParen);
+                tokens.push(SpannedToken::new(Token::LParen, line_index.span(start, start + 1)));
+                // 进入括号，括号内不计算缩进
+                paren_depth += 1;
                 // 消耗当前字符（移动到下一个字符）
                 chars.next();
                 // 位置计数器加1
@@ -344,10 +676,14 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
             }
             // 右括号匹配：遇到')'字符
             ')' => {
-                // 打印调试信息，确认匹配到右括号
-                println!("DEBUG: 匹配右括号");
+                // 跟踪日志：确认匹配到右括号
+                trace!(target: "lexer", "匹配右括号");
                 // 创建RParen token并添加到结果向量
-                tokens.push(Token::RParen);
+                tokens.push(SpannedToken::new(Token::RParen, line_index.span(start, start + 1)));
+                // 离开括号
+                if paren_depth > 0 {
+                    paren_depth -= 1;
+                }
                 // 消耗当前字符（移动到下一个字符）
                 chars.next();
                 // 位置计数器加1
@@ -355,79 +691,33 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
             }
             // 逗号匹配：遇到','字符
             ',' => {
-                // 打印调试信息，确认匹配到逗号
-                println!("DEBUG: 匹配逗号");
+                // 跟踪日志：确认匹配到逗号
+                trace!(target: "lexer", "匹配逗号");
                 // 创建Comma token并添加到结果向量
-                tokens.push(Token::Comma);
+                tokens.push(SpannedToken::new(Token::Comma, line_index.span(start, start + 1)));
                 // 消耗当前字符（移动到下一个字符）
                 chars.next();
                 // 位置计数器加1
                 position += 1;
             }
-            // 冒号匹配：遇到':'字符（用于类型注解或Python风格语法）
+            // 冒号匹配：遇到':'字符（用于类型注解或Python风格代码块）
+            // 冒号后的换行和缩进由行首处理生成Newline/Indent token
             ':' => {
-                // 首先生成冒号token
-                tokens.push(Token::Colon);
+                // 跟踪日志：确认匹配到冒号
+                trace!(target: "lexer", "匹配冒号");
+                // 创建Colon token并添加到结果向量
+                tokens.push(SpannedToken::new(Token::Colon, line_index.span(start, start + 1)));
+                // 消耗当前字符（移动到下一个字符）
                 chars.next();
+                // 位置计数器加1
                 position += 1;
-                
-                // 检查是否是Python风格语法（冒号后跟空白字符和换行符）
-                // 我们需要预检查后面的字符，但不能消耗它们
-                let mut temp_chars = chars.clone();
-                let mut found_newline = false;
-                
-                // 跳过可能的空白字符
-                while let Some(&next_c) = temp_chars.peek() {
-                    if next_c == ' ' || next_c == '\t' {
-                        temp_chars.next();
-                    } else if next_c == '\n' {
-                        // 找到换行符，这是Python风格语法
-                        found_newline = true;
-                        break;
-                    } else {
-                        // 遇到非空白非换行字符，不是Python风格语法
-                        break;
-                    }
-                }
-                
-                if found_newline {
-
-                    // Python风格冒号语法，我们需要消耗掉冒号后的空白字符和换行符
-                    // 并生成换行token，保持与正常换行处理的一致性
-                    
-                    // 消耗冒号后的空白字符
-                    while let Some(&next_c) = chars.peek() {
-                        if next_c == ' ' || next_c == '\t' {
-                            chars.next();
-                            position += 1;
-                        } else {
-                            break;
-                        }
-                    }
-                    
-                    // 消耗换行符并生成Newline token
-                    if let Some('\n') = chars.peek() {
-                        // 生成换行token
-                        tokens.push(Token::Newline);
-                        println!("DEBUG: 生成换行token（Python风格冒号后）");
-                        chars.next();
-                        position += 1;
-                    }
-                    
-                    // 重置状态（只需要设置_at_line_start为true，不需要重置current_indent）
-                    _at_line_start = true;
-                    
-
-                } else {
-                    println!("DEBUG: 匹配普通冒号（类型注解或同一行代码块）");
-                }
             }
             // 分号匹配：遇到';'字符
             ';' => {
-                // 打印调试信息，确认匹配到分号
-                println!("DEBUG: 匹配分号");
+                // 跟踪日志：确认匹配到分号
+                trace!(target: "lexer", "匹配分号");
                 // 创建Semicolon token并添加到结果向量
-                tokens.push(Token::Semicolon);
+                tokens.push(SpannedToken::new(Token::Semicolon, line_index.span(start, start + 1)));
                 // 消耗当前字符（移动到下一个字符）
                 chars.next();
                 // 位置计数器加1
@@ -435,10 +725,11 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
             }
             // 左花括号匹配：遇到'{'字符
             '{' => {
-                // 打印调试信息，确认匹配到左花括号
-                println!("DEBUG: 匹配左花括号");
+                // 跟踪日志：确认匹配到左花括号
+                trace!(target: "lexer", "匹配左花括号");
                 // 创建LBrace token并添加到结果向量
-                tokens.push(Token::LBrace);
+                tokens.push(SpannedToken::new(Token::LBrace, line_index.span(start, start + 1)));
+                brace_indent_depths.push(indent_stack.len());
                 // 消耗当前字符（移动到下一个字符）
                 chars.next();
                 // 位置计数器加1
@@ -446,10 +737,13 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
             }
             // 右花括号匹配：遇到'}'字符
             '}' => {
-                // 打印调试信息，确认匹配到右花括号
-                println!("DEBUG: 匹配右花括号");
+                // 跟踪日志：确认匹配到右花括号
+                trace!(target: "lexer", "匹配右花括号");
                 // 创建RBrace token并添加到结果向量
-                tokens.push(Token::RBrace);
+                tokens.push(SpannedToken::new(Token::RBrace, line_index.span(start, start + 1)));
+                if let Some(depth) = brace_indent_depths.pop() {
+                    indent_stack.truncate(depth);
+                }
                 // 消耗当前字符（移动到下一个字符）
                 chars.next();
                 // 位置计数器加1
@@ -457,16 +751,20 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
             }
             // 字符串字面量匹配：遇到'"'字符（双引号）
             '"' => {
-                // 打印调试信息，确认开始匹配字符串
-                println!("DEBUG: 匹配字符串字面量");
+                // 跟踪日志：确认开始匹配字符串
+                trace!(target: "lexer", "匹配字符串字面量");
                 // 消耗开头的双引号（移动到下一个字符）
                 chars.next();
                 // 位置计数器加1
                 position += 1;
                 // 创建空字符串用于存储字符串内容
                 let mut content = String::new();
+                // 是否遇到了结束的双引号
+                let mut terminated = false;
                 
                 // 循环读取字符串内容，直到遇到结束的双引号
+                // 字符串不能跨行，遇到换行说明缺少结束的双引号
+                // 转义序列在这里处理，所有后端得到的都是处理后的字符串内容
                 while let Some(c) = chars.peek() {
                     // 检查是否遇到结束的双引号
                     if *c == '"' {
@@ -474,28 +772,59 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
                         chars.next();
                         // 位置计数器加1
                         position += 1;
+                        terminated = true;
                         // 跳出内层循环，字符串解析完成
                         break;
+                    } else if *c == '\n' {
+                        break;
+                    } else if *c == '\\' {
+                        // 转义序列：反斜杠和后面的一个字符
+                        let escape_start = position;
+                        chars.next();
+                        position += 1;
+                        match chars.peek().copied() {
+                            Some(escaped @ ('n' | 't' | 'r' | '0' | '\\' | '"')) => {
+                                content.push(match escaped {
+                                    'n' => '\n',
+                                    't' => '\t',
+                                    'r' => '\r',
+                                    '0' => '\0',
+                                    other => other,
+                                });
+                                chars.next();
+                                position += 1;
+                            },
+                            // 换行或输入结束：由外层报告缺少结束的双引号
+                            Some('\n') | None => {},
+                            Some(other) => {
+                                chars.next();
+                                position += other.len_utf8();
+                                errors.push(LexError::InvalidEscape { ch: other, span: line_index.span(escape_start, position) });
+                            },
+                        }
                     } else {
                         // 将当前字符添加到字符串内容中
                         content.push(*c);
+                        // 位置计数器前进该字符的字节长度（字符串中可能包含非ASCII字符）
+                        position += c.len_utf8();
                         // 消耗当前字符（移动到下一个字符）
                         chars.next();
-                        // 位置计数器加1
-                        position += 1;
                     }
                 }
                 
-                // 将字符串token添加到结果列表
-                tokens.push(Token::StringLiteral(content.clone()));
-                // 打印调试信息，显示解析到的字符串内容
-                println!("DEBUG: 字符串内容: {:?}", content);
+                if !terminated {
+                    errors.push(LexError::UnterminatedString { span: line_index.span(start, position) });
+                }
+                // 将字符串token添加到结果列表（未结束的字符串也保留，避免后续出现连锁的语法错误）
+                tokens.push(SpannedToken::new(Token::StringLiteral(content.clone()), line_index.span(start, position)));
+                // 跟踪日志：显示解析到的字符串内容
+                trace!(target: "lexer", "字符串内容: {:?}", content);
             }
             
             // 数字字面量匹配：遇到数字字符（0-9）
             '0'..='9' => {
-                // 打印调试信息，确认开始匹配数字
-                println!("DEBUG: 匹配数字");
+                // 跟踪日志：确认开始匹配数字
+                trace!(target: "lexer", "匹配数字");
                 // 创建空字符串用于存储数字文本
                 let mut num_str = String::new();
                 
@@ -515,14 +844,18 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
                     }
                 }
                 
+                // 是否带有小数部分或指数部分
+                let mut is_float = false;
+                
                 // 检查是否遇到小数点，支持浮点数
                 if let Some(&'.') = chars.peek() {
                     // 消耗小数点
                     chars.next();
                     position += 1;
                     num_str.push('.');
+                    is_float = true;
                     
-                    // 读取小数部分
+                    // 读取小数部分（允许为空，如"1."）
                     while let Some(c) = chars.peek() {
                         if c.is_ascii_digit() {
                             num_str.push(*c);
@@ -532,16 +865,48 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
                             break;
                         }
                     }
-                    
-                    // 尝试解析为f64浮点数
+                }
+                
+                // 检查指数部分：e或E，后跟可选的正负号和至少一位数字
+                if let Some(&('e' | 'E')) = chars.peek() {
+                    let mut lookahead = chars.clone();
+                    lookahead.next();
+                    let has_sign = matches!(lookahead.peek(), Some(&('+' | '-')));
+                    if has_sign {
+                        lookahead.next();
+                    }
+                    if lookahead.peek().is_some_and(|c| c.is_ascii_digit()) {
+                        // 消耗e和正负号
+                        num_str.push('e');
+                        chars.next();
+                        position += 1;
+                        if has_sign {
+                            num_str.push(chars.next().unwrap());
+                            position += 1;
+                        }
+                        // 读取指数数字
+                        while let Some(c) = chars.peek() {
+                            if c.is_ascii_digit() {
+                                num_str.push(*c);
+                                chars.next();
+                                position += 1;
+                            } else {
+                                break;
+                            }
+                        }
+                        is_float = true;
+                    }
+                }
+                
+                if is_float {
+                    // 解析为f64浮点数，超出范围（如1e999）视为错误
                     match num_str.parse::<f64>() {
-                        Ok(num) => {
-                            // 将浮点数转换为i64（简化处理，后续可扩展Token支持浮点类型）
-                            tokens.push(Token::Number(num as i64));
-                            println!("DEBUG: 浮点数值: {}", num);
+                        Ok(num) if num.is_finite() => {
+                            tokens.push(SpannedToken::new(Token::FloatLiteral(num), line_index.span(start, position)));
+                            trace!(target: "lexer", "浮点数值: {}", num);
                         },
-                        Err(_) => {
-                            return Err(format!("无效的浮点数: {}", num_str));
+                        _ => {
+                            errors.push(LexError::InvalidNumber { text: num_str, span: line_index.span(start, position) });
                         }
                     }
                 } else {
@@ -549,13 +914,13 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
                     match num_str.parse::<i64>() {
                         Ok(num) => {
                             // 解析成功，创建Number token并添加到结果向量
-                            tokens.push(Token::Number(num));
-                            // 打印调试信息，显示解析到的数字值
-                            println!("DEBUG: 数字值: {}", num);
+                            tokens.push(SpannedToken::new(Token::Number(num), line_index.span(start, position)));
+                            // 跟踪日志：显示解析到的数字值
+                            trace!(target: "lexer", "数字值: {}", num);
                         },
                         Err(_) => {
-                            // 解析失败，返回错误信息
-                            return Err(format!("无效的数字: {}", num_str));
+                            // 解析失败，记录错误信息（例如超出i64范围）
+                            errors.push(LexError::InvalidNumber { text: num_str, span: line_index.span(start, position) });
                         }
                     }
                 }
@@ -563,8 +928,8 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
             
             // 标识符或关键字匹配：遇到字母或下划线
             'a'..='z' | 'A'..='Z' | '_' => {
-                // 打印调试信息，确认开始匹配标识符或关键字
-                println!("DEBUG: 匹配标识符/关键字");
+                // 跟踪日志：确认开始匹配标识符或关键字
+                trace!(target: "lexer", "匹配标识符/关键字");
                 // 创建空字符串用于存储标识符/关键字文本
                 let mut ident = String::new();
                 
@@ -589,197 +954,424 @@ pub fn tokenize(input: &str) -> Result<Vec<Token>, String> {
                 let token = match ident.as_str() {
                     // 变量声明关键字（Nexa只支持var）
                     "var" => {
-                        // 打印调试信息，确认匹配到var关键字
-                        println!("DEBUG: 匹配关键字 'var'");
+                        // 跟踪日志：确认匹配到var关键字
+                        trace!(target: "lexer", "匹配关键字 'var'");
                         // 返回Var token
                         Token::Var
                     },
                     // 输出打印关键字
                     "print" => {
-                        // 打印调试信息，确认匹配到print关键字
-                        println!("DEBUG: 匹配关键字 'print'");
+                        // 跟踪日志：确认匹配到print关键字
+                        trace!(target: "lexer", "匹配关键字 'print'");
                         // 返回Print token
                         Token::Print
                     },
                     // 换行打印关键字
                     "println" => {
-                        // 打印调试信息，确认匹配到println关键字
-                        println!("DEBUG: 匹配关键字 'println'");
+                        // 跟踪日志：确认匹配到println关键字
+                        trace!(target: "lexer", "匹配关键字 'println'");
                         // 返回Println token
                         Token::Println
                     },
                     // 条件语句关键字
                     "if" => {
-                        // 打印调试信息，确认匹配到if关键字
-                        println!("DEBUG: 匹配关键字 'if'");
+                        // 跟踪日志：确认匹配到if关键字
+                        trace!(target: "lexer", "匹配关键字 'if'");
                         // 返回If token
                         Token::If
                     },
                     // then关键字
                     "then" => {
-                        // 打印调试信息，确认匹配到then关键字
-                        println!("DEBUG: 匹配关键字 'then'");
+                        // 跟踪日志：确认匹配到then关键字
+                        trace!(target: "lexer", "匹配关键字 'then'");
                         // 返回Then token
                         Token::Then
                     },
                     // else关键字
                     "else" => {
-                        // 打印调试信息，确认匹配到else关键字
-                        println!("DEBUG: 匹配关键字 'else'");
+                        // 跟踪日志：确认匹配到else关键字
+                        trace!(target: "lexer", "匹配关键字 'else'");
                         // 返回Else token
                         Token::Else
                     },
+                    // elif关键字
+                    "elif" => {
+                        // 跟踪日志：确认匹配到elif关键字
+                        trace!(target: "lexer", "匹配关键字 'elif'");
+                        // 返回Elif token
+                        Token::Elif
+                    },
                     // end关键字
                     "end" => {
-                        // 打印调试信息，确认匹配到end关键字
-                        println!("DEBUG: 匹配关键字 'end'");
+                        // 跟踪日志：确认匹配到end关键字
+                        trace!(target: "lexer", "匹配关键字 'end'");
                         // 返回End token
                         Token::End
                     },
                     // 加法运算关键字
                     "add" => {
-                        // 打印调试信息，确认匹配到add关键字
-                        println!("DEBUG: 匹配关键字 'add'");
+                        // 跟踪日志：确认匹配到add关键字
+                        trace!(target: "lexer", "匹配关键字 'add'");
                         // 返回Add token
                         Token::Add
                     },
                     // 减法运算关键字
                     "sub" => {
-                        // 打印调试信息，确认匹配到sub关键字
-                        println!("DEBUG: 匹配关键字 'sub'");
+                        // 跟踪日志：确认匹配到sub关键字
+                        trace!(target: "lexer", "匹配关键字 'sub'");
                         // 返回Sub token
                         Token::Sub
                     },
                     // 乘法运算关键字
                     "mul" => {
-                        // 打印调试信息，确认匹配到mul关键字
-                        println!("DEBUG: 匹配关键字 'mul'");
+                        // 跟踪日志：确认匹配到mul关键字
+                        trace!(target: "lexer", "匹配关键字 'mul'");
                         // 返回Mul token
                         Token::Mul
                     },
                     // 除法运算关键字
                     "div" => {
-                        // 打印调试信息，确认匹配到div关键字
-                        println!("DEBUG: 匹配关键字 'div'");
+                        // 跟踪日志：确认匹配到div关键字
+                        trace!(target: "lexer", "匹配关键字 'div'");
                         // 返回Div token
                         Token::Div
                     },
                     // 相等比较关键字
                     "eq" => {
-                        // 打印调试信息，确认匹配到eq关键字
-                        println!("DEBUG: 匹配关键字 'eq'");
+                        // 跟踪日志：确认匹配到eq关键字
+                        trace!(target: "lexer", "匹配关键字 'eq'");
                         // 返回Eq token
                         Token::Eq
                     },
                     // 小于比较关键字
                     "lt" => {
-                        // 打印调试信息，确认匹配到lt关键字
-                        println!("DEBUG: 匹配关键字 'lt'");
+                        // 跟踪日志：确认匹配到lt关键字
+                        trace!(target: "lexer", "匹配关键字 'lt'");
                         // 返回Lt token
                         Token::Lt
                     },
                     // 大于比较关键字
                     "gt" => {
-                        // 打印调试信息，确认匹配到gt关键字
-                        println!("DEBUG: 匹配关键字 'gt'");
+                        // 跟踪日志：确认匹配到gt关键字
+                        trace!(target: "lexer", "匹配关键字 'gt'");
                         // 返回Gt token
                         Token::Gt
                     },
+                    // 逻辑与关键字
+                    "and" => {
+                        // 跟踪日志：确认匹配到and关键字
+                        trace!(target: "lexer", "匹配关键字 'and'");
+                        // 返回And token
+                        Token::And
+                    },
+                    // 逻辑或关键字
+                    "or" => {
+                        // 跟踪日志：确认匹配到or关键字
+                        trace!(target: "lexer", "匹配关键字 'or'");
+                        // 返回Or token
+                        Token::Or
+                    },
+                    // 逻辑非关键字
+                    "not" => {
+                        // 跟踪日志：确认匹配到not关键字
+                        trace!(target: "lexer", "匹配关键字 'not'");
+                        // 返回Not token
+                        Token::Not
+                    },
                     // 整数类型关键字
                     "int" => {
-                        // 打印调试信息，确认匹配到int关键字
-                        println!("DEBUG: 匹配关键字 'int'");
+                        // 跟踪日志：确认匹配到int关键字
+                        trace!(target: "lexer", "匹配关键字 'int'");
                         // 返回Int token
                         Token::Int
                     },
                     // 浮点数类型关键字
                     "float" => {
-                        // 打印调试信息，确认匹配到float关键字
-                        println!("DEBUG: 匹配关键字 'float'");
+                        // 跟踪日志：确认匹配到float关键字
+                        trace!(target: "lexer", "匹配关键字 'float'");
                         // 返回Float token
                         Token::Float
                     },
                     // 布尔类型关键字
                     "bool" => {
-                        // 打印调试信息，确认匹配到bool关键字
-                        println!("DEBUG: 匹配关键字 'bool'");
+                        // 跟踪日志：确认匹配到bool关键字
+                        trace!(target: "lexer", "匹配关键字 'bool'");
                         // 返回Bool token
                         Token::Bool
                     },
                     // 布尔字面量 true
                     "true" => {
-                        // 打印调试信息，确认匹配到true字面量
-                        println!("DEBUG: 匹配布尔字面量 'true'");
+                        // 跟踪日志：确认匹配到true字面量
+                        trace!(target: "lexer", "匹配布尔字面量 'true'");
                         // 返回BoolLiteral token，值为true
                         Token::BoolLiteral(true)
                     },
                     // 布尔字面量 false
                     "false" => {
-                        // 打印调试信息，确认匹配到false字面量
-                        println!("DEBUG: 匹配布尔字面量 'false'");
+                        // 跟踪日志：确认匹配到false字面量
+                        trace!(target: "lexer", "匹配布尔字面量 'false'");
                         // 返回BoolLiteral token，值为false
                         Token::BoolLiteral(false)
                     },
                     // 字符串类型关键字
                     "string" => {
-                        // 打印调试信息，确认匹配到string关键字
-                        println!("DEBUG: 匹配关键字 'string'");
+                        // 跟踪日志：确认匹配到string关键字
+                        trace!(target: "lexer", "匹配关键字 'string'");
                         // 返回StringType token
                         Token::StringType
                     },
                     // while循环关键字
                     "while" => {
-                        // 打印调试信息，确认匹配到while关键字
-                        println!("DEBUG: 匹配关键字 'while'");
+                        // 跟踪日志：确认匹配到while关键字
+                        trace!(target: "lexer", "匹配关键字 'while'");
                         // 返回While token
                         Token::While
                     },
                     // for循环关键字
                     "for" => {
-                        // 打印调试信息，确认匹配到for关键字
-                        println!("DEBUG: 匹配关键字 'for'");
+                        // 跟踪日志：确认匹配到for关键字
+                        trace!(target: "lexer", "匹配关键字 'for'");
                         // 返回For token
                         Token::For
                     },
+                    // 函数定义关键字
+                    "fn" => {
+                        // 跟踪日志：确认匹配到fn关键字
+                        trace!(target: "lexer", "匹配关键字 'fn'");
+                        // 返回Fn token
+                        Token::Fn
+                    },
+                    // 函数返回关键字
+                    "return" => {
+                        // 跟踪日志：确认匹配到return关键字
+                        trace!(target: "lexer", "匹配关键字 'return'");
+                        // 返回Return token
+                        Token::Return
+                    },
+                    // break关键字
+                    "break" => {
+                        // 跟踪日志：确认匹配到break关键字
+                        trace!(target: "lexer", "匹配关键字 'break'");
+                        // 返回Break token
+                        Token::Break
+                    },
+                    // continue关键字
+                    "continue" => {
+                        // 跟踪日志：确认匹配到continue关键字
+                        trace!(target: "lexer", "匹配关键字 'continue'");
+                        // 返回Continue token
+                        Token::Continue
+                    },
                     // in关键字（用于for循环）
                     "in" => {
-                        // 打印调试信息，确认匹配到in关键字
-                        println!("DEBUG: 匹配关键字 'in'");
+                        // 跟踪日志：确认匹配到in关键字
+                        trace!(target: "lexer", "匹配关键字 'in'");
                         // 返回In token
                         Token::In
                     },
                     // 默认情况：不是关键字，作为标识符处理
                     _ => {
-                        // 打印调试信息，确认匹配到用户定义的标识符
-                        println!("DEBUG: 匹配标识符: {:?}", ident);
+                        // 跟踪日志：确认匹配到用户定义的标识符
+                        trace!(target: "lexer", "匹配标识符: {:?}", ident);
                         // 创建Identifier token，包含标识符名称
                         Token::Identifier(ident)
                     },
                 };
                 
                 // 将创建好的token添加到结果向量中
-                tokens.push(token);
+                tokens.push(SpannedToken::new(token, line_index.span(start, position)));
                 
-                // 标记不再在行首
-                _at_line_start = false;
             }
             
             // 无法识别的字符处理分支
             // 当遇到不属于Nexa语言字符集的字符时执行
             _ => {
-                // 打印调试信息，显示遇到的未知字符
-                println!("DEBUG: 无法识别的字符: {:?}", c);
-                // 返回错误信息，包含字符内容和位置信息
-                return Err(format!("[UPDATED LEXER v2] 无法识别的字符: '{}' 在位置 {}", c, position));
+                // 跟踪日志：显示遇到的未知字符
+                trace!(target: "lexer", "无法识别的字符: {:?}", c);
+                // 记录错误信息，包含字符内容和位置信息，跳过该字符继续分析
+                let c = *c;
+                chars.next();
+                position += c.len_utf8();
+                errors.push(LexError::UnexpectedChar { ch: c, span: line_index.span(start, position) });
             }
         }
     }
     
-    // 打印词法分析完成信息，显示生成的token数量
-    println!("DEBUG: 词法分析完成，共生成 {} 个token", tokens.len());
-    // 打印完整的token列表，用于调试分析
-    println!("DEBUG: Token列表: {:?}", tokens);
+    // 文件结束时关闭所有未结束的缩进代码块
+    if indent_stack.len() > 1 {
+        // 保证最后一条语句以换行结束
+        if !matches!(tokens.last(), Some(t) if t.token == Token::Newline) {
+            tokens.push(SpannedToken::new(Token::Newline, line_index.span(position, position)));
+        }
+        while indent_stack.len() > 1 {
+            indent_stack.pop();
+            tokens.push(SpannedToken::new(Token::Dedent, line_index.span(position, position)));
+        }
+    }
     
-    // 返回成功结果，包含所有解析到的token
-    Ok(tokens)
-}
\ No newline at end of file
+    // 调试日志：生成的token数量和错误数量
+    debug!(target: "lexer", "词法分析完成，共生成 {} 个token，{} 个错误", tokens.len(), errors.len());
+    // 跟踪日志：完整的token列表
+    trace!(target: "lexer", "Token列表: {:?}", tokens);
+    
+    // 返回所有解析到的token和诊断
+    (tokens, errors)
+}
+// 测试模块：包含单元测试函数
+// 仅在测试模式下编译（#[cfg(test)]）
+#[cfg(test)]
+mod tests {
+    // 导入父模块的所有内容
+    use super::*;
+    
+    // 测试完整的var语句词法分析
+    #[test]
+    fn test_tokenize_var() {
+        // 定义测试输入：变量声明语句
+        let input = "var x=10";
+        // 调用词法分析函数
+        let result = tokenize(input);
+        // 打印输入和结果，用于调试
+        println!("Input: {:?}, Result: {:?}", input, result);
+        // 断言结果必须是Ok（成功）
+        assert!(result.is_ok());
+    }
+    
+    // 测试简单词法分析功能
+    #[test]
+    fn test_tokenize_simple() {
+        // 测试单个关键字"var"
+        let result = tokenize("var");
+        println!("测试 'var': {:?}", result);
+        // 断言必须成功
+        assert!(result.is_ok());
+        
+        // 测试关键字+标识符"var x"
+        let result = tokenize("var x");
+        println!("测试 'var x': {:?}", result);
+        // 断言必须成功
+        assert!(result.is_ok());
+        
+        // 测试完整的变量声明语句"var x=10"
+        let result = tokenize("var x=10");
+        println!("测试 'var x=10': {:?}", result);
+        // 断言必须成功
+        assert!(result.is_ok());
+    }
+    
+    // 测试token的行列号和字节范围
+    #[test]
+    fn test_token_spans() {
+        let tokens = tokenize("var x = 10\nprint \"é\" + x").unwrap();
+        // 第一行的var关键字
+        assert_eq!(tokens[0].span, Span::new(1, 1, 0, 3));
+        // 第一行的数字10
+        assert_eq!(tokens[3].token, Token::Number(10));
+        assert_eq!(tokens[3].span, Span::new(1, 9, 8, 10));
+        // 第二行的字符串字面量（包含两个字节的非ASCII字符）
+        assert_eq!(tokens[6].token, Token::StringLiteral("é".to_string()));
+        assert_eq!(tokens[6].span, Span::new(2, 7, 17, 21));
+        // 字符串之后的标识符，列号按字符计算
+        assert_eq!(tokens[8].span, Span::new(2, 13, 24, 25));
+    }
+    
+    // 测试缩进代码块生成Indent/Dedent，空行不影响缩进
+    #[test]
+    fn test_indent_dedent() {
+        let tokens: Vec<Token> = tokenize("while x:\n    print x\n\n    x = 1\nprint x")
+            .unwrap()
+            .into_iter()
+            .map(|t| t.token)
+            .collect();
+        let indents = tokens.iter().filter(|t| **t == Token::Indent).count();
+        let dedents = tokens.iter().filter(|t| **t == Token::Dedent).count();
+        assert_eq!((indents, dedents), (1, 1));
+        // 缩进出现在冒号和换行之后
+        assert_eq!(&tokens[2..5], &[Token::Colon, Token::Newline, Token::Indent]);
+        
+        // 文件结束时自动关闭未结束的代码块
+        let tokens = tokenize("if x:\n    if y:\n        print y").unwrap();
+        let tail: Vec<Token> = tokens[tokens.len() - 3..].iter().map(|t| t.token.clone()).collect();
+        assert_eq!(tail, vec![Token::Newline, Token::Dedent, Token::Dedent]);
+        
+        // 回退到不存在的缩进级别是错误
+        assert!(tokenize("if x:\n        print x\n    print y").is_err());
+    }
+    
+    // 测试错误信息包含行列号
+    #[test]
+    fn test_error_position() {
+        let errors = tokenize("var x = 1\nvar y = @ + $").unwrap_err();
+        // 跳过无法识别的字符后继续分析，两个错误都被报告
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].to_string(), "第2行第9列: 错误[E0001]: 无法识别的字符: '@'");
+        assert_eq!(errors[1].span().column, 13);
+    }
+
+    // 测试错误种类和错误代码
+    #[test]
+    fn test_error_kinds() {
+        let errors = tokenize("var s = \"abc\nprint s").unwrap_err();
+        assert!(matches!(errors[0], LexError::UnterminatedString { span } if span.line == 1 && span.column == 9));
+        assert_eq!(errors[0].code(), "E0002");
+        let errors = tokenize("var n = 99999999999999999999").unwrap_err();
+        assert!(matches!(&errors[0], LexError::InvalidNumber { text, .. } if text == "99999999999999999999"));
+        let diagnostic = Diagnostic::from(errors[0].clone());
+        assert_eq!(diagnostic.code, Some("E0003"));
+        let errors = tokenize("print \"a\\qb\"").unwrap_err();
+        assert_eq!(errors[0].to_string(), "第1行第9列: 错误[E0005]: 无效的转义序列: '\\q'");
+    }
+
+    // 测试字符串中的转义序列在词法分析时处理
+    #[test]
+    fn test_string_escapes() {
+        let tokens = tokenize("\"x\\\\\" \"say \\\"hi\\\"\\n\\t\"").unwrap();
+        assert_eq!(tokens[0].token, Token::StringLiteral("x\\".to_string()));
+        assert_eq!(tokens[1].token, Token::StringLiteral("say \"hi\"\n\t".to_string()));
+        assert_eq!(tokens[1].span.end, 22);
+    }
+
+    // 测试浮点数字面量和科学计数法
+    #[test]
+    fn test_float_literals() {
+        let tokens: Vec<Token> = tokenize("2.75 1e-3 2.5E+2 7 1.").unwrap().into_iter().map(|t| t.token).collect();
+        assert_eq!(
+            tokens,
+            vec![
+                Token::FloatLiteral(2.75),
+                Token::FloatLiteral(0.001),
+                Token::FloatLiteral(250.0),
+                Token::Number(7),
+                Token::FloatLiteral(1.0),
+            ]
+        );
+        // 没有指数数字的e不属于数字，按标识符处理
+        let tokens = tokenize("2e").unwrap();
+        assert_eq!(tokens[0].token, Token::Number(2));
+        assert_eq!(tokens[1].token, Token::Identifier("e".to_string()));
+        assert_eq!(tokenize("1e999").unwrap_err()[0].message(), "无效的数字: 1e999");
+    }
+    
+    // 测试逻辑运算符和不等、相等比较
+    #[test]
+    fn test_logical_operators() {
+        let tokens: Vec<Token> = tokenize("a and b or not c && !d || e != f == g").unwrap().into_iter().map(|t| t.token).collect();
+        let operators: Vec<&Token> = tokens.iter().filter(|t| !matches!(t, Token::Identifier(_))).collect();
+        assert_eq!(operators, [&Token::And, &Token::Or, &Token::Not, &Token::AmpAmp, &Token::Bang, &Token::PipePipe, &Token::NotEqual, &Token::EqualEqual]);
+        // 单个&和|不是运算符
+        let errors = tokenize("a & b | c").unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert_eq!(errors[0].message(), "无法识别的字符: '&'");
+    }
+    
+    // 测试取余、整除和乘方运算符
+    #[test]
+    fn test_arithmetic_operators() {
+        let tokens = tokenize("a % b // c ** d * e / f").unwrap();
+        let operators: Vec<&Token> = tokens.iter().map(|t| &t.token).filter(|t| t.is_operator()).collect();
+        assert_eq!(operators, [&Token::Percent, &Token::SlashSlash, &Token::StarStar, &Token::Star, &Token::Slash]);
+        assert_eq!(tokens[3].span.start, 6);
+        assert_eq!(tokens[3].span.end, 8);
+    }
+}
+
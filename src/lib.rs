@@ -0,0 +1,37 @@
+// Nexa语言编译器库
+// 提供词法分析、语法分析、检查、代码生成和执行各阶段，其他Rust程序可以依赖nexa-lang嵌入编译器
+// 一般通过Session按阶段调用：tokenize → parse → check → generate → execute
+
+// 词法分析器模块
+pub mod lexer;
+// 诊断信息模块
+pub mod diagnostic;
+// 语法分析器模块
+pub mod parser;
+// 代码生成器模块
+pub mod codegen;
+// 树遍历解释器模块
+pub mod interp;
+// 名称解析模块
+pub mod resolve;
+// 类型检查器模块
+pub mod typeck;
+// 字节码编译器模块
+pub mod bytecode;
+// 字节码虚拟机模块
+pub mod vm;
+// 用rustc编译运行生成的Rust代码
+pub mod exec;
+// 编译缓存模块
+pub mod cache;
+// 编译会话模块
+pub mod session;
+// 日志配置模块
+pub mod logging;
+// 交互式环境的求值引擎
+pub mod repl;
+// 语言服务器模块
+pub mod lsp;
+
+pub use diagnostic::Diagnostic;
+pub use session::{Backend, CompileError, CompileOptions, Session, Stage};
@@ -0,0 +1,55 @@
+// 日志：编译各阶段的调试信息通过tracing输出到标准错误，不会混入程序输出
+// 每个阶段使用自己的目标（lexer、parser、codegen、exec），可以单独打开，例如：
+//   NEXA_LOG=lexer=trace,codegen=debug nexa run main.nexa
+//   nexa --log parser=debug run main.nexa
+// 默认只显示警告和错误
+use std::io::IsTerminal;
+use tracing_subscriber::EnvFilter;
+
+// 控制日志过滤规则的环境变量
+pub const LOG_ENV: &str = "NEXA_LOG";
+
+// 没有指定过滤规则时使用的默认规则
+pub const DEFAULT_FILTER: &str = "warn";
+
+// 确定过滤规则：命令行参数优先，其次是环境变量，最后是默认规则
+pub fn filter_directives(cli: Option<&str>) -> String {
+    match cli {
+        Some(filter) => filter.to_string(),
+        None => std::env::var(LOG_ENV).unwrap_or_else(|_| DEFAULT_FILTER.to_string()),
+    }
+}
+
+// 解析过滤规则，规则无效时返回错误信息
+fn parse_filter(directives: &str) -> Result<EnvFilter, String> {
+    EnvFilter::try_new(directives).map_err(|e| format!("无效的日志过滤规则 '{}': {}", directives, e))
+}
+
+// 初始化全局日志输出，过滤规则无效时返回错误信息
+// 重复初始化（例如嵌入到已经设置了日志的程序中）时保留已有的设置
+pub fn init(cli: Option<&str>) -> Result<(), String> {
+    let filter = parse_filter(&filter_directives(cli))?;
+    let _ = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .without_time()
+        .try_init();
+    Ok(())
+}
+
+// 测试模块：日志配置单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 测试命令行参数优先于环境变量，以及过滤规则的解析
+    // 不调用init，避免在测试进程中安装全局日志输出
+    #[test]
+    fn test_filter_directives() {
+        assert_eq!(filter_directives(Some("lexer=trace")), "lexer=trace");
+        assert!(parse_filter("parser=debug,codegen=info").is_ok());
+        let err = parse_filter("lexer=nonsense").unwrap_err();
+        assert!(err.contains("lexer=nonsense"), "{}", err);
+    }
+}
@@ -0,0 +1,848 @@
+// 语言服务器（LSP）：通过标准输入输出和编辑器（VS Code、Neovim等）通信
+// 提供诊断、语义高亮、悬停显示类型、跳转到定义、文档符号和补全
+// 消息是带Content-Length头的JSON-RPC，文档内容每次修改都整体同步
+use crate::diagnostic::{Diagnostic, Severity};
+use crate::lexer::{self, Span, SpannedToken, Token};
+use crate::parser::{self, Expr, ExprKind, Statement, StatementKind};
+use crate::session::Session;
+use crate::typeck::{self, Type};
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::io::{self, BufRead, Write};
+use tracing::debug;
+
+// JSON-RPC错误代码
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+
+// 单条消息内容的最大长度，防止按不可信的Content-Length分配过大的内存
+const MAX_CONTENT_LENGTH: usize = 16 * 1024 * 1024;
+
+// 语义高亮的词法单元类型，下标就是返回给编辑器的类型编号
+const TOKEN_TYPES: &[&str] = &["keyword", "type", "operator", "variable", "function", "parameter", "number", "string"];
+
+// 补全时提供的关键字
+const KEYWORDS: &[&str] = &[
+    "var", "print", "println", "if", "else", "elif", "while", "for", "in", "fn", "return", "break", "continue", "int", "float", "bool", "string",
+    "true", "false", "and", "or", "not",
+];
+
+// ===== 消息读写 =====
+
+// 读取一条消息的内容，输入结束时返回None
+pub fn read_frame<R: BufRead>(input: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse::<usize>().ok();
+            }
+        }
+    }
+    let length = length.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "缺少Content-Length头"))?;
+    if length > MAX_CONTENT_LENGTH {
+        let message = format!("Content-Length {} 超过上限 {}", length, MAX_CONTENT_LENGTH);
+        return Err(io::Error::new(io::ErrorKind::InvalidData, message));
+    }
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    Ok(Some(body))
+}
+
+// 写入一条消息
+pub fn write_message<W: Write>(output: &mut W, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+// 成功响应
+fn response(id: &Value, result: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "result": result })
+}
+
+// 错误响应
+fn error_response(id: &Value, code: i64, message: &str) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+// 通知
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+// ===== 位置换算 =====
+
+// 字节偏移和LSP位置（行号从0开始，列号按UTF-16编码单元计数）之间的换算
+struct LineIndex<'a> {
+    text: &'a str,
+    // 每行开头的字节偏移
+    line_starts: Vec<usize>,
+}
+
+impl<'a> LineIndex<'a> {
+    fn new(text: &'a str) -> Self {
+        let line_starts = std::iter::once(0).chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        LineIndex { text, line_starts }
+    }
+
+    // 字节偏移对应的(行, 列)
+    fn line_column(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let line = self.line_starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.line_starts[line];
+        let column = self.text.get(start..offset).map_or(0, |s| s.encode_utf16().count());
+        (line, column)
+    }
+
+    // 字节偏移对应的LSP位置
+    fn position(&self, offset: usize) -> Value {
+        let (line, character) = self.line_column(offset);
+        json!({ "line": line, "character": character })
+    }
+
+    // Span对应的LSP范围
+    fn range(&self, span: Span) -> Value {
+        json!({ "start": self.position(span.start), "end": self.position(span.end.max(span.start)) })
+    }
+
+    // LSP位置对应的字节偏移，超出范围时取行尾或文件末尾
+    fn offset(&self, position: &Value) -> usize {
+        let line = position["line"].as_u64().unwrap_or(0) as usize;
+        let character = position["character"].as_u64().unwrap_or(0) as usize;
+        let Some(&start) = self.line_starts.get(line) else {
+            return self.text.len();
+        };
+        let end = self.line_starts.get(line + 1).map_or(self.text.len(), |&next| next - 1);
+        let mut units = 0;
+        for (i, c) in self.text[start..end].char_indices() {
+            if units >= character {
+                return start + i;
+            }
+            units += c.len_utf16();
+        }
+        end
+    }
+}
+
+// ===== 语义分析 =====
+
+// 定义的种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum DefinitionKind {
+    Variable,
+    Parameter,
+    Function,
+}
+
+// 一个变量、参数或函数的定义
+#[derive(Debug, Clone)]
+struct Definition {
+    name: String,
+    kind: DefinitionKind,
+    // 名字的位置
+    span: Span,
+    // 整个定义（语句或参数）的位置
+    full: Span,
+    // 悬停时显示的声明，如 var x: int、fn f(n: int) -> int
+    detail: String,
+    // 可见范围：从visible_from到scope_end（字节偏移）
+    visible_from: usize,
+    scope_end: usize,
+    // 所在函数的定义下标，顶层定义为None
+    parent: Option<usize>,
+}
+
+// 一份文档的分析结果：所有定义和每个名字的使用位置对应的定义
+#[derive(Debug, Default)]
+struct Analysis {
+    definitions: Vec<Definition>,
+    references: Vec<(Span, usize)>,
+}
+
+impl Analysis {
+    // 偏移处的名字（使用或定义）对应的定义下标
+    fn definition_at(&self, offset: usize) -> Option<usize> {
+        let contains = |span: &Span| span.start <= offset && offset <= span.end;
+        self.references
+            .iter()
+            .find(|(span, _)| contains(span))
+            .map(|(_, id)| *id)
+            .or_else(|| self.definitions.iter().position(|def| contains(&def.span)))
+    }
+
+    // 偏移处可见的定义，内层的同名定义优先
+    fn visible_at(&self, offset: usize) -> Vec<&Definition> {
+        let mut seen = HashSet::new();
+        let mut visible = Vec::new();
+        for def in self.definitions.iter().rev() {
+            if def.visible_from <= offset && offset < def.scope_end && seen.insert(def.name.as_str()) {
+                visible.push(def);
+            }
+        }
+        visible
+    }
+}
+
+// 变量声明的显示形式，不知道类型时只显示变量名
+fn variable_detail(name: &str, data_type: Option<Type>) -> String {
+    match data_type {
+        Some(t) => format!("var {}: {}", name, t),
+        None => format!("var {}", name),
+    }
+}
+
+// 遍历抽象语法树，按作用域记录定义和使用
+struct Analyzer<'a> {
+    tokens: &'a [SpannedToken],
+    // 作用域栈，每层是(名字, 定义下标)
+    scopes: Vec<Vec<(String, usize)>>,
+    // 各层作用域结束的字节偏移
+    scope_ends: Vec<usize>,
+    // 当前所在函数的定义下标
+    function: Option<usize>,
+    // 函数语句的起始偏移到函数定义下标，函数在代码块开始时提前定义
+    functions: HashMap<usize, usize>,
+    analysis: Analysis,
+}
+
+impl<'a> Analyzer<'a> {
+    fn new(tokens: &'a [SpannedToken]) -> Self {
+        Analyzer {
+            tokens,
+            scopes: Vec::new(),
+            scope_ends: Vec::new(),
+            function: None,
+            functions: HashMap::new(),
+            analysis: Analysis::default(),
+        }
+    }
+
+    // 从after开始的第一个名为name的标识符的位置，找不到时返回fallback
+    fn name_span(&self, after: usize, name: &str, fallback: Span) -> Span {
+        self.tokens
+            .iter()
+            .find(|t| t.span.start >= after && matches!(&t.token, Token::Identifier(n) if n == name))
+            .map_or(fallback, |t| t.span)
+    }
+
+    // 在当前作用域中添加定义
+    fn define(&mut self, name: &str, kind: DefinitionKind, span: Span, full: Span, detail: String, visible_from: usize) -> usize {
+        let id = self.analysis.definitions.len();
+        self.analysis.definitions.push(Definition {
+            name: name.to_string(),
+            kind,
+            span,
+            full,
+            detail,
+            visible_from,
+            scope_end: *self.scope_ends.last().unwrap_or(&usize::MAX),
+            parent: self.function,
+        });
+        if let Some(scope) = self.scopes.last_mut() {
+            scope.push((name.to_string(), id));
+        }
+        id
+    }
+
+    // 记录名字的使用
+    fn reference(&mut self, span: Span, name: &str) {
+        let found = self.scopes.iter().rev().find_map(|scope| scope.iter().rev().find(|(n, _)| n == name));
+        if let Some(&(_, id)) = found {
+            self.analysis.references.push((span, id));
+        }
+    }
+
+    // 分析代码块：块中的函数先定义，整个块内可见
+    // context是顶层代码块的语句，用于推断顶层变量的类型
+    fn block(&mut self, statements: &[Statement], start: usize, end: usize, context: Option<&[Statement]>) {
+        self.scopes.push(Vec::new());
+        self.scope_ends.push(end);
+        for stmt in statements {
+            if let StatementKind::Function(name, params, return_type, _) = &stmt.kind {
+                let params: Vec<String> =
+                    params.iter().map(|p| format!("{}: {}", p.name, Type::from(&p.data_type))).collect();
+                let mut detail = format!("fn {}({})", name, params.join(", "));
+                if let Some(t) = return_type {
+                    detail += &format!(" -> {}", Type::from(t));
+                }
+                let span = self.name_span(stmt.span.start, name, stmt.span);
+                let id = self.define(name, DefinitionKind::Function, span, stmt.span, detail, start);
+                self.functions.insert(stmt.span.start, id);
+            }
+        }
+        for (i, stmt) in statements.iter().enumerate() {
+            self.statement(stmt, context.map(|_| &statements[..i]));
+        }
+        self.scopes.pop();
+        self.scope_ends.pop();
+    }
+
+    fn statement(&mut self, stmt: &Statement, preceding: Option<&[Statement]>) {
+        let end = stmt.span.end;
+        match &stmt.kind {
+            StatementKind::Var(name, data_type, init) => {
+                self.expression(init);
+                // 没有类型注解时，在前面的顶层语句中推断初始值的类型
+                let data_type = match data_type {
+                    Some(t) => Some(Type::from(t)),
+                    None => typeck::infer_type(preceding.unwrap_or(&[]), init).ok(),
+                };
+                let span = self.name_span(stmt.span.start, name, stmt.span);
+                self.define(name, DefinitionKind::Variable, span, stmt.span, variable_detail(name, data_type), end);
+            },
+            StatementKind::Assign(name, value) => {
+                let span = self.name_span(stmt.span.start, name, stmt.span);
+                self.reference(span, name);
+                self.expression(value);
+            },
+            StatementKind::Print(exprs) | StatementKind::Println(exprs) => {
+                exprs.iter().for_each(|e| self.expression(e));
+            },
+            StatementKind::If(condition, then_branch, else_branch) => {
+                self.expression(condition);
+                self.block(then_branch, condition.span.end, end, None);
+                if let Some(else_branch) = else_branch {
+                    self.block(else_branch, condition.span.end, end, None);
+                }
+            },
+            StatementKind::For(name, iterable, body) => {
+                self.expression(iterable);
+                // 循环变量单独一层作用域，循环体中可以重新声明同名变量
+                self.scopes.push(Vec::new());
+                self.scope_ends.push(end);
+                let span = self.name_span(stmt.span.start, name, stmt.span);
+                self.define(name, DefinitionKind::Variable, span, span, variable_detail(name, Some(Type::Int)), span.end);
+                self.block(body, iterable.span.end, end, None);
+                self.scopes.pop();
+                self.scope_ends.pop();
+            },
+            StatementKind::While(condition, body) => {
+                self.expression(condition);
+                self.block(body, condition.span.end, end, None);
+            },
+            StatementKind::Expression(expr) => self.expression(expr),
+            StatementKind::Function(_, params, _, body) => {
+                let outer = self.function;
+                self.function = self.functions.get(&stmt.span.start).copied();
+                self.scopes.push(Vec::new());
+                self.scope_ends.push(end);
+                for param in params {
+                    let span = self.name_span(param.span.start, &param.name, param.span);
+                    let detail = format!("{}: {}", param.name, Type::from(&param.data_type));
+                    self.define(&param.name, DefinitionKind::Parameter, span, param.span, detail, param.span.end);
+                }
+                let body_start = params.last().map_or(stmt.span.start, |p| p.span.end);
+                self.block(body, body_start, end, None);
+                self.scopes.pop();
+                self.scope_ends.pop();
+                self.function = outer;
+            },
+            StatementKind::Return(value) => {
+                if let Some(value) = value {
+                    self.expression(value);
+                }
+            },
+            StatementKind::Break | StatementKind::Continue => {},
+        }
+    }
+
+    fn expression(&mut self, expr: &Expr) {
+        match &expr.kind {
+            ExprKind::Identifier(name) => self.reference(expr.span, name),
+            ExprKind::Call(name, arguments) => {
+                let span = Span { end: expr.span.start + name.len(), ..expr.span };
+                self.reference(span, name);
+                arguments.iter().for_each(|a| self.expression(a));
+            },
+            ExprKind::BinaryOp(left, _, right) | ExprKind::Range(left, right) => {
+                self.expression(left);
+                self.expression(right);
+            },
+            ExprKind::UnaryOp(_, operand) => self.expression(operand),
+            ExprKind::Number(_) | ExprKind::Float(_) | ExprKind::String(_) | ExprKind::BoolLiteral(_) => {},
+        }
+    }
+}
+
+// 分析整个程序
+fn analyze(tokens: &[SpannedToken], statements: &[Statement]) -> Analysis {
+    let mut analyzer = Analyzer::new(tokens);
+    analyzer.block(statements, 0, usize::MAX, Some(statements));
+    analyzer.analysis
+}
+
+// ===== 文档和请求处理 =====
+
+// 打开的文档：源代码、词法单元和语法分析成功时的分析结果
+struct Document {
+    text: String,
+    tokens: Vec<SpannedToken>,
+    analysis: Option<Analysis>,
+}
+
+impl Document {
+    fn new(text: String) -> Self {
+        let (tokens, _) = lexer::tokenize_with_recovery(&text);
+        let analysis = parser::parse_source(&text).ok().map(|statements| analyze(&tokens, &statements));
+        Document { text, tokens, analysis }
+    }
+}
+
+// 把诊断转换为LSP诊断
+fn lsp_diagnostic(index: &LineIndex, diagnostic: &Diagnostic) -> Value {
+    let severity = match diagnostic.severity {
+        Severity::Error => 1,
+        Severity::Warning => 2,
+        Severity::Note => 3,
+    };
+    let mut message = diagnostic.message.clone();
+    if let Some(help) = &diagnostic.details.help {
+        message += &format!("\n帮助: {}", help);
+    }
+    // 零宽度的位置（如文件末尾）至少标记一个字符
+    let mut span = diagnostic.span;
+    if span.end <= span.start {
+        span.end = span.start + 1;
+    }
+    json!({
+        "range": index.range(span),
+        "severity": severity,
+        "code": diagnostic.code,
+        "source": "nexa",
+        "message": message,
+    })
+}
+
+// 语义高亮的类型编号，不需要高亮的词法单元返回None
+fn token_type(tokens: &[SpannedToken], i: usize, analysis: Option<&Analysis>) -> Option<usize> {
+    let type_index = |name| TOKEN_TYPES.iter().position(|t| *t == name);
+    let token = &tokens[i];
+    match &token.token {
+        Token::Int | Token::Float | Token::Bool | Token::StringType => type_index("type"),
+        t if t.is_keyword() || t.is_keyword_operator() => type_index("keyword"),
+        Token::Then | Token::End | Token::BoolLiteral(_) => type_index("keyword"),
+        t if t.is_operator() => type_index("operator"),
+        Token::Arrow => type_index("operator"),
+        Token::Number(_) | Token::FloatLiteral(_) => type_index("number"),
+        Token::StringLiteral(_) => type_index("string"),
+        Token::Identifier(_) => {
+            let kind = analysis
+                .and_then(|a| a.definition_at(token.span.start))
+                .map(|id| analysis.unwrap().definitions[id].kind);
+            match kind {
+                Some(DefinitionKind::Function) => type_index("function"),
+                Some(DefinitionKind::Parameter) => type_index("parameter"),
+                Some(DefinitionKind::Variable) => type_index("variable"),
+                // 没有分析结果时，后面紧跟括号的是函数
+                None if matches!(tokens.get(i + 1), Some(t) if t.token == Token::LParen) => type_index("function"),
+                None => type_index("variable"),
+            }
+        },
+        _ => None,
+    }
+}
+
+// 语言服务器的状态
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, Document>,
+    shut_down: bool,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 处理一条消息，返回要发送给编辑器的响应和通知
+    pub fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let id = message.get("id");
+        debug!(target: "lsp", "收到 {}", method);
+
+        // 没有id的是通知，不需要响应
+        let Some(id) = id else {
+            return self.handle_notification(method, params);
+        };
+        if self.shut_down {
+            return vec![error_response(id, INVALID_REQUEST, "服务器已关闭")];
+        }
+        let result = match method {
+            "initialize" => Some(Self::capabilities()),
+            "shutdown" => {
+                self.shut_down = true;
+                Some(Value::Null)
+            },
+            "textDocument/hover" => Some(self.hover(params)),
+            "textDocument/definition" => Some(self.definition(params)),
+            "textDocument/documentSymbol" => Some(self.document_symbols(params)),
+            "textDocument/completion" => Some(self.completion(params)),
+            "textDocument/semanticTokens/full" => Some(self.semantic_tokens(params)),
+            _ => None,
+        };
+        match result {
+            Some(result) => vec![response(id, result)],
+            None => vec![error_response(id, METHOD_NOT_FOUND, &format!("不支持的方法: {}", method))],
+        }
+    }
+
+    // 服务器支持的功能
+    fn capabilities() -> Value {
+        json!({
+            "capabilities": {
+                "textDocumentSync": 1,
+                "hoverProvider": true,
+                "definitionProvider": true,
+                "documentSymbolProvider": true,
+                "completionProvider": {},
+                "semanticTokensProvider": {
+                    "legend": { "tokenTypes": TOKEN_TYPES, "tokenModifiers": [] },
+                    "full": true,
+                },
+            },
+            "serverInfo": { "name": "nexa-lsp", "version": env!("CARGO_PKG_VERSION") },
+        })
+    }
+
+    // 处理通知：打开、修改文档后重新发布诊断，关闭文档时清空诊断
+    fn handle_notification(&mut self, method: &str, params: &Value) -> Vec<Value> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default().to_string();
+        match method {
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                self.documents.insert(uri.clone(), Document::new(text.to_string()));
+                vec![self.publish_diagnostics(&uri)]
+            },
+            "textDocument/didChange" => {
+                // 整体同步：最后一次修改就是完整的新内容
+                let Some(text) = params["contentChanges"].as_array().and_then(|c| c.last()).and_then(|c| c["text"].as_str())
+                else {
+                    return Vec::new();
+                };
+                self.documents.insert(uri.clone(), Document::new(text.to_string()));
+                vec![self.publish_diagnostics(&uri)]
+            },
+            "textDocument/didClose" => {
+                self.documents.remove(&uri);
+                vec![notification("textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": [] }))]
+            },
+            _ => Vec::new(),
+        }
+    }
+
+    // 检查文档并发布诊断
+    fn publish_diagnostics(&self, uri: &str) -> Value {
+        let text = self.documents.get(uri).map_or("", |d| d.text.as_str());
+        let index = LineIndex::new(text);
+        let diagnostics: Vec<Value> = match Session::new(text).check() {
+            Ok(_) => Vec::new(),
+            Err(e) => e.diagnostics().iter().map(|d| lsp_diagnostic(&index, d)).collect(),
+        };
+        notification("textDocument/publishDiagnostics", json!({ "uri": uri, "diagnostics": diagnostics }))
+    }
+
+    // 请求中的文档和光标位置的字节偏移
+    fn document_at<'a>(&'a self, params: &'a Value) -> Option<(&'a str, &'a Document, usize)> {
+        let uri = params["textDocument"]["uri"].as_str()?;
+        let document = self.documents.get(uri)?;
+        let offset = LineIndex::new(&document.text).offset(&params["position"]);
+        Some((uri, document, offset))
+    }
+
+    // 悬停：显示变量、参数或函数的声明
+    fn hover(&self, params: &Value) -> Value {
+        let Some((_, document, offset)) = self.document_at(params) else {
+            return Value::Null;
+        };
+        let Some(analysis) = &document.analysis else {
+            return Value::Null;
+        };
+        let Some(id) = analysis.definition_at(offset) else {
+            return Value::Null;
+        };
+        let definition = &analysis.definitions[id];
+        json!({ "contents": { "kind": "markdown", "value": format!("```nexa\n{}\n```", definition.detail) } })
+    }
+
+    // 跳转到定义
+    fn definition(&self, params: &Value) -> Value {
+        let Some((uri, document, offset)) = self.document_at(params) else {
+            return Value::Null;
+        };
+        let Some(analysis) = &document.analysis else {
+            return Value::Null;
+        };
+        match analysis.definition_at(offset) {
+            Some(id) => {
+                let index = LineIndex::new(&document.text);
+                json!({ "uri": uri, "range": index.range(analysis.definitions[id].span) })
+            },
+            None => Value::Null,
+        }
+    }
+
+    // 文档符号：顶层的变量和函数，函数下面是它的参数和局部变量
+    fn document_symbols(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let Some(document) = self.documents.get(uri) else {
+            return Value::Null;
+        };
+        let Some(analysis) = &document.analysis else {
+            return json!([]);
+        };
+        let index = LineIndex::new(&document.text);
+        let symbol = |def: &Definition, children: Vec<Value>| {
+            // 函数为12，变量为13
+            let kind = if def.kind == DefinitionKind::Function { 12 } else { 13 };
+            json!({
+                "name": def.name,
+                "detail": def.detail,
+                "kind": kind,
+                "range": index.range(def.full.to(def.span)),
+                "selectionRange": index.range(def.span),
+                "children": children,
+            })
+        };
+        let symbols: Vec<Value> = analysis
+            .definitions
+            .iter()
+            .enumerate()
+            .filter(|(_, def)| def.parent.is_none())
+            .map(|(id, def)| {
+                let children = analysis
+                    .definitions
+                    .iter()
+                    .filter(|child| child.parent == Some(id))
+                    .map(|child| symbol(child, Vec::new()))
+                    .collect();
+                symbol(def, children)
+            })
+            .collect();
+        json!(symbols)
+    }
+
+    // 补全：关键字和光标处可见的变量、参数和函数
+    // 文档有语法错误时，用文档中出现过的所有标识符代替
+    fn completion(&self, params: &Value) -> Value {
+        let Some((_, document, offset)) = self.document_at(params) else {
+            return Value::Null;
+        };
+        // 关键字为14，函数为3，变量为6
+        let mut items: Vec<Value> = KEYWORDS.iter().map(|k| json!({ "label": k, "kind": 14 })).collect();
+        match &document.analysis {
+            Some(analysis) => {
+                for def in analysis.visible_at(offset) {
+                    let kind = if def.kind == DefinitionKind::Function { 3 } else { 6 };
+                    items.push(json!({ "label": def.name, "kind": kind, "detail": def.detail }));
+                }
+            },
+            None => {
+                let mut seen = HashSet::new();
+                for token in &document.tokens {
+                    if let Token::Identifier(name) = &token.token {
+                        if seen.insert(name.as_str()) {
+                            items.push(json!({ "label": name, "kind": 6 }));
+                        }
+                    }
+                }
+            },
+        }
+        json!(items)
+    }
+
+    // 语义高亮：每个词法单元编码为(行差, 列差, 长度, 类型, 修饰)五个数
+    fn semantic_tokens(&self, params: &Value) -> Value {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let Some(document) = self.documents.get(uri) else {
+            return Value::Null;
+        };
+        let index = LineIndex::new(&document.text);
+        let mut data = Vec::new();
+        let (mut previous_line, mut previous_column) = (0, 0);
+        for (i, token) in document.tokens.iter().enumerate() {
+            let Some(token_type) = token_type(&document.tokens, i, document.analysis.as_ref()) else {
+                continue;
+            };
+            let (line, column) = index.line_column(token.span.start);
+            let length = document.text.get(token.span.start..token.span.end).map_or(0, |s| s.encode_utf16().count());
+            let delta_column = if line == previous_line { column - previous_column } else { column };
+            data.extend([line - previous_line, delta_column, length, token_type, 0]);
+            (previous_line, previous_column) = (line, column);
+        }
+        json!({ "data": data })
+    }
+}
+
+// 运行语言服务器：从input读取消息，把响应写入output，收到exit通知或输入结束时返回
+pub fn run<R: BufRead, W: Write>(mut input: R, mut output: W) -> io::Result<()> {
+    let mut server = Server::new();
+    while let Some(body) = read_frame(&mut input)? {
+        let message: Value = match serde_json::from_slice(&body) {
+            Ok(message) => message,
+            Err(e) => {
+                write_message(&mut output, &error_response(&Value::Null, PARSE_ERROR, &e.to_string()))?;
+                continue;
+            },
+        };
+        if message["method"] == "exit" {
+            break;
+        }
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+    }
+    Ok(())
+}
+
+// 测试模块：语言服务器单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    const URI: &str = "file:///test.nexa";
+
+    // 辅助函数：模拟编辑器依次发送消息，返回服务器发送的所有消息
+    fn exchange(messages: &[Value]) -> Vec<Value> {
+        let mut input = Vec::new();
+        for message in messages {
+            write_message(&mut input, message).unwrap();
+        }
+        let mut output = Vec::new();
+        run(Cursor::new(input), &mut output).unwrap();
+        let mut output = Cursor::new(output);
+        let mut replies = Vec::new();
+        while let Some(body) = read_frame(&mut output).unwrap() {
+            replies.push(serde_json::from_slice(&body).unwrap());
+        }
+        replies
+    }
+
+    // 辅助函数：构造请求
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    // 辅助函数：打开文档的通知
+    fn open(text: &str) -> Value {
+        notification("textDocument/didOpen", json!({ "textDocument": { "uri": URI, "languageId": "nexa", "version": 1, "text": text } }))
+    }
+
+    // 辅助函数：光标位置相关的请求
+    fn at(id: u64, method: &str, line: u64, character: u64) -> Value {
+        request(id, method, json!({ "textDocument": { "uri": URI }, "position": { "line": line, "character": character } }))
+    }
+
+    // 辅助函数：取出某个请求的结果
+    fn result(replies: &[Value], id: u64) -> &Value {
+        &replies.iter().find(|r| r["id"] == id).unwrap()["result"]
+    }
+
+    // 测试初始化、修改时的诊断、关闭和未知方法
+    #[test]
+    fn test_lifecycle_and_diagnostics() {
+        let change = notification(
+            "textDocument/didChange",
+            json!({ "textDocument": { "uri": URI, "version": 2 }, "contentChanges": [{ "text": "var a = 1\nprintln(a)\n" }] }),
+        );
+        let replies = exchange(&[
+            request(1, "initialize", json!({ "capabilities": {} })),
+            notification("initialized", json!({})),
+            open("var a = 1\nprintln(b)\n"),
+            change,
+            request(2, "textDocument/unknown", json!({})),
+            request(3, "shutdown", Value::Null),
+            notification("exit", Value::Null),
+            request(4, "shutdown", Value::Null),
+        ]);
+        let capabilities = &result(&replies, 1)["capabilities"];
+        assert_eq!(capabilities["semanticTokensProvider"]["legend"]["tokenTypes"][0], "keyword");
+        assert_eq!(replies[1]["method"], "textDocument/publishDiagnostics");
+        let diagnostics = replies[1]["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0]["range"], json!({ "start": { "line": 1, "character": 8 }, "end": { "line": 1, "character": 9 } }));
+        assert_eq!(diagnostics[0]["severity"], 1);
+        assert_eq!(replies[2]["params"]["diagnostics"], json!([]));
+        assert_eq!(replies[3]["error"]["code"], METHOD_NOT_FOUND);
+        assert_eq!(result(&replies, 3), &Value::Null);
+        // exit之后的消息不再处理
+        assert_eq!(replies.len(), 5);
+    }
+
+    // 测试悬停、跳转到定义、文档符号和补全
+    #[test]
+    fn test_navigation_and_completion() {
+        let source = "var count: float = 1\nvar name = \"nexa\"\nfn twice(n: int) -> int:\n    var doubled = n * 2\n    return doubled\nprintln(count, twice(2))\n";
+        let replies = exchange(&[
+            open(source),
+            at(1, "textDocument/hover", 5, 9),
+            at(2, "textDocument/hover", 1, 5),
+            at(3, "textDocument/hover", 5, 17),
+            at(4, "textDocument/definition", 4, 12),
+            request(5, "textDocument/documentSymbol", json!({ "textDocument": { "uri": URI } })),
+            at(6, "textDocument/completion", 4, 4),
+            at(7, "textDocument/completion", 5, 0),
+        ]);
+        assert_eq!(result(&replies, 1)["contents"]["value"], "```nexa\nvar count: float\n```");
+        assert_eq!(result(&replies, 2)["contents"]["value"], "```nexa\nvar name: string\n```");
+        assert_eq!(result(&replies, 3)["contents"]["value"], "```nexa\nfn twice(n: int) -> int\n```");
+        let definition = result(&replies, 4);
+        assert_eq!(definition["uri"], URI);
+        assert_eq!(definition["range"]["start"], json!({ "line": 3, "character": 8 }));
+
+        let symbols = result(&replies, 5).as_array().unwrap();
+        let names: Vec<&str> = symbols.iter().map(|s| s["name"].as_str().unwrap()).collect();
+        assert_eq!(names, ["twice", "count", "name"]);
+        let children: Vec<&str> = symbols[0]["children"].as_array().unwrap().iter().map(|s| s["name"].as_str().unwrap()).collect();
+        assert_eq!(children, ["n", "doubled"]);
+
+        let labels = |id| -> Vec<String> {
+            result(&replies, id).as_array().unwrap().iter().map(|i| i["label"].as_str().unwrap().to_string()).collect()
+        };
+        let inside = labels(6);
+        assert!(inside.contains(&"while".to_string()));
+        assert!(["doubled", "n", "twice", "count", "name"].iter().all(|n| inside.contains(&n.to_string())));
+        let outside = labels(7);
+        assert!(outside.contains(&"twice".to_string()) && !outside.contains(&"doubled".to_string()));
+    }
+
+    // 测试语义高亮的编码
+    #[test]
+    fn test_semantic_tokens() {
+        let replies = exchange(&[
+            open("fn f(x: int):\n    print x\n"),
+            request(1, "textDocument/semanticTokens/full", json!({ "textDocument": { "uri": URI } })),
+        ]);
+        let data: Vec<u64> = result(&replies, 1)["data"].as_array().unwrap().iter().map(|v| v.as_u64().unwrap()).collect();
+        let index = |name| TOKEN_TYPES.iter().position(|t| *t == name).unwrap() as u64;
+        #[rustfmt::skip]
+        let expected = [
+            0, 0, 2, index("keyword"), 0,   // fn
+            0, 3, 1, index("function"), 0,  // f
+            0, 2, 1, index("parameter"), 0, // x
+            0, 3, 3, index("type"), 0,      // int
+            1, 4, 5, index("keyword"), 0,   // print
+            0, 6, 1, index("parameter"), 0, // x
+        ];
+        assert_eq!(data, expected);
+    }
+
+    // 测试过大的Content-Length在分配内存之前被拒绝
+    #[test]
+    fn test_oversized_frame() {
+        let mut input = Cursor::new(format!("Content-Length: {}\r\n\r\n{{}}", usize::MAX));
+        let err = read_frame(&mut input).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+}
@@ -1,22 +1,23 @@
 // 导入colored库用于终端彩色输出
 use colored::*;
 // 导入标准库中的IO模块，用于读写操作
-use std::io::{self, Write};
+use std::io::{self, IsTerminal, Write};
 // 导入标准库中的文件系统模块
 use std::fs;
-// 导入标准库中的Command模块，用于执行外部命令
-use std::process::Command;
-// 导入tokio运行时
-use tokio;
-
-// 声明词法分析器模块
-mod lexer;
-// 声明语法分析器模块
-mod parser;
-// 声明代码生成器模块
-mod codegen;
+// 导入进程退出码
+use std::process::ExitCode;
+// 导入编译器库中的各个阶段
+use nexa_lang::cache::CompileCache;
+use nexa_lang::exec::execute_rust_code;
+use nexa_lang::repl::{self, Repl};
+use nexa_lang::{codegen, diagnostic, lexer, logging, lsp, parser, Backend, CompileError, CompileOptions, Session};
+// 声明命令行参数解析模块
+mod cli;
+use cli::Command;
 // 声明Web编辑器模块
 mod web_editor;
+// 声明Web编辑器会话工作区模块
+mod workspace;
 
 // 直接测试词法分析器的函数
 // 用于快速验证词法分析器的基本功能
@@ -24,7 +25,7 @@ fn test_lexer_direct() {
     // 打印测试标题，空行用于分隔输出
     println!("\n=== 直接词法分析器测试 ===");
     // 打印测试时间标识，用于确认代码版本
-    println!("测试时间: {}", "2024-12-19 更新版本");
+    println!("测试时间: 2024-12-19 更新版本");
     
     // 测试基本的token识别
     let test_input = "var x = 10";
@@ -38,7 +39,7 @@ fn test_lexer_direct() {
             }
         }
         Err(e) => {
-            println!("❌ 词法分析错误: {}", e);
+            println!("❌ 词法分析错误: {}", diagnostic::format_all(&e));
         }
     }
     
@@ -55,7 +56,7 @@ fn test_lexer_direct() {
             }
         }
         Err(e) => {
-            println!("❌ 词法分析错误: {}", e);
+            println!("❌ 词法分析错误: {}", diagnostic::format_all(&e));
         }
     }
     
@@ -78,7 +79,7 @@ fn test_lexer_direct() {
             }
         }
         Err(e) => {
-            println!("❌ 词法分析错误: {}", e);
+            println!("❌ 词法分析错误: {}", diagnostic::format_all(&e));
         }
     }
 }
@@ -108,7 +109,7 @@ fn test_println_fix() {
                             
                             // 执行生成的代码
                             match execute_rust_code(&code) {
-                                Ok(result) => println!("执行结果: {}", result),
+                                Ok(result) => println!("执行结果: {}", result.stdout),
                                 Err(e) => println!("执行错误: {}", e)
                             }
                         },
@@ -118,12 +119,12 @@ fn test_println_fix() {
                     }
                 },
                 Err(e) => {
-                    println!("语法分析错误: {}", e);
+                    println!("语法分析错误: {}", diagnostic::format_all(&e));
                 }
             }
         },
         Err(e) => {
-            println!("词法分析错误: {}", e);
+            println!("词法分析错误: {}", diagnostic::format_all(&e));
         }
     }
     
@@ -146,7 +147,7 @@ fn test_println_fix() {
                             
                             // 执行生成的代码
                             match execute_rust_code(&code) {
-                                Ok(result) => println!("执行结果: {}", result),
+                                Ok(result) => println!("执行结果: {}", result.stdout),
                                 Err(e) => println!("执行错误: {}", e)
                             }
                         },
@@ -156,12 +157,12 @@ fn test_println_fix() {
                     }
                 },
                 Err(e) => {
-                    println!("语法分析错误: {}", e);
+                    println!("语法分析错误: {}", diagnostic::format_all(&e));
                 }
             }
         },
         Err(e) => {
-            println!("词法分析错误: {}", e);
+            println!("词法分析错误: {}", diagnostic::format_all(&e));
         }
     }
 }
@@ -194,7 +195,7 @@ fn test_while_loop_fix() {
                             // 执行生成的代码
                             match execute_rust_code(&code) {
                                 Ok(result) => {
-                                    println!("执行结果: {}", result);
+                                    println!("执行结果: {}", result.stdout);
                                 },
                                 Err(e) => {
                                     println!("执行错误: {}", e);
@@ -207,12 +208,12 @@ fn test_while_loop_fix() {
                     }
                 },
                 Err(e) => {
-                    println!("语法分析错误: {}", e);
+                    println!("语法分析错误: {}", diagnostic::format_all(&e));
                 }
             }
         },
         Err(e) => {
-            println!("词法分析错误: {}", e);
+            println!("词法分析错误: {}", diagnostic::format_all(&e));
         }
     }
 }
@@ -245,7 +246,7 @@ fn test_for_loop_fix() {
                             // 执行生成的代码
                             match execute_rust_code(&code) {
                                 Ok(result) => {
-                                    println!("执行结果: {}", result);
+                                    println!("执行结果: {}", result.stdout);
                                 },
                                 Err(e) => {
                                     println!("执行错误: {}", e);
@@ -258,212 +259,390 @@ fn test_for_loop_fix() {
                     }
                 },
                 Err(e) => {
-                    println!("语法分析错误: {}", e);
+                    println!("语法分析错误: {}", diagnostic::format_all(&e));
                 }
             }
         },
         Err(e) => {
-            println!("词法分析错误: {}", e);
+            println!("词法分析错误: {}", diagnostic::format_all(&e));
         }
     }
 }
 
-// 运行Nexa文件的函数
-// 接收文件路径作为参数，执行文件中的Nexa代码
-fn run_file(file_path: &str) {
-    // 读取文件内容
+// 读取Nexa文件，创建使用指定后端的编译会话，读取失败时打印错误并返回None
+fn open_session(file_path: &str, backend: Backend) -> Option<Session> {
     match fs::read_to_string(file_path) {
         Ok(content) => {
-            // 打印读取的文件内容
-            println!("读取文件内容:\n{}", content);
-            
-            // 调用词法分析器进行测试
-            match lexer::tokenize(&content) {
-                Ok(tokens) => {
-                    // 词法分析成功
-                    println!("词法分析成功: {:?}", tokens);
-                    // 调用语法分析器
-                    match parser::parse(&tokens) {
-                        Ok(ast) => {
-                            // 语法分析成功
-                            println!("语法分析成功: {:?}", ast);
-                            // 调用代码生成器
-                            match codegen::generate_code(&ast) {
-                                Ok(code) => {
-                                    // 代码生成成功
-                                    println!("代码生成成功:");
-                                    println!("{}", code);
-                                    // 执行生成的代码
-                                    match execute_rust_code(&code) {
-                                        Ok(result) => {
-                                            println!("执行结果: {}", result);
-                                        },
-                                        Err(e) => {
-                                            println!("执行错误: {}", e);
-                                        }
-                                    }
-                                },
-                                Err(e) => {
-                                    println!("代码生成错误: {}", e);
-                                }
-                            }
-                        },
-                        Err(e) => {
-                            println!("语法分析错误: {}", e);
-                        }
-                    }
-                },
-                Err(e) => {
-                    println!("词法分析错误: {}", e);
-                }
+            let options = CompileOptions {
+                file_name: file_path.to_string(),
+                backend,
+                ..CompileOptions::default()
+            };
+            Some(Session::with_options(content, options))
+        },
+        Err(e) => {
+            eprintln!("读取文件错误: {}: {}", file_path, e);
+            None
+        }
+    }
+}
+
+// 写到标准错误的红色文本：只有标准错误是终端时才着色
+// 标准输出的颜色设置（见main）不影响标准错误，例如 2>err.log 时日志中不会出现颜色代码
+fn stderr_red(text: &str) -> String {
+    diagnostic::paint(text, Some(Color::Red), false, io::stderr().is_terminal())
+}
+
+// 打印编译错误到标准错误：诊断按rustc的格式渲染，最后给出错误总数
+// 标准错误是终端时使用颜色，重定向到文件或管道时输出纯文本
+fn print_error(session: &Session, error: &CompileError) {
+    match error {
+        CompileError::Diagnostics { diagnostics, .. } => {
+            eprintln!("{}", session.render_error(error, io::stderr().is_terminal()));
+            let errors = diagnostics.iter().filter(|d| d.is_error()).count();
+            if errors > 0 {
+                eprintln!("{}", stderr_red(&format!("共 {} 个错误", errors)));
             }
         },
+        CompileError::Runtime(e) => eprintln!("\n执行错误: {}", e),
+        _ => eprintln!("{}", error),
+    }
+}
+
+// 读取Nexa文件并运行到某个编译阶段，出错时打印错误并返回None
+fn run_stage<T>(
+    file_path: &str,
+    backend: Backend,
+    stage: impl FnOnce(&Session) -> Result<T, CompileError>,
+) -> Option<T> {
+    let session = open_session(file_path, backend)?;
+    match stage(&session) {
+        Ok(value) => Some(value),
+        Err(e) => {
+            print_error(&session, &e);
+            None
+        },
+    }
+}
+
+// 运行Nexa文件的函数
+// 接收文件路径和后端，程序输出直接写到标准输出，返回是否运行成功
+fn run_file(file_path: &str, backend: Backend) -> bool {
+    run_stage(file_path, backend, |session| session.execute_to(io::stdout())).is_some()
+}
+
+// 打印Nexa文件编译出的字节码清单
+fn emit_bytecode(file_path: &str) -> bool {
+    match run_stage(file_path, Backend::Vm, Session::compile_bytecode) {
+        Some(program) => {
+            print!("{}", nexa_lang::bytecode::disassemble(&program));
+            true
+        },
+        None => false,
+    }
+}
+
+// 打印Nexa文件生成的Rust代码
+fn emit_rust(file_path: &str) -> bool {
+    match run_stage(file_path, Backend::Rustc, Session::generate) {
+        Some(code) => {
+            print!("{}", code);
+            true
+        },
+        None => false,
+    }
+}
+
+// 打印Nexa文件的词法单元，每行一个："行:列  词法单元"
+fn print_tokens(file_path: &str) -> bool {
+    match run_stage(file_path, Backend::Interpreter, Session::tokenize) {
+        Some(tokens) => {
+            for token in tokens {
+                println!("{}:{}\t{:?}", token.span.line, token.span.column, token.token);
+            }
+            true
+        },
+        None => false,
+    }
+}
+
+// 打印Nexa文件的抽象语法树
+fn print_ast(file_path: &str) -> bool {
+    match run_stage(file_path, Backend::Interpreter, Session::parse) {
+        Some(ast) => {
+            println!("{:#?}", ast);
+            true
+        },
+        None => false,
+    }
+}
+
+// 执行一个子命令，返回是否成功
+async fn run_command(command: Command) -> bool {
+    match command {
+        Command::Repl => {
+            repl().await;
+            true
+        },
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            true
+        },
+        Command::Run { file, backend } => run_file(&file, backend),
+        Command::Build { file, output } => run_stage(&file, Backend::Rustc, |session| session.build(&output)).is_some(),
+        Command::Check { file } => run_stage(&file, Backend::Interpreter, Session::check).is_some(),
+        Command::Tokens { file } => print_tokens(&file),
+        Command::Ast { file } => print_ast(&file),
+        Command::EmitRust { file } => emit_rust(&file),
+        Command::EmitBytecode { file } => emit_bytecode(&file),
+        Command::Web { port } => match web_editor::run(port).await {
+            Ok(_) => true,
+            Err(e) => {
+                eprintln!("{} {}", stderr_red("Web编辑器启动错误:"), e);
+                false
+            },
+        },
+        Command::CacheClean => clean_cache(),
+        // 语言服务器使用标准输出通信，日志只写到标准错误
+        Command::Lsp => match lsp::run(io::stdin().lock(), io::stdout().lock()) {
+            Ok(()) => true,
+            Err(e) => {
+                eprintln!("{} {}", stderr_red("语言服务器错误:"), e);
+                false
+            },
+        },
+    }
+}
+
+// 清空编译缓存，打印删除的文件数和释放的空间
+fn clean_cache() -> bool {
+    let cache = CompileCache::default();
+    match cache.clean() {
+        Ok(stats) => {
+            println!("已清空编译缓存 {}：删除 {} 个文件，释放 {} 字节", cache.dir().display(), stats.files, stats.bytes);
+            true
+        },
         Err(e) => {
-            println!("读取文件错误: {}", e);
+            eprintln!("{} {}", stderr_red("清空编译缓存错误:"), e);
+            false
+        },
+    }
+}
+
+// 从命令行参数中取出日志参数，返回过滤规则
+// --log <规则> 直接作为过滤规则，-v打开所有阶段的调试日志，-vv打开跟踪日志
+fn take_log_filter(args: &mut Vec<String>) -> Option<String> {
+    let mut filter = None;
+    let mut i = 0;
+    while i < args.len() {
+        match args[i].as_str() {
+            "--log" if i + 1 < args.len() => {
+                filter = Some(args.remove(i + 1));
+                args.remove(i);
+            },
+            "-v" | "--verbose" => {
+                filter = Some("debug".to_string());
+                args.remove(i);
+            },
+            "-vv" => {
+                filter = Some("trace".to_string());
+                args.remove(i);
+            },
+            _ => i += 1,
         }
     }
+    filter
 }
 
 // 主函数
-// 程序的入口点
+// 程序的入口点：有子命令时执行子命令，否则启动交互式环境
+// 退出码：0 成功，1 编译或运行错误，2 命令行参数错误
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
+    // 只有标准输出是终端时才输出颜色（只影响写到标准输出的文本，标准错误见stderr_red）
+    colored::control::set_override(io::stdout().is_terminal());
+    
+    // 全局日志参数：--log <过滤规则>、-v（调试）、-vv（跟踪），其余参数留给子命令
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    let log_filter = take_log_filter(&mut args);
+    if let Err(e) = logging::init(log_filter.as_deref()) {
+        eprintln!("{}", e);
+        return ExitCode::from(2);
+    }
+
+    let command = match cli::parse_args(&args) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("错误: {}\n\n{}", e, cli::USAGE);
+            return ExitCode::from(2);
+        },
+    };
+    if run_command(command).await {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+// 交互式环境：执行输入的Nexa代码，变量和函数在多次输入之间保留
+// 以冒号开头的是元命令（见 :help），输入exit或按Ctrl+D退出
+async fn repl() {
     // 打印欢迎信息
     println!("{}", "欢迎使用Nexa语言解释器 v0.1".bold().cyan());
-    println!("{}", "类型 'help' 查看可用命令，'exit' 退出程序".italic());
+    println!("{}", "输入Nexa代码直接执行，':help' 查看可用命令，'exit' 退出程序".italic());
     
+    let mut repl = Repl::new(io::stdout());
     // 主循环，持续接收用户输入
+    while let Some(input) = read_input() {
+        let input = input.trim_end();
+        match input.trim() {
+            // 空输入
+            "" => continue,
+            // 退出命令
+            "exit" | "quit" => break,
+            // 帮助命令
+            "help" => print_repl_help(),
+            // 元命令
+            command if command.starts_with(':') => {
+                if !meta_command(&mut repl, &command[1..]).await {
+                    break;
+                }
+            },
+            // 其他输入作为Nexa代码执行
+            _ => {
+                if let Err(e) = repl.eval(input) {
+                    print_error(&repl_session(input), &e);
+                }
+            },
+        }
+    }
+    println!("{}", "感谢使用Nexa语言解释器！".bold().cyan());
+}
+
+// 读取一段输入：代码块没有结束时（未闭合的花括号、以冒号结尾的行）继续读取下一行
+// 输入结束（Ctrl+D或管道读完）且没有读到内容时返回None
+fn read_input() -> Option<String> {
+    let mut buffer = String::new();
     loop {
-        // 打印命令提示符
-        print!("{}", "> ".bold().green());
+        // 第一行使用 "> " 提示符，续行使用 "... "
+        let prompt = if buffer.is_empty() { "> " } else { "... " };
+        print!("{}", prompt.bold().green());
         // 刷新标准输出，确保提示符立即显示
         io::stdout().flush().unwrap();
         
-        // 读取用户输入
-        let mut input = String::new();
-        match io::stdin().read_line(&mut input) {
-            Ok(_) => {
-                // 去除输入字符串末尾的换行符
-                let input = input.trim_end();
-                // 匹配用户输入的命令
-                match input {
-                    // 退出命令
-                    "exit" => {
-                        println!("{}", "感谢使用Nexa语言解释器！".bold().cyan());
-                        break;
-                    },
-                    // 帮助命令
-                    "help" => {
-                        println!("{}", "可用命令:".bold().cyan());
-                        println!("  help     - 显示此帮助信息");
-                        println!("  exit     - 退出程序");
-                        println!("  test     - 运行内置测试");
-                        println!("  run <文件路径> - 运行指定的Nexa文件");
-                        println!("  web      - 启动Web编辑器");
-                        println!("  lexer    - 测试词法分析器");
-                        println!("  while    - 测试while循环修复");
-                        println!("  for      - 测试for循环修复");
-                        println!("  println  - 测试println函数修复");
-                    },
-                    // 测试命令
-                    "test" => {
-                        // 调用所有测试函数
-                        test_lexer_direct();
-                        test_while_loop_fix();
-                        test_for_loop_fix();
-                        test_explicit_types();
-                        test_lexer();
-                        test_println_fix();
-                    },
-                    // 词法分析器测试命令
-                    "lexer" => {
-                        test_lexer_direct();
-                    },
-                    // while循环修复测试命令
-                    "while" => {
-                        test_while_loop_fix();
-                    },
-                    // for循环修复测试命令
-                    "for" => {
-                        test_for_loop_fix();
-                    },
-                    // println函数修复测试命令
-                    "println" => {
-                        test_println_fix();
-                    },
-                    // Web编辑器命令
-                    "web" => {
-                        println!("启动Web编辑器...");
-                        // 调用Web编辑器模块的run函数，并处理结果
-                        match web_editor::run().await {
-                            Ok(_) => println!("Web编辑器已关闭"),
-                            Err(e) => println!("{} {}", "Web编辑器启动错误:".red(), e),
-                        }
-                    },
-                    // 运行文件命令
-                    cmd if cmd.starts_with("run ") => {
-                        // 提取文件路径
-                        let file_path = &cmd[4..];
-                        run_file(file_path);
-                    },
-                    // 空输入
-                    "" => continue,
-                    // 其他输入
-                    _ => {
-                        println!("{}", "未知命令，请输入 'help' 查看可用命令".red());
-                    }
-                }
-            },
+        let mut line = String::new();
+        match io::stdin().read_line(&mut line) {
+            Ok(0) => return if buffer.is_empty() { None } else { Some(buffer) },
+            Ok(_) => {},
             Err(e) => {
-                println!("{}", format!("读取输入错误: {}", e).red());
-            }
+                eprintln!("{}", stderr_red(&format!("读取输入错误: {}", e)));
+                return None;
+            },
+        }
+        if !buffer.is_empty() {
+            buffer.push('\n');
+        }
+        buffer.push_str(line.trim_end_matches(['\n', '\r']));
+        if !repl::is_incomplete(&buffer) {
+            return Some(buffer);
         }
     }
 }
 
-// 执行Rust代码的函数
-// 将生成的Rust代码写入临时文件并执行
-fn execute_rust_code(code: &str) -> Result<String, String> {
-    // 创建临时文件
-    let temp_file = std::env::temp_dir().join("temp.rs");
-    // 写入代码到临时文件
-    match fs::write(&temp_file, code) {
-        Ok(_) => {},
-        Err(e) => {
-            return Err(format!("写入临时文件错误: {}", e));
+// 用于渲染交互式环境中错误的会话，诊断中的文件名显示为<repl>
+fn repl_session(input: &str) -> Session {
+    let options = CompileOptions {
+        file_name: "<repl>".to_string(),
+        ..CompileOptions::default()
+    };
+    Session::with_options(input, options)
+}
+
+// 打印交互式环境的帮助信息
+fn print_repl_help() {
+    println!("{}", "输入Nexa语句或表达式直接执行，表达式的值会显示出来".bold().cyan());
+    println!("以 '{{' 或 ':' 结尾的行开始多行输入，缩进代码块以空行结束");
+    println!("{}", "可用命令:".bold().cyan());
+    println!("  :help            - 显示此帮助信息");
+    println!("  :quit, exit      - 退出程序");
+    println!("  :reset           - 清空所有变量和函数");
+    println!("  :tokens <代码>   - 打印代码的词法单元");
+    println!("  :ast <代码>      - 打印代码的抽象语法树");
+    println!("  :rust <代码>     - 打印代码生成的Rust程序");
+    println!("  :type <表达式>   - 显示表达式的类型");
+    println!("  :run <文件路径>  - 使用解释器运行指定的Nexa文件");
+    println!("  :rustc <文件路径> - 生成Rust代码并用rustc编译运行");
+    println!("  :vm <文件路径>   - 编译为字节码并用虚拟机运行");
+    println!("  :bytecode <文件路径> - 打印字节码清单");
+    println!("  :web             - 启动Web编辑器");
+    println!("  :test [lexer|while|for|println] - 运行内置测试");
+}
+
+// 执行元命令，返回false表示退出交互式环境
+async fn meta_command(repl: &mut Repl<io::Stdout>, command: &str) -> bool {
+    let (name, argument) = match command.split_once(char::is_whitespace) {
+        Some((name, argument)) => (name, argument.trim()),
+        None => (command, ""),
+    };
+    // 对代码执行一个阶段，出错时打印错误
+    let report = |result: Result<(), CompileError>| {
+        if let Err(e) = result {
+            print_error(&repl_session(argument), &e);
         }
+    };
+    match name {
+        "help" => print_repl_help(),
+        "quit" | "exit" => return false,
+        "reset" => {
+            repl.reset();
+            println!("已清空所有变量和函数");
+        },
+        "tokens" => report(repl.tokens(argument).map(|tokens| {
+            for token in tokens {
+                println!("{}:{}\t{:?}", token.span.line, token.span.column, token.token);
+            }
+        })),
+        "ast" => report(repl.parse(argument).map(|ast| println!("{:#?}", ast))),
+        "rust" => report(repl.rust(argument).map(|code| print!("{}", code))),
+        "type" => report(repl.type_of(argument).map(|t| println!("{}", t))),
+        "run" => {
+            run_file(argument, Backend::Interpreter);
+        },
+        "rustc" => {
+            run_file(argument, Backend::Rustc);
+        },
+        "vm" => {
+            run_file(argument, Backend::Vm);
+        },
+        "bytecode" => {
+            emit_bytecode(argument);
+        },
+        "web" => {
+            println!("启动Web编辑器...");
+            // 调用Web编辑器模块的run函数，并处理结果
+            match web_editor::run(cli::DEFAULT_PORT).await {
+                Ok(_) => println!("Web编辑器已关闭"),
+                Err(e) => println!("{} {}", "Web编辑器启动错误:".red(), e),
+            }
+        },
+        "test" => match argument {
+            "lexer" => test_lexer_direct(),
+            "while" => test_while_loop_fix(),
+            "for" => test_for_loop_fix(),
+            "println" => test_println_fix(),
+            _ => {
+                // 调用所有测试函数
+                test_lexer_direct();
+                test_while_loop_fix();
+                test_for_loop_fix();
+                test_explicit_types();
+                test_lexer();
+                test_println_fix();
+            },
+        },
+        _ => println!("{}", format!("未知命令 ':{}'，请输入 ':help' 查看可用命令", name).red()),
     }
-    
-    // 编译临时文件
-    let output = Command::new("rustc")
-        .arg(&temp_file)
-        .arg("-o")
-        .arg(temp_file.with_extension(""))
-        .output()
-        .map_err(|e| format!("编译命令执行错误: {}", e))?;
-    
-    // 检查编译是否成功
-    if !output.status.success() {
-        // 编译失败，返回错误信息
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        return Err(format!("编译错误: {}", stderr));
-    }
-    
-    // 执行编译后的程序
-    let output = Command::new(temp_file.with_extension(""))
-        .output()
-        .map_err(|e| format!("执行命令错误: {}", e))?;
-    
-    // 检查执行是否成功
-    if output.status.success() {
-        // 执行成功，返回标准输出
-        let stdout = String::from_utf8_lossy(&output.stdout);
-        Ok(stdout.to_string())
-    } else {
-        // 执行失败，返回错误信息
-        let stderr = String::from_utf8_lossy(&output.stderr);
-        Err(format!("执行错误: {}", stderr))
-    }
+    true
 }
 
 // 测试显式类型定义的函数
@@ -504,7 +683,7 @@ fn test_explicit_types() {
                                 // 执行生成的代码
                                 match execute_rust_code(&code) {
                                     Ok(result) => {
-                                        println!("执行结果: {}", result);
+                                        println!("执行结果: {}", result.stdout);
                                     },
                                     Err(e) => {
                                         println!("执行错误: {}", e);
@@ -517,12 +696,12 @@ fn test_explicit_types() {
                         }
                     },
                     Err(e) => {
-                        println!("语法分析错误: {}", e);
+                        println!("语法分析错误: {}", diagnostic::format_all(&e));
                     }
                 }
             },
             Err(e) => {
-                println!("词法分析错误: {}", e);
+                println!("词法分析错误: {}", diagnostic::format_all(&e));
             }
         }
     }
@@ -558,7 +737,7 @@ fn test_lexer() {
             },
             Err(e) => {
                 // 测试失败，打印错误标志和错误信息
-                println!("❌ 错误: {}", e);
+                println!("❌ 错误: {}", diagnostic::format_all(&e));
             }
         }
     }
@@ -1,12 +1,33 @@
-// 导入词法分析器模块中的Token枚举，用于解析词法单元
-use crate::lexer::Token;
+// 导入词法分析器模块中的Token枚举和位置信息，用于解析词法单元
+use crate::lexer::{tokenize_with_recovery, Span, SpannedToken, Token};
+// 导入诊断信息类型，语法错误以诊断形式报告
+use crate::diagnostic::Diagnostic;
+use std::fmt;
+use tracing::{debug, trace};
 
-// 表达式枚举：表示程序中所有可能的表达式类型
-#[derive(Debug, PartialEq)]
-#[allow(dead_code)]
-pub enum Expr {
+// 表达式结构体：表达式种类加上它在源文件中的位置
+#[derive(Debug, PartialEq, Clone)]
+pub struct Expr {
+    // 表达式的具体种类
+    pub kind: ExprKind,
+    // 表达式覆盖的源代码范围
+    pub span: Span,
+}
+
+impl Expr {
+    // 创建新的表达式节点
+    pub fn new(kind: ExprKind, span: Span) -> Self {
+        Expr { kind, span }
+    }
+}
+
+// 表达式种类枚举：表示程序中所有可能的表达式类型
+#[derive(Debug, PartialEq, Clone)]
+pub enum ExprKind {
     // 数字字面量：i64类型整数
     Number(i64),
+    // 浮点数字面量：f64类型
+    Float(f64),
     // 字符串字面量：String类型
     String(String),
     // 布尔字面量：true或false
@@ -20,24 +41,33 @@ pub enum Expr {
     UnaryOp(Operator, Box<Expr>),
     // 范围表达式：表示从start到end的范围
     Range(Box<Expr>, Box<Expr>),
+    // 函数调用表达式：函数名和参数列表
+    Call(String, Vec<Expr>),
 }
 
 // 运算符枚举：表示程序中所有支持的运算符类型
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Operator {
     Add,    // 加法运算符：+ 或 add
     Sub,    // 减法运算符：- 或 sub
     Mul,    // 乘法运算符：* 或 mul
     Div,    // 除法运算符：/ 或 div
+    Mod,    // 取余运算符：%，结果的符号与除数相同
+    FloorDiv, // 整除运算符：//，向负无穷取整
+    Pow,    // 乘方运算符：**，右结合
     Eq,     // 相等运算符：= 或 eq
     Lt,     // 小于运算符：< 或 lt
     Le,     // 小于等于运算符：<= 或 le
     Gt,     // 大于运算符：> 或 gt
     Ge,     // 大于等于运算符：>= 或 ge
+    Ne,     // 不等运算符：!=
+    And,    // 逻辑与：and 或 &&，短路求值
+    Or,     // 逻辑或：or 或 ||，短路求值
+    Not,    // 逻辑非（一元）：not 或 !
 }
 
 // 数据类型枚举：表示支持的数据类型
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum DataType {
     Int,
     Float,
@@ -45,15 +75,42 @@ pub enum DataType {
     String,
 }
 
-// 语句枚举：表示程序中所有可能的语句类型
-#[derive(Debug, PartialEq)]
-pub enum Statement {
+// 函数参数：参数名、参数类型及其位置
+#[derive(Debug, PartialEq, Clone)]
+pub struct Param {
+    // 参数名
+    pub name: String,
+    // 参数类型（函数参数必须显式标注类型）
+    pub data_type: DataType,
+    // 参数在源文件中的位置
+    pub span: Span,
+}
+
+// 语句结构体：语句种类加上它在源文件中的位置
+#[derive(Debug, PartialEq, Clone)]
+pub struct Statement {
+    // 语句的具体种类
+    pub kind: StatementKind,
+    // 语句覆盖的源代码范围
+    pub span: Span,
+}
+
+impl Statement {
+    // 创建新的语句节点
+    pub fn new(kind: StatementKind, span: Span) -> Self {
+        Statement { kind, span }
+    }
+}
+
+// 语句种类枚举：表示程序中所有可能的语句类型
+#[derive(Debug, PartialEq, Clone)]
+pub enum StatementKind {
     // 变量声明语句（Nexa使用var），包含变量名、可选类型和初始值表达式
     Var(String, Option<DataType>, Box<Expr>),
     // 变量赋值语句，包含变量名和赋值表达式
     Assign(String, Box<Expr>),
-    // 打印语句，包含要打印的表达式
-    Print(Box<Expr>),
+    // 打印语句，包含要打印的表达式（多个表达式之间用空格分隔）
+    Print(Vec<Expr>),
     // 换行打印语句，包含多个要打印的表达式
     Println(Vec<Expr>),
     // 条件语句，包含条件表达式、then分支语句列表和可选的else分支语句列表
@@ -64,30 +121,249 @@ pub enum Statement {
     While(Box<Expr>, Vec<Statement>),
     // 表达式语句：独立的表达式（如函数调用、算术运算等）
     Expression(Box<Expr>),
+    // 函数定义语句：fn 函数名(参数列表) [-> 返回类型] { 语句列表 }
+    Function(String, Vec<Param>, Option<DataType>, Vec<Statement>),
+    // 返回语句：return [表达式]
+    Return(Option<Box<Expr>>),
+    // 跳出最内层循环：break
+    Break,
+    // 跳到最内层循环的下一次迭代：continue
+    Continue,
+}
+
+// 语法错误：每种错误带有出错位置和稳定的错误代码（E01xx）
+// found为None表示已经到达文件末尾
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParseError {
+    // E0101 期望某个词法单元
+    ExpectedToken { expected: Token, found: Option<Token>, span: Span },
+    // E0102 期望表达式
+    ExpectedExpression { found: Option<Token>, span: Span },
+    // E0103 期望数据类型关键字
+    ExpectedType { found: Option<Token>, span: Span },
+    // E0104 期望标识符，what说明标识符的用途（变量名、函数名等）
+    ExpectedIdentifier { what: &'static str, found: Option<Token>, span: Span },
+    // E0105 期望'{'或':'开始代码块，context说明是哪个代码块
+    ExpectedBlock { context: String, found: Option<Token>, span: Span },
+    // E0106 冒号换行后期望缩进的代码块
+    ExpectedIndent { found: Option<Token>, span: Span },
+    // E0107 无法作为语句开头的词法单元
+    UnexpectedStatement { found: Option<Token>, span: Span },
+    // E0108 不支持的运算符
+    UnsupportedOperator { token: Token, span: Span },
+    // E0109 已经移除的关键字（then、end）
+    RemovedKeyword { keyword: &'static str, span: Span },
+    // E0110 没有与之匹配的代码块开始的'}'或取消缩进
+    UnmatchedBlockEnd { token: Token, span: Span },
+}
 
+// 描述实际遇到的词法单元，None表示文件末尾
+fn describe(found: &Option<Token>) -> String {
+    match found {
+        Some(token) => format!("{:?}", token),
+        None => "文件末尾".to_string(),
+    }
 }
 
+impl ParseError {
+    // 稳定的错误代码，工具可以据此匹配错误种类
+    pub fn code(&self) -> &'static str {
+        match self {
+            ParseError::ExpectedToken { .. } => "E0101",
+            ParseError::ExpectedExpression { .. } => "E0102",
+            ParseError::ExpectedType { .. } => "E0103",
+            ParseError::ExpectedIdentifier { .. } => "E0104",
+            ParseError::ExpectedBlock { .. } => "E0105",
+            ParseError::ExpectedIndent { .. } => "E0106",
+            ParseError::UnexpectedStatement { .. } => "E0107",
+            ParseError::UnsupportedOperator { .. } => "E0108",
+            ParseError::RemovedKeyword { .. } => "E0109",
+            ParseError::UnmatchedBlockEnd { .. } => "E0110",
+        }
+    }
+
+    // 出错的源代码位置
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::ExpectedToken { span, .. }
+            | ParseError::ExpectedExpression { span, .. }
+            | ParseError::ExpectedType { span, .. }
+            | ParseError::ExpectedIdentifier { span, .. }
+            | ParseError::ExpectedBlock { span, .. }
+            | ParseError::ExpectedIndent { span, .. }
+            | ParseError::UnexpectedStatement { span, .. }
+            | ParseError::UnsupportedOperator { span, .. }
+            | ParseError::RemovedKeyword { span, .. }
+            | ParseError::UnmatchedBlockEnd { span, .. } => *span,
+        }
+    }
+
+    // 错误消息（不含位置）
+    pub fn message(&self) -> String {
+        match self {
+            ParseError::ExpectedToken { expected, found: Some(found), .. } => {
+                format!("期望 {:?}, 但得到 {:?}", expected, found)
+            },
+            ParseError::ExpectedToken { expected, found: None, .. } => {
+                format!("期望 {:?}, 但已到达文件末尾", expected)
+            },
+            ParseError::ExpectedExpression { found: Some(found), .. } => format!("期望表达式，但得到 {:?}", found),
+            ParseError::ExpectedExpression { found: None, .. } => "期望表达式，但已到达文件末尾".to_string(),
+            ParseError::ExpectedType { found, .. } => {
+                format!("期望数据类型 (int, float, bool, string)，但得到 {}", describe(found))
+            },
+            ParseError::ExpectedIdentifier { what, found, .. } => {
+                format!("期望标识符作为{}，但得到 {}", what, describe(found))
+            },
+            ParseError::ExpectedBlock { context, found, .. } => {
+                format!("期望 '{{' 或 ':' 开始{}，实际找到: {}", context, describe(found))
+            },
+            ParseError::ExpectedIndent { found, .. } => format!("期望缩进的代码块，但得到 {}", describe(found)),
+            ParseError::UnexpectedStatement { found: Some(found), .. } => format!("无法识别的语句: {:?}", found),
+            ParseError::UnexpectedStatement { found: None, .. } => "期望语句，但已到达文件末尾".to_string(),
+            ParseError::UnsupportedOperator { token, .. } => format!("无效的运算符: {:?}", token),
+            ParseError::RemovedKeyword { keyword, .. } => format!("'{}'关键字不再使用", keyword),
+            ParseError::UnmatchedBlockEnd { token, .. } => format!("多余的 {:?}，没有与之匹配的代码块开始", token),
+        }
+    }
+}
+
+impl From<ParseError> for Diagnostic {
+    fn from(error: ParseError) -> Self {
+        let diagnostic = Diagnostic::error(error.message(), error.span()).with_code(error.code());
+        match error {
+            ParseError::RemovedKeyword { .. } => diagnostic.with_help("请使用花括号{}或缩进风格代替"),
+            _ => diagnostic,
+        }
+    }
+}
+
+// 以诊断的单行格式显示
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Diagnostic::from(self.clone()))
+    }
+}
+
+// not运算符的优先级：低于比较运算，高于and
+const NOT_PRECEDENCE: u8 = 2;
+// 负号操作数的最低优先级，与乘方相同：只有乘方比负号结合得更紧（-2 ** 2 即 -(2 ** 2)，-x * 2 即 (-x) * 2）
+const NEG_OPERAND_PRECEDENCE: u8 = 6;
+
 // 解析器结构体：用于将词法单元序列转换为抽象语法树
 pub struct Parser {
-    // 存储待解析的词法单元序列
-    tokens: Vec<Token>,
+    // 存储待解析的词法单元序列（带位置信息）
+    tokens: Vec<SpannedToken>,
     // 当前解析位置的索引，用于跟踪解析进度
     position: usize,
+    // 已经恢复的语法错误，解析结束后一起报告
+    errors: Vec<ParseError>,
 }
 
 // 解析器实现块：为Parser结构体添加方法
 impl Parser {
     // 创建新的解析器实例
-    fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(tokens: Vec<SpannedToken>) -> Self {
         Parser {
             tokens,      // 初始化词法单元序列
             position: 0,  // 初始化位置为0，从第一个token开始
+            errors: Vec::new(), // 初始没有错误
         }
     }
+
+    // 解析过程中记录的所有语法错误
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
     
     // 获取当前位置的词法单元
     fn current(&self) -> Option<&Token> {
-        self.tokens.get(self.position)
+        self.tokens.get(self.position).map(|t| &t.token)
+    }
+    
+    // 获取当前词法单元的位置
+    // 如果已到达末尾，返回最后一个token结束处的位置
+    fn current_span(&self) -> Span {
+        match self.tokens.get(self.position) {
+            Some(t) => t.span,
+            None => self.eof_span(),
+        }
+    }
+    
+    // 获取上一个已消耗词法单元的位置，用于计算语法节点的结束位置
+    fn previous_span(&self) -> Span {
+        if self.position == 0 {
+            return self.current_span();
+        }
+        match self.tokens.get(self.position - 1) {
+            Some(t) => t.span,
+            None => self.eof_span(),
+        }
+    }
+    
+    // 文件末尾的位置：紧跟在最后一个token之后
+    fn eof_span(&self) -> Span {
+        match self.tokens.last() {
+            Some(t) => Span::new(t.span.line, t.span.column + (t.span.end - t.span.start), t.span.end, t.span.end),
+            None => Span::new(1, 1, 0, 0),
+        }
+    }
+    
+    // 当前的词法单元和它的位置，用于生成语法错误
+    fn found(&self) -> (Option<Token>, Span) {
+        (self.current().cloned(), self.current_span())
+    }
+    
+    // 解析一条语句并加入列表；出错时记录错误并跳到下一条语句继续解析
+    fn parse_statement_into(&mut self, statements: &mut Vec<Statement>) {
+        match self.parse_statement() {
+            Ok(stmt) => statements.push(stmt),
+            Err(error) => {
+                self.errors.push(error);
+                self.synchronize();
+            },
+        }
+    }
+    
+    // 错误恢复：跳过出错语句剩余的token，停在换行、分号或所在代码块的结束处（'}'或Dedent）
+    // 出错语句后面如果跟着缩进的代码块（例如写错的if头部），该代码块也一并跳过，避免连锁错误
+    fn synchronize(&mut self) {
+        // 跳过过程中进入的嵌套代码块层数
+        let mut depth = 0;
+        while let Some(token) = self.current() {
+            match token {
+                Token::Newline | Token::Semicolon if depth == 0 => {
+                    let mut lookahead = self.position;
+                    while let Some(Token::Newline) = self.tokens.get(lookahead).map(|t| &t.token) {
+                        lookahead += 1;
+                    }
+                    if self.tokens.get(lookahead).map(|t| &t.token) != Some(&Token::Indent) {
+                        return;
+                    }
+                    self.position = lookahead;
+                    continue;
+                },
+                Token::Indent | Token::LBrace => depth += 1,
+                Token::Dedent | Token::RBrace => {
+                    if depth == 0 {
+                        return;
+                    }
+                    depth -= 1;
+                    // 跳过的嵌套代码块结束，后面是下一条语句
+                    if depth == 0 {
+                        self.advance();
+                        return;
+                    }
+                },
+                _ => {},
+            }
+            self.advance();
+        }
+    }
+    
+    // 判断当前位置之后第offset个词法单元是否为指定token
+    fn peek_is(&self, offset: usize, expected: &Token) -> bool {
+        self.tokens.get(self.position + offset).map(|t| &t.token) == Some(expected)
     }
     
     // 前进到下一个词法单元
@@ -98,46 +374,53 @@ impl Parser {
     // 消耗指定的词法单元
     // 如果当前token与期望的token匹配，则前进位置并返回成功
     // 如果不匹配，则返回错误信息
-    fn consume(&mut self, expected: Token) -> Result<(), String> {
-        if let Some(token) = self.current() {
-            if token == &expected {
-                // 匹配成功，前进位置
-                self.advance();
-                Ok(())
-            } else {
-                // 匹配失败，返回错误信息
-                Err(format!("期望 {:?}, 但得到 {:?}", expected, token))
-            }
+    fn consume(&mut self, expected: Token) -> Result<(), ParseError> {
+        if self.current() == Some(&expected) {
+            // 匹配成功，前进位置
+            self.advance();
+            Ok(())
         } else {
-            // 已到达末尾，返回错误信息
-            Err(format!("期望 {:?}, 但已到达文件末尾", expected))
+            // 匹配失败或已到达末尾，返回错误信息
+            let (found, span) = self.found();
+            Err(ParseError::ExpectedToken { expected, found, span })
         }
     }
     
     // 解析基本表达式（原子表达式）
     // 包括数字、字符串、布尔、标识符和括号表达式
-    fn parse_primary(&mut self) -> Result<Expr, String> {
+    fn parse_primary(&mut self) -> Result<Expr, ParseError> {
+        // 记录表达式起始位置
+        let span = self.current_span();
         match self.current().cloned() {
             // 匹配数字字面量
             Some(Token::Number(n)) => {
                 self.advance();
-                Ok(Expr::Number(n))
+                Ok(Expr::new(ExprKind::Number(n), span))
+            },
+            // 匹配浮点数字面量
+            Some(Token::FloatLiteral(x)) => {
+                self.advance();
+                Ok(Expr::new(ExprKind::Float(x), span))
             },
             // 匹配字符串字面量
             Some(Token::StringLiteral(s)) => {
                 self.advance();
-                Ok(Expr::String(s))
+                Ok(Expr::new(ExprKind::String(s), span))
             },
             // 匹配布尔字面量
             Some(Token::BoolLiteral(b)) => {
                 self.advance();
-                Ok(Expr::BoolLiteral(b))
+                Ok(Expr::new(ExprKind::BoolLiteral(b), span))
             },
-            // 匹配标识符（变量名）
+            // 匹配标识符（变量名）或函数调用：name(args)
             Some(Token::Identifier(id)) => {
                 let name = id;
                 self.advance();
-                Ok(Expr::Identifier(name))
+                if let Some(Token::LParen) = self.current() {
+                    let arguments = self.parse_call_arguments()?;
+                    return Ok(Expr::new(ExprKind::Call(name, arguments), span.to(self.previous_span())));
+                }
+                Ok(Expr::new(ExprKind::Identifier(name), span))
             },
             // 匹配括号表达式
             Some(Token::LParen) => {
@@ -149,20 +432,17 @@ impl Parser {
                     self.advance();
                     let second_expr = self.parse_expression()?;
                     self.consume(Token::RParen)?;
-                    Ok(Expr::Range(Box::new(first_expr), Box::new(second_expr)))
+                    Ok(Expr::new(ExprKind::Range(Box::new(first_expr), Box::new(second_expr)), span.to(self.previous_span())))
                 } else {
                     // 普通括号表达式
                     self.consume(Token::RParen)?;
                     Ok(first_expr)
                 }
             },
-            // 匹配到不期望的token
-            Some(token) => {
-                Err(format!("期望表达式，但得到 {:?}", token))
-            },
-            // 已到达末尾
-            None => {
-                Err("期望表达式，但已到达文件末尾".to_string())
+            // 匹配到不期望的token或已到达末尾
+            _ => {
+                let (found, span) = self.found();
+                Err(ParseError::ExpectedExpression { found, span })
             },
         }
     }
@@ -179,8 +459,16 @@ impl Parser {
             Token::Star | Token::Mul => Some(Operator::Mul),
             // 除法运算符：/ 或 div
             Token::Slash | Token::Div => Some(Operator::Div),
-            // 相等运算符：= 或 eq
-            Token::Equal | Token::Eq => Some(Operator::Eq),
+            // 取余运算符：%
+            Token::Percent => Some(Operator::Mod),
+            // 整除运算符：//
+            Token::SlashSlash => Some(Operator::FloorDiv),
+            // 乘方运算符：**
+            Token::StarStar => Some(Operator::Pow),
+            // 相等运算符：=、== 或 eq
+            Token::Equal | Token::EqualEqual | Token::Eq => Some(Operator::Eq),
+            // 不等运算符：!=
+            Token::NotEqual => Some(Operator::Ne),
             // 小于运算符：< 或 lt
             Token::LessThan | Token::Lt => Some(Operator::Lt),
             // 小于等于运算符：<= 或 le
@@ -189,35 +477,111 @@ impl Parser {
             Token::GreaterThan | Token::Gt => Some(Operator::Gt),
             // 大于等于运算符：>= 或 ge
             Token::GreaterThanOrEqual => Some(Operator::Ge),
+            // 逻辑与：and 或 &&
+            Token::And | Token::AmpAmp => Some(Operator::And),
+            // 逻辑或：or 或 ||
+            Token::Or | Token::PipePipe => Some(Operator::Or),
             // 不是运算符
             _ => None,
         }
     }
     
+    // 解析数据类型关键字：int, float, bool, string
+    fn parse_data_type(&mut self) -> Result<DataType, ParseError> {
+        let data_type = match self.current() {
+            Some(Token::Int) => DataType::Int,
+            Some(Token::Float) => DataType::Float,
+            Some(Token::Bool) => DataType::Bool,
+            Some(Token::StringType) => DataType::String,
+            _ => {
+                let (found, span) = self.found();
+                return Err(ParseError::ExpectedType { found, span });
+            },
+        };
+        self.advance();
+        Ok(data_type)
+    }
+    
+    // 解析函数调用的参数列表：(expr1, expr2, ...)
+    // 调用前当前token必须是左括号
+    fn parse_call_arguments(&mut self) -> Result<Vec<Expr>, ParseError> {
+        self.consume(Token::LParen)?;
+        
+        let mut arguments = Vec::new();
+        if let Some(Token::RParen) = self.current() {
+            // 无参数调用
+            self.advance();
+            return Ok(arguments);
+        }
+        
+        arguments.push(self.parse_expression()?);
+        // 解析逗号分隔的额外参数
+        while let Some(Token::Comma) = self.current() {
+            self.advance(); // 消耗逗号
+            arguments.push(self.parse_expression()?);
+        }
+        
+        self.consume(Token::RParen)?;
+        Ok(arguments)
+    }
+    
     // 解析表达式
     // 调用二元表达式解析函数，初始优先级为0
-    fn parse_expression(&mut self) -> Result<Expr, String> {
+    fn parse_expression(&mut self) -> Result<Expr, ParseError> {
         self.parse_binary_expression(0)
     }
     
     // 获取运算符的优先级
     // 数值越大优先级越高，用于正确处理运算符优先级
+    // 从低到高：or < and < not < 比较 < 加减 < 乘除取余整除 < 乘方
     fn precedence(&self, op: &Operator) -> u8 {
         match op {
-            // 加减法优先级为1
-            Operator::Add | Operator::Sub => 1,
-            // 乘除法优先级为2，高于加减法
-            Operator::Mul | Operator::Div => 2,
-            // 比较运算符优先级最低，为0
-            Operator::Eq | Operator::Lt | Operator::Le | Operator::Gt | Operator::Ge => 0,
+            // 逻辑或优先级最低，为0
+            Operator::Or => 0,
+            // 逻辑与优先级为1
+            Operator::And => 1,
+            // not的操作数是比较表达式，优先级为2
+            Operator::Not => NOT_PRECEDENCE,
+            // 比较运算符优先级为3
+            Operator::Eq | Operator::Ne | Operator::Lt | Operator::Le | Operator::Gt | Operator::Ge => 3,
+            // 加减法优先级为4
+            Operator::Add | Operator::Sub => 4,
+            // 乘除、取余和整除优先级为5，高于加减法
+            Operator::Mul | Operator::Div | Operator::Mod | Operator::FloorDiv => 5,
+            // 乘方优先级最高，为6
+            Operator::Pow => 6,
         }
     }
     
+    // 解析一元表达式
+    // not的优先级低于比较（not x > 1 即 not (x > 1)），!只作用于紧跟的操作数（!done）
+    // 负号的优先级高于乘除、低于乘方
+    fn parse_unary(&mut self) -> Result<Expr, ParseError> {
+        let span = self.current_span();
+        let (op, operand) = match self.current() {
+            Some(Token::Not) => {
+                self.advance();
+                (Operator::Not, self.parse_binary_expression(NOT_PRECEDENCE + 1)?)
+            },
+            Some(Token::Bang) => {
+                self.advance();
+                (Operator::Not, self.parse_unary()?)
+            },
+            Some(Token::Minus) => {
+                self.advance();
+                (Operator::Sub, self.parse_binary_expression(NEG_OPERAND_PRECEDENCE)?)
+            },
+            _ => return self.parse_primary(),
+        };
+        let span = span.to(operand.span);
+        Ok(Expr::new(ExprKind::UnaryOp(op, Box::new(operand)), span))
+    }
+    
     // 解析二元表达式
     // 使用递归下降算法处理运算符优先级
-    fn parse_binary_expression(&mut self, precedence: u8) -> Result<Expr, String> {
-        // 首先解析左操作数（基本表达式）
-        let mut left = self.parse_primary()?;
+    fn parse_binary_expression(&mut self, precedence: u8) -> Result<Expr, ParseError> {
+        // 首先解析左操作数（一元表达式或基本表达式）
+        let mut left = self.parse_unary()?;
         
         // 循环处理后续运算符和右操作数
         while let Some(token) = self.current() {
@@ -230,10 +594,12 @@ impl Parser {
                 
                 // 消耗运算符token
                 self.advance();
-                // 递归解析右操作数，优先级+1确保左结合性
-                let right = self.parse_binary_expression(current_precedence + 1)?;
-                // 构建二元运算表达式节点
-                left = Expr::BinaryOp(Box::new(left), op, Box::new(right));
+                // 递归解析右操作数，优先级+1确保左结合性；乘方是右结合的，使用相同的优先级
+                let right_precedence = if op == Operator::Pow { current_precedence } else { current_precedence + 1 };
+                let right = self.parse_binary_expression(right_precedence)?;
+                // 构建二元运算表达式节点，位置覆盖左右操作数
+                let span = left.span.to(right.span);
+                left = Expr::new(ExprKind::BinaryOp(Box::new(left), op, Box::new(right)), span);
             } else {
                 // 不是运算符，结束解析
                 break;
@@ -244,15 +610,23 @@ impl Parser {
     }
     
     // 解析语句
+    // 记录语句的起止位置，具体解析交给parse_statement_kind
+    fn parse_statement(&mut self) -> Result<Statement, ParseError> {
+        let start = self.current_span();
+        let kind = self.parse_statement_kind()?;
+        Ok(Statement::new(kind, start.to(self.previous_span())))
+    }
+    
+    // 解析语句种类
     // 根据当前token的类型选择相应的解析方法
-    fn parse_statement(&mut self) -> Result<Statement, String> {
+    fn parse_statement_kind(&mut self) -> Result<StatementKind, ParseError> {
         match self.current() {
             // 处理表达式语句：直接解析表达式作为语句
-            Some(Token::Number(_)) | Some(Token::StringLiteral(_)) | Some(Token::BoolLiteral(_)) | 
-            Some(Token::LParen) | Some(Token::Plus) | Some(Token::Minus) => {
+            Some(Token::Number(_)) | Some(Token::FloatLiteral(_)) | Some(Token::StringLiteral(_)) | Some(Token::BoolLiteral(_)) | 
+            Some(Token::LParen) | Some(Token::Plus) | Some(Token::Minus) | Some(Token::Not) | Some(Token::Bang) => {
                 // 解析整个表达式
                 let expr = self.parse_expression()?;
-                Ok(Statement::Expression(Box::new(expr)))
+                Ok(StatementKind::Expression(Box::new(expr)))
             },
             // 解析变量声明语句：var variable [: type] = expression
             Some(Token::Var) => {
@@ -267,25 +641,7 @@ impl Parser {
                     // 检查是否有类型注解
                     let data_type = if let Some(Token::Colon) = self.current() {
                         self.advance(); // 消耗冒号
-                        match self.current() {
-                            Some(Token::Int) => {
-                                self.advance();
-                                Some(DataType::Int)
-                            },
-                            Some(Token::Float) => {
-                                self.advance();
-                                Some(DataType::Float)
-                            },
-                            Some(Token::Bool) => {
-                                self.advance();
-                                Some(DataType::Bool)
-                            },
-                            Some(Token::StringType) => {
-                                self.advance();
-                                Some(DataType::String)
-                            },
-                            _ => return Err("期望数据类型 (int, float, bool, string)".to_string())
-                        }
+                        Some(self.parse_data_type()?)
                     } else {
                         None // 没有类型注解
                     };
@@ -295,14 +651,22 @@ impl Parser {
                     // 解析初始值表达式
                     let expr = self.parse_expression()?;
                     
-                    Ok(Statement::Var(name, data_type, Box::new(expr)))
+                    Ok(StatementKind::Var(name, data_type, Box::new(expr)))
                 } else {
-                    Err("期望标识符作为变量名".to_string())
+                    let (found, span) = self.found();
+                    Err(ParseError::ExpectedIdentifier { what: "变量名", found, span })
                 }
             },
+            // 函数调用语句：name(args)，作为表达式语句解析
+            Some(Token::Identifier(_)) if self.peek_is(1, &Token::LParen) => {
+                let expr = self.parse_expression()?;
+                Ok(StatementKind::Expression(Box::new(expr)))
+            },
             // 解析标识符相关语句：可能是赋值语句或表达式语句
             Some(Token::Identifier(id)) => {
                 let name = id.clone();
+                let name_span = self.current_span();
+                let start = self.position;
                 self.advance();
                 
                 // 跳过任何换行token
@@ -310,12 +674,15 @@ impl Parser {
                     self.advance();
                 }
                 
-                // 检查是否是复合赋值语句 (+=, -=, *=, /= 等)
+                // 检查是否是复合赋值语句 (+=, -=, *=, /=, %=, //=, **= 等)
                 if let Some(op_token) = self.current() {
                     // 检查是否是可能的复合赋值运算符前缀
-                    if matches!(op_token, Token::Plus | Token::Minus | Token::Star | Token::Slash | Token::LessThan | Token::GreaterThan) {
+                    if matches!(op_token, Token::Plus | Token::Minus | Token::Star | Token::Slash | Token::Percent | Token::SlashSlash | Token::StarStar | Token::LessThan | Token::GreaterThan) {
                         // 保存当前运算符
-                        let op = self.get_operator(op_token).ok_or(format!("无效的运算符: {:?}", op_token))?;
+                        let op = self.get_operator(op_token).ok_or_else(|| ParseError::UnsupportedOperator {
+                            token: op_token.clone(),
+                            span: self.current_span(),
+                        })?;
                         self.advance();
                         
                         // 检查下一个token是否是等号
@@ -324,17 +691,16 @@ impl Parser {
                             let rhs = self.parse_expression()?;
                             
                             // 构建复合赋值的表达式: 例如 i += 1 转换为 i = i + 1
-                            let lhs = Expr::Identifier(name.clone());
-                            let combined_expr = Expr::BinaryOp(Box::new(lhs), op, Box::new(rhs));
+                            let lhs = Expr::new(ExprKind::Identifier(name.clone()), name_span);
+                            let span = name_span.to(rhs.span);
+                            let combined_expr = Expr::new(ExprKind::BinaryOp(Box::new(lhs), op, Box::new(rhs)), span);
                             
-                            return Ok(Statement::Assign(name, Box::new(combined_expr)));
+                            return Ok(StatementKind::Assign(name, Box::new(combined_expr)));
                         } else {
-                            // 不是复合赋值，回退并解析为普通二元表达式
-                            // 构建二元运算表达式
-                            let lhs = Expr::Identifier(name);
-                            let rhs = self.parse_binary_expression(self.precedence(&op) + 1)?;
-                            let expr = Expr::BinaryOp(Box::new(lhs), op, Box::new(rhs));
-                            return Ok(Statement::Expression(Box::new(expr)));
+                            // 不是复合赋值，回退到标识符处把整行解析为表达式语句
+                            self.position = start;
+                            let expr = self.parse_expression()?;
+                            return Ok(StatementKind::Expression(Box::new(expr)));
                         }
                     }
                 }
@@ -343,11 +709,12 @@ impl Parser {
                 if let Some(Token::Equal) = self.current() {
                     self.advance();
                     let expr = self.parse_expression()?;
-                    Ok(Statement::Assign(name, Box::new(expr)))
+                    Ok(StatementKind::Assign(name, Box::new(expr)))
                 } else {
-                    // 不是赋值语句，解析为表达式语句
-                    let expr = Expr::Identifier(name);
-                    Ok(Statement::Expression(Box::new(expr)))
+                    // 不是赋值语句，回退到标识符处解析为表达式语句
+                    self.position = start;
+                    let expr = self.parse_expression()?;
+                    Ok(StatementKind::Expression(Box::new(expr)))
                 }
             },
             // 解析打印语句：print expression 或 print(expr1, expr2, ...)
@@ -371,21 +738,11 @@ impl Parser {
                     
                     self.consume(Token::RParen)?;
                     
-                    // 将多个表达式用二元操作符连接成单个表达式
-                    let combined_expr = if expressions.len() == 1 {
-                        expressions.into_iter().next().unwrap()
-                    } else {
-                        // 用加法操作符连接所有表达式（实际生成时会用空格分隔）
-                        expressions.into_iter().reduce(|acc, expr| {
-                            Expr::BinaryOp(Box::new(acc), Operator::Add, Box::new(expr))
-                        }).unwrap()
-                    };
-                    
-                    Ok(Statement::Print(Box::new(combined_expr)))
+                    Ok(StatementKind::Print(expressions))
                 } else {
                     // 单参数语法：print expression
                     let expr = self.parse_expression()?;
-                    Ok(Statement::Print(Box::new(expr)))
+                    Ok(StatementKind::Print(vec![expr]))
                 }
             },
             // 解析换行打印语句：println expression 或 println(expr1, expr2, ...)
@@ -409,109 +766,20 @@ impl Parser {
                     
                     self.consume(Token::RParen)?;
                     
-                    Ok(Statement::Println(expressions))
+                    Ok(StatementKind::Println(expressions))
                 } else {
                     // 单参数语法：println expression
                     let expr = self.parse_expression()?;
-                    Ok(Statement::Println(vec![expr]))
+                    Ok(StatementKind::Println(vec![expr]))
                 }
             },
-            // 解析条件语句：if condition { statements } [else { statements }] 或 if condition\n    statements（缩进风格）
+            // 解析条件语句：if condition { statements } [else { statements }] 或 if condition:\n    statements（缩进风格）
             Some(Token::If) => {
                 // 消耗if关键字
                 self.advance();
-                // 解析条件表达式
-                let condition = self.parse_expression()?;
-                
-                // 检查代码块开始方式：只支持花括号
-                let mut then_branch = Vec::new();
-
-                // 只支持花括号语法
-                if let Some(Token::LBrace) = self.current() {
-                    // 消耗左花括号
-                    self.advance();
-                    
-                    // 解析花括号内的语句
-                    while let Some(token) = self.current() {
-                        if let Token::RBrace = token {
-                            break;
-                        }
-                        
-                        // 跳过换行符
-                        while let Some(Token::Newline) = self.current() {
-                            self.advance();
-                        }
-                        
-                        if let Some(token) = self.current() {
-                            if let Token::RBrace = token {
-                                break;
-                            }
-                            then_branch.push(self.parse_statement()?);
-                            
-                            // 如果下一个token是分号，消耗它（可选的分号分隔符）
-                            while let Some(Token::Semicolon) = self.current() {
-                                self.advance();
-                            }
-                        }
-                    }
-                    
-                    // 期望右花括号结束then分支
-                    self.consume(Token::RBrace)?;
-                } else {
-                    // 不支持其他语法，返回错误
-                    return Err(format!("期望 '{{' 开始then分支，实际找到: {:?}", self.current()));
-                }
-                
-                // 解析可选的else分支
-                let else_branch = if let Some(Token::Else) = self.current() {
-                    self.advance();
-                    let mut else_stmts = Vec::new();
-                    
-                    // 检查else分支的代码块开始方式
-                    match self.current() {
-                        // 只支持花括号语法
-                        Some(Token::LBrace) => {
-                            // 消耗左花括号
-                            self.advance();
-                            
-                            // 解析花括号内的语句
-                            while let Some(token) = self.current() {
-                                if let Token::RBrace = token {
-                                    break;
-                                }
-                                
-                                // 跳过换行符
-                                while let Some(Token::Newline) = self.current() {
-                                    self.advance();
-                                }
-                                
-                                if let Some(token) = self.current() {
-                                    if let Token::RBrace = token {
-                                        break;
-                                    }
-                                    else_stmts.push(self.parse_statement()?);
-                                    
-                                    // 如果下一个token是分号，消耗它（可选的分号分隔符）
-                                    while let Some(Token::Semicolon) = self.current() {
-                                        self.advance();
-                                    }
-                                }
-                            }
-                            
-                            // 期望右花括号结束else分支
-                            self.consume(Token::RBrace)?;
-                        },
-                        _ => return Err(format!("期望 '{{' 开始else分支，实际找到: {:?}", self.current())),
-                    }
-                    
-                    Some(else_stmts)
-                } else {
-                    None
-                };
-                
-                Ok(Statement::If(Box::new(condition), then_branch, else_branch))
+                self.parse_if()
             },
-            // 解析for循环：for variable in expression { statements } 或 for variable in expression\n    statements（缩进风格）
+            // 解析for循环：for variable in expression { statements } 或 for variable in expression:\n    statements（缩进风格）
             Some(Token::For) => {
                 // 消耗for关键字
                 self.advance();
@@ -522,7 +790,8 @@ impl Parser {
                     self.advance();
                     name
                 } else {
-                    return Err("期望标识符作为循环变量".to_string());
+                    let (found, span) = self.found();
+                    return Err(ParseError::ExpectedIdentifier { what: "循环变量", found, span });
                 };
                 
                 // 期望in关键字
@@ -531,49 +800,12 @@ impl Parser {
                 // 解析要遍历的表达式（通常是字符串）
                 let iterable = self.parse_expression()?;
                 
-                // 检查代码块开始方式：只支持花括号
-                let body = match self.current() {
-                    // 只支持花括号语法
-                    Some(Token::LBrace) => {
-                        // 消耗左花括号
-                        self.advance();
-                        
-                        // 解析花括号内的语句
-                        let mut statements = Vec::new();
-                        while let Some(token) = self.current() {
-                            if let Token::RBrace = token {
-                                break;
-                            }
-                            
-                            // 跳过换行符
-                            while let Some(Token::Newline) = self.current() {
-                                self.advance();
-                            }
-                            
-                            if let Some(token) = self.current() {
-                                if let Token::RBrace = token {
-                                    break;
-                                }
-                                statements.push(self.parse_statement()?);
-                                
-                                // 如果下一个token是分号，消耗它（可选的分号分隔符）
-                                while let Some(Token::Semicolon) = self.current() {
-                                    self.advance();
-                                }
-                            }
-                        }
-                        
-                        // 期望右花括号结束循环体
-                        self.consume(Token::RBrace)?;
-                        statements
-                    },
-                    // 不支持其他语法
-                    _ => return Err("期望'{'开始循环体".to_string()),
-                };
+                // 解析循环体
+                let body = self.parse_block("循环体")?;
                 
-                Ok(Statement::For(var_name, Box::new(iterable), body))
+                Ok(StatementKind::For(var_name, Box::new(iterable), body))
             },
-            // 解析while循环：while condition { statements } 或 while condition\n    statements（缩进风格）
+            // 解析while循环：while condition { statements } 或 while condition:\n    statements（缩进风格）
             Some(Token::While) => {
                 // 消耗while关键字
                 self.advance();
@@ -581,95 +813,572 @@ impl Parser {
                 // 解析循环条件表达式
                 let condition = self.parse_expression()?;
                 
-                // 检查代码块开始方式：只支持花括号
-                let body = match self.current() {
-                    // 只支持花括号语法
-                    Some(Token::LBrace) => {
-                        // 消耗左花括号
-                        self.advance();
-                        
-                        // 解析花括号内的语句
-                        let mut statements = Vec::new();
-                        while let Some(token) = self.current() {
-                            if let Token::RBrace = token {
-                                break;
-                            }
-                            
-                            // 跳过换行符
-                            while let Some(Token::Newline) = self.current() {
-                                self.advance();
-                            }
-                            
-                            if let Some(token) = self.current() {
-                                if let Token::RBrace = token {
-                                    break;
-                                }
-                                statements.push(self.parse_statement()?);
-                                
-                                // 如果下一个token是分号，消耗它（可选的分号分隔符）
-                                while let Some(Token::Semicolon) = self.current() {
-                                    self.advance();
-                                }
-                            }
-                        }
-                        
-                        // 期望右花括号结束循环体
-                        self.consume(Token::RBrace)?;
-                        statements
-                    },
-                    _ => return Err("期望'{'开始循环体".to_string()),
+                // 解析循环体
+                let body = self.parse_block("循环体")?;
+                
+                Ok(StatementKind::While(Box::new(condition), body))
+            },
+            // 解析函数定义：fn name(a: int, b: string) -> int { statements } 或冒号缩进风格
+            Some(Token::Fn) => {
+                // 消耗fn关键字
+                self.advance();
+                
+                // 期望标识符作为函数名
+                let name = if let Some(Token::Identifier(id)) = self.current() {
+                    let name = id.clone();
+                    self.advance();
+                    name
+                } else {
+                    let (found, span) = self.found();
+                    return Err(ParseError::ExpectedIdentifier { what: "函数名", found, span });
                 };
                 
-                Ok(Statement::While(Box::new(condition), body))
-            },
-            // 无法识别的语句类型
-            Some(token) => {
-                // 如果是then或end关键字，提示这些关键字不再使用
-                match token {
-                    Token::Then => return Err("语法错误: 'then'关键字不再使用。请使用花括号{}或缩进风格代替。".to_string()),
-                    Token::End => return Err("语法错误: 'end'关键字不再使用。请使用花括号{}或缩进风格代替。".to_string()),
-                    _ => return Err(format!("无法识别的语句: {:?}", token))
+                // 解析参数列表：每个参数都是 name: type
+                self.consume(Token::LParen)?;
+                let mut params = Vec::new();
+                while let Some(Token::Identifier(id)) = self.current() {
+                    let param_name = id.clone();
+                    let param_span = self.current_span();
+                    self.advance();
+                    self.consume(Token::Colon)?;
+                    let data_type = self.parse_data_type()?;
+                    params.push(Param {
+                        name: param_name,
+                        data_type,
+                        span: param_span.to(self.previous_span()),
+                    });
+                    
+                    // 参数之间用逗号分隔
+                    if let Some(Token::Comma) = self.current() {
+                        self.advance();
+                    } else {
+                        break;
+                    }
                 }
+                self.consume(Token::RParen)?;
+                
+                // 解析可选的返回类型：-> type
+                let return_type = if let Some(Token::Arrow) = self.current() {
+                    self.advance();
+                    Some(self.parse_data_type()?)
+                } else {
+                    None
+                };
+                
+                // 解析函数体
+                let body = self.parse_block("函数体")?;
+                
+                Ok(StatementKind::Function(name, params, return_type, body))
+            },
+            // 解析返回语句：return [expression]
+            Some(Token::Return) => {
+                // 消耗return关键字
+                self.advance();
+                
+                // 语句在行尾或代码块结尾结束时没有返回值
+                let value = match self.current() {
+                    None | Some(Token::Newline | Token::Semicolon | Token::RBrace | Token::Dedent) => None,
+                    _ => Some(Box::new(self.parse_expression()?)),
+                };
+                
+                Ok(StatementKind::Return(value))
+            },
+            // 解析循环控制语句：break和continue（是否在循环体内由类型检查报告）
+            Some(Token::Break) => {
+                self.advance();
+                Ok(StatementKind::Break)
             },
-            // 已到达文件末尾
-            None => {
-                Err("期望语句，但已到达文件末尾".to_string())
+            Some(Token::Continue) => {
+                self.advance();
+                Ok(StatementKind::Continue)
+            },
+            // 如果是then或end关键字，提示这些关键字不再使用
+            Some(Token::Then) => Err(ParseError::RemovedKeyword { keyword: "then", span: self.current_span() }),
+            Some(Token::End) => Err(ParseError::RemovedKeyword { keyword: "end", span: self.current_span() }),
+            // 无法识别的语句类型或已到达文件末尾
+            _ => {
+                let (found, span) = self.found();
+                Err(ParseError::UnexpectedStatement { found, span })
             }
         }
     }
     
+    // 解析if语句的条件、then分支和可选的else分支（if或elif关键字已被消耗）
+    // else if和elif被解析为else分支中嵌套的if语句
+    fn parse_if(&mut self) -> Result<StatementKind, ParseError> {
+        // 解析条件表达式
+        let condition = self.parse_expression()?;
+        
+        // 解析then分支：花括号代码块或冒号缩进代码块
+        let then_branch = self.parse_block("then分支")?;
+        
+        // 解析可选的elif或else分支（可以出现在下一行）
+        let else_branch = if self.skip_newlines_before(&Token::Elif) {
+            Some(vec![self.parse_else_if()?])
+        } else if self.skip_newlines_before(&Token::Else) {
+            // 消耗else关键字
+            self.advance();
+            if let Some(Token::If) = self.current() {
+                Some(vec![self.parse_else_if()?])
+            } else {
+                Some(self.parse_block("else分支")?)
+            }
+        } else {
+            None
+        };
+        
+        Ok(StatementKind::If(Box::new(condition), then_branch, else_branch))
+    }
+    
+    // 解析else if或elif开始的嵌套if语句，当前token是if或elif
+    fn parse_else_if(&mut self) -> Result<Statement, ParseError> {
+        let start = self.current_span();
+        self.advance();
+        let kind = self.parse_if()?;
+        Ok(Statement::new(kind, start.to(self.previous_span())))
+    }
+    
+    // 如果跳过若干换行后是指定的token，则消耗这些换行并返回true
+    // 否则保持位置不变并返回false（用于支持换到下一行的else）
+    fn skip_newlines_before(&mut self, expected: &Token) -> bool {
+        let mut lookahead = self.position;
+        while let Some(Token::Newline) = self.tokens.get(lookahead).map(|t| &t.token) {
+            lookahead += 1;
+        }
+        if self.tokens.get(lookahead).map(|t| &t.token) == Some(expected) {
+            self.position = lookahead;
+            true
+        } else {
+            false
+        }
+    }
+    
+    // 解析代码块：支持花括号代码块和冒号缩进代码块两种写法
+    // context用于错误信息，例如"then分支"、"循环体"
+    fn parse_block(&mut self, context: &str) -> Result<Vec<Statement>, ParseError> {
+        match self.current() {
+            Some(Token::LBrace) => self.parse_brace_block(),
+            Some(Token::Colon) => self.parse_indented_block(),
+            _ => {
+                let (found, span) = self.found();
+                Err(ParseError::ExpectedBlock { context: context.to_string(), found, span })
+            },
+        }
+    }
+    
+    // 解析花括号代码块：{ statements }
+    // 花括号内的缩进只是排版，Indent/Dedent和换行一样被跳过
+    fn parse_brace_block(&mut self) -> Result<Vec<Statement>, ParseError> {
+        // 消耗左花括号
+        self.consume(Token::LBrace)?;
+        
+        let mut statements = Vec::new();
+        loop {
+            // 跳过换行符、分号和缩进变化
+            while let Some(Token::Newline | Token::Semicolon | Token::Indent | Token::Dedent) = self.current() {
+                self.advance();
+            }
+            
+            // 遇到右花括号或文件末尾时结束
+            match self.current() {
+                Some(Token::RBrace) | None => break,
+                _ => self.parse_statement_into(&mut statements),
+            }
+        }
+        
+        // 期望右花括号结束代码块
+        self.consume(Token::RBrace)?;
+        Ok(statements)
+    }
+    
+    // 解析冒号缩进代码块
+    // 冒号后换行：代码块由Indent开始、Dedent结束
+    // 冒号后同一行：代码块为该行剩余的语句（可用分号分隔）
+    fn parse_indented_block(&mut self) -> Result<Vec<Statement>, ParseError> {
+        // 消耗冒号
+        self.consume(Token::Colon)?;
+        
+        let mut statements = Vec::new();
+        if let Some(Token::Newline) = self.current() {
+            // 跳过冒号后的换行符
+            while let Some(Token::Newline) = self.current() {
+                self.advance();
+            }
+            
+            // 代码块必须缩进
+            if let Some(Token::Indent) = self.current() {
+                self.advance();
+            } else {
+                let (found, span) = self.found();
+                return Err(ParseError::ExpectedIndent { found, span });
+            }
+            
+            loop {
+                // 跳过换行符和分号
+                while let Some(Token::Newline | Token::Semicolon) = self.current() {
+                    self.advance();
+                }
+                
+                match self.current() {
+                    // 缩进减少，代码块结束
+                    Some(Token::Dedent) => {
+                        self.advance();
+                        break;
+                    },
+                    // 缩进代码块中没有可以结束的花括号代码块，多余的'}'报告错误后跳过
+                    Some(Token::RBrace) => {
+                        let error = ParseError::UnmatchedBlockEnd { token: Token::RBrace, span: self.current_span() };
+                        self.errors.push(error);
+                        self.advance();
+                    },
+                    None => break,
+                    _ => self.parse_statement_into(&mut statements),
+                }
+            }
+        } else {
+            // 同一行的代码块：解析到行尾为止
+            loop {
+                self.parse_statement_into(&mut statements);
+                
+                // 消耗语句之间的分号
+                while let Some(Token::Semicolon) = self.current() {
+                    self.advance();
+                }
+                
+                if let None | Some(Token::Newline | Token::Dedent | Token::RBrace | Token::Else | Token::Elif) = self.current() {
+                    break;
+                }
+            }
+        }
+        
+        Ok(statements)
+    }
+    
     // 解析整个程序
-    // 循环解析所有语句直到结束，支持可选的分号分隔
-    fn parse(&mut self) -> Result<Vec<Statement>, String> {
+    // 循环解析所有语句直到结束，支持可选的分号分隔；出错的语句被跳过，错误记录在diagnostics中
+    pub fn parse(&mut self) -> Vec<Statement> {
         let mut statements = Vec::new();
 
         // 循环解析语句，直到所有token都被处理
         while self.position < self.tokens.len() {
-            // 跳过换行符
-            while let Some(Token::Newline) = self.current() {
+            // 跳过换行符和分号（分号是可选的语句分隔符）
+            while let Some(Token::Newline | Token::Semicolon) = self.current() {
                 self.advance();
             }
             
-            // 如果还有token，解析语句
-            if self.position < self.tokens.len() {
-                statements.push(self.parse_statement()?);
-                
-                // 如果下一个token是分号，消耗它（可选的分号分隔符）
-                if let Some(Token::Semicolon) = self.current() {
+            match self.current() {
+                None => break,
+                // 顶层没有可以结束的代码块，多余的'}'报告错误后跳过
+                Some(token @ (Token::RBrace | Token::Dedent)) => {
+                    let error = ParseError::UnmatchedBlockEnd { token: token.clone(), span: self.current_span() };
+                    self.errors.push(error);
                     self.advance();
-                }
+                },
+                _ => self.parse_statement_into(&mut statements),
             }
         }
 
-        Ok(statements)
+        statements
     }
 }
 
 // 公开的解析函数
-// 接收词法单元序列，返回解析后的语句列表或错误信息
-pub fn parse(tokens: &Vec<Token>) -> Result<Vec<Statement>, String> {
+// 接收带位置信息的词法单元序列，返回解析后的语句列表或所有语法错误
+pub fn parse(tokens: &[SpannedToken]) -> Result<Vec<Statement>, Vec<ParseError>> {
     // 创建解析器实例，克隆tokens以避免所有权问题
-    let mut parser = Parser::new(tokens.clone());
+    let mut parser = Parser::new(tokens.to_vec());
     // 执行解析
-    parser.parse()
-}
\ No newline at end of file
+    let statements = parser.parse();
+    debug!(target: "parser", "语法分析完成，共 {} 条顶层语句，{} 个错误", statements.len(), parser.errors.len());
+    trace!(target: "parser", "抽象语法树: {:#?}", statements);
+    if parser.errors.is_empty() {
+        Ok(statements)
+    } else {
+        Err(parser.errors)
+    }
+}
+
+// 对源代码进行词法分析和语法分析，一次返回两个阶段的所有诊断
+// 有词法错误时仍然用已识别的token继续语法分析，诊断按源代码位置排序
+pub fn parse_source(source: &str) -> Result<Vec<Statement>, Vec<Diagnostic>> {
+    let (tokens, lex_errors) = tokenize_with_recovery(source);
+    let mut diagnostics: Vec<Diagnostic> = lex_errors.into_iter().map(Diagnostic::from).collect();
+    match parse(&tokens) {
+        Ok(statements) if diagnostics.is_empty() => Ok(statements),
+        Ok(_) => Err(diagnostics),
+        Err(parse_errors) => {
+            diagnostics.extend(parse_errors.into_iter().map(Diagnostic::from));
+            diagnostics.sort_by_key(|d| d.span.start);
+            Err(diagnostics)
+        },
+    }
+}
+
+// 把源代码解析为单个表达式，表达式后面只能有换行
+// 用于交互式环境中求值以数字、括号等开头的表达式和查询表达式的类型
+pub fn parse_expression_source(source: &str) -> Result<Expr, Vec<Diagnostic>> {
+    let (tokens, lex_errors) = tokenize_with_recovery(source);
+    if !lex_errors.is_empty() {
+        return Err(lex_errors.into_iter().map(Diagnostic::from).collect());
+    }
+    let mut parser = Parser::new(tokens);
+    let result = parser.parse_expression().and_then(|expr| {
+        while let Some(Token::Newline) = parser.current() {
+            parser.advance();
+        }
+        match parser.found() {
+            (None, _) => Ok(expr),
+            (found, span) => Err(ParseError::ExpectedToken { expected: Token::Newline, found, span }),
+        }
+    });
+    result.map_err(|error| vec![Diagnostic::from(error)])
+}
+
+// 测试模块：语法分析器单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::tokenize;
+    
+    // 辅助函数：词法分析并解析源代码
+    fn parse_source(source: &str) -> Vec<Statement> {
+        let tokens = tokenize(source).unwrap();
+        parse(&tokens).unwrap()
+    }
+    
+    // 测试冒号加缩进的while循环
+    #[test]
+    fn test_indented_while() {
+        let ast = parse_source("var i = 1\nwhile i < 5:\n    println(i)\n    i += 1\nprint i");
+        assert_eq!(ast.len(), 3);
+        match &ast[1].kind {
+            StatementKind::While(_, body) => assert_eq!(body.len(), 2),
+            other => panic!("期望while语句，得到 {:?}", other),
+        }
+    }
+    
+    // 测试同一行的冒号代码块
+    #[test]
+    fn test_same_line_colon_block() {
+        let ast = parse_source("var i = 1; while i < 5: println(i); i += 1;");
+        assert_eq!(ast.len(), 2);
+        match &ast[1].kind {
+            StatementKind::While(_, body) => assert_eq!(body.len(), 2),
+            other => panic!("期望while语句，得到 {:?}", other),
+        }
+    }
+    
+    // 测试嵌套的缩进代码块以及if/else
+    #[test]
+    fn test_nested_indented_if_else() {
+        let source = "for i in (1, 3):\n    if i < 2:\n        print i\n    else:\n        print 0\nprint 9\n";
+        let ast = parse_source(source);
+        assert_eq!(ast.len(), 2);
+        match &ast[0].kind {
+            StatementKind::For(_, _, body) => match &body[0].kind {
+                StatementKind::If(_, then_branch, Some(else_branch)) => {
+                    assert_eq!(then_branch.len(), 1);
+                    assert_eq!(else_branch.len(), 1);
+                },
+                other => panic!("期望if/else语句，得到 {:?}", other),
+            },
+            other => panic!("期望for语句，得到 {:?}", other),
+        }
+    }
+    
+    // 测试花括号代码块内部的缩进不影响解析
+    #[test]
+    fn test_brace_block_with_indentation() {
+        let ast = parse_source("if true {\n    print 1\n    print 2\n} else {\n    print 3\n}");
+        match &ast[0].kind {
+            StatementKind::If(_, then_branch, Some(else_branch)) => {
+                assert_eq!(then_branch.len(), 2);
+                assert_eq!(else_branch.len(), 1);
+            },
+            other => panic!("期望if/else语句，得到 {:?}", other),
+        }
+        // 缩进代码块中的花括号代码块：'}'与块内语句在同一行时，后面不会多出Dedent
+        let ast = parse_source("while i < 2:\n    i = i + 1\n    if i == 1 {\n        println(\"one\") }\nprintln(i)");
+        assert_eq!(ast.len(), 2);
+        match &ast[0].kind {
+            StatementKind::While(_, body) => assert_eq!(body.len(), 2),
+            other => panic!("期望while语句，得到 {:?}", other),
+        }
+        // 花括号代码块中的冒号缩进代码块
+        let ast = parse_source("if true {\n    while x:\n        x = x - 1\n    print x\n}\nprint 0");
+        assert_eq!(ast.len(), 2);
+    }
+    
+    // 测试函数定义、返回语句和调用表达式
+    #[test]
+    fn test_function_declaration_and_call() {
+        let ast = parse_source("fn sum(a: int, b: int) -> int {\n    return a + b\n}\nprintln(sum(1, 2))\nsum(3, 4)");
+        assert_eq!(ast.len(), 3);
+        match &ast[0].kind {
+            StatementKind::Function(name, params, Some(DataType::Int), body) => {
+                assert_eq!(name, "sum");
+                assert_eq!(params.len(), 2);
+                assert_eq!(params[1].data_type, DataType::Int);
+                assert!(matches!(body[0].kind, StatementKind::Return(Some(_))));
+            },
+            other => panic!("期望函数定义，得到 {:?}", other),
+        }
+        match &ast[2].kind {
+            StatementKind::Expression(expr) => assert!(matches!(&expr.kind, ExprKind::Call(name, args) if name == "sum" && args.len() == 2)),
+            other => panic!("期望调用表达式语句，得到 {:?}", other),
+        }
+    }
+    
+    // 测试缺少缩进时报告带位置的错误
+    #[test]
+    fn test_missing_indent_error() {
+        let tokens = tokenize("while true:\nprint 1").unwrap();
+        let errors = parse(&tokens).unwrap_err();
+        assert!(matches!(&errors[0], ParseError::ExpectedIndent { found: Some(Token::Print), span } if span.line == 2));
+        assert!(errors[0].message().contains("期望缩进的代码块"), "{}", errors[0]);
+    }
+
+    // 测试错误种类带有期望和实际的词法单元，并转换为带错误代码的诊断
+    #[test]
+    fn test_error_kinds_and_codes() {
+        let errors = parse(&tokenize("fn f(a: int:\n    return a").unwrap()).unwrap_err();
+        assert_eq!(
+            errors[0],
+            ParseError::ExpectedToken { expected: Token::RParen, found: Some(Token::Colon), span: Span::new(1, 12, 11, 12) }
+        );
+        let diagnostics = super::parse_source("if x then:\n    print x\nthen").unwrap_err();
+        let last = diagnostics.last().unwrap();
+        assert_eq!(last.code, Some("E0109"));
+        assert_eq!(last.details.help.as_deref(), Some("请使用花括号{}或缩进风格代替"));
+        assert_eq!(diagnostics[0].to_string(), "第1行第6列: 错误[E0105]: 期望 '{' 或 ':' 开始then分支，实际找到: Then");
+    }
+    
+    // 测试错误恢复：一次报告多个语法错误和词法错误
+    #[test]
+    fn test_error_recovery_reports_all_errors() {
+        let source = "var a = \nvar b = 2\nif b > :\n    print b\n    var c = )\nb = * 2\nvar d = @\nprint d\n}\n";
+        let errors = super::parse_source(source).unwrap_err();
+        let lines: Vec<usize> = errors.iter().map(|d| d.span.line).collect();
+        assert_eq!(lines, vec![1, 3, 6, 7, 7, 9], "{:#?}", errors);
+        assert!(errors[3].message.contains("无法识别的字符"), "{}", errors[3]);
+        // 出错的if头部后面的缩进代码块被整体跳过，其中的错误（第5行）不会产生连锁诊断
+        // 没有错误的源代码正常解析
+        assert_eq!(super::parse_source("var x = 1; print x").unwrap().len(), 2);
+    }
+    
+    // 测试缩进代码块中多余的'}'报告E0110错误，不会使解析陷入死循环
+    #[test]
+    fn test_stray_brace_in_indented_block() {
+        for source in ["while true:\n    }\n", "fn f():\n    }\n    print 1\n"] {
+            let errors = super::parse_source(source).unwrap_err();
+            assert_eq!(errors.len(), 1, "{:#?}", errors);
+            assert_eq!(errors[0].code, Some("E0110"));
+            assert_eq!((errors[0].span.line, errors[0].span.column), (2, 5));
+        }
+    }
+    
+    // 辅助函数：解析表达式并还原为带括号的形式
+    fn show_expression(source: &str) -> String {
+        show(&super::parse_expression_source(source).unwrap())
+    }
+    
+    // 辅助函数：把表达式树显示为带括号的字符串
+    fn show(expr: &Expr) -> String {
+        match &expr.kind {
+            ExprKind::Identifier(name) => name.clone(),
+            ExprKind::Number(n) => n.to_string(),
+            ExprKind::BinaryOp(left, op, right) => format!("({} {:?} {})", show(left), op, show(right)),
+            ExprKind::UnaryOp(op, operand) => format!("({:?} {})", op, show(operand)),
+            other => format!("{:?}", other),
+        }
+    }
+    
+    // 测试逻辑运算符的优先级：or < and < not < 比较 < 算术
+    #[test]
+    fn test_logical_operator_precedence() {
+        let parse = show_expression;
+        assert_eq!(parse("a or b and c"), "(a Or (b And c))");
+        assert_eq!(parse("x > 0 and x < 10"), "((x Gt 0) And (x Lt 10))");
+        assert_eq!(parse("not x == 1 or y"), "((Not (x Eq 1)) Or y)");
+        assert_eq!(parse("!a && b || c != d + 1"), "(((Not a) And b) Or (c Ne (d Add 1)))");
+        assert_eq!(parse("a and not b and c"), "((a And (Not b)) And c)");
+    }
+    
+    // 测试取余、整除和乘方：乘方右结合且优先级高于乘除
+    #[test]
+    fn test_arithmetic_operator_precedence() {
+        let parse = show_expression;
+        assert_eq!(parse("a % b // c * d"), "(((a Mod b) FloorDiv c) Mul d)");
+        assert_eq!(parse("2 ** 3 ** 2"), "(2 Pow (3 Pow 2))");
+        assert_eq!(parse("a * b ** 2 + c % 3"), "((a Mul (b Pow 2)) Add (c Mod 3))");
+        let statements = parse_source("x **= 2");
+        assert!(matches!(&statements[0].kind, StatementKind::Assign(_, expr) if matches!(expr.kind, ExprKind::BinaryOp(_, Operator::Pow, _))));
+    }
+    
+    // 测试以标识符开头的表达式语句按完整的运算符优先级解析整行
+    #[test]
+    fn test_identifier_expression_statements() {
+        let statements = parse_source("var x = 5\nx * 2 + 1\nx ** 2 ** 3\nx and y or z\nx < 3 == y\nx");
+        let shown: Vec<String> = statements[1..6]
+            .iter()
+            .map(|stmt| match &stmt.kind {
+                StatementKind::Expression(expr) => show(expr),
+                other => panic!("应为表达式语句: {:?}", other),
+            })
+            .collect();
+        assert_eq!(shown, ["((x Mul 2) Add 1)", "(x Pow (2 Pow 3))", "((x And y) Or z)", "((x Lt 3) Eq y)", "x"]);
+        let tokens = tokenize("x - 1 += 2").unwrap();
+        assert!(parse(&tokens).is_err());
+    }
+    
+    // 测试else if和elif链被解析为else分支中嵌套的if语句
+    #[test]
+    fn test_else_if_chains() {
+        // 辅助函数：依次取出if链中每一层的条件变量名，以及最后是否有else分支
+        fn chain(statement: &Statement) -> (Vec<String>, bool) {
+            let mut names = Vec::new();
+            let mut current = statement;
+            loop {
+                let StatementKind::If(condition, _, else_branch) = &current.kind else { panic!("期望if语句") };
+                let ExprKind::Identifier(name) = &condition.kind else { panic!("期望标识符条件") };
+                names.push(name.clone());
+                match else_branch.as_deref() {
+                    Some([nested @ Statement { kind: StatementKind::If(..), .. }]) => current = nested,
+                    else_branch => return (names, else_branch.is_some()),
+                }
+            }
+        }
+        let indented = parse_source("if a:\n    print 1\nelif b:\n    print 2\nelif c:\n    print 3\nelse:\n    print 4\nprint 5");
+        assert_eq!(indented.len(), 2);
+        assert_eq!(chain(&indented[0]), (vec!["a".to_string(), "b".to_string(), "c".to_string()], true));
+        let braces = parse_source("if a { print 1 } else if b { print 2 }\nelse if c {\n    print 3\n}");
+        assert_eq!(braces.len(), 1);
+        assert_eq!(chain(&braces[0]), (vec!["a".to_string(), "b".to_string(), "c".to_string()], false));
+        let same_line = parse_source("if a: print 1\nelif b: print 2\nelse: print 3");
+        assert_eq!(chain(&same_line[0]), (vec!["a".to_string(), "b".to_string()], true));
+    }
+    
+    // 测试break和continue语句
+    #[test]
+    fn test_break_continue_statements() {
+        let statements = parse_source("while true:\n    if x: break\n    continue");
+        let StatementKind::While(_, body) = &statements[0].kind else { panic!("期望while语句") };
+        assert!(matches!(&body[0].kind, StatementKind::If(_, then_branch, None) if then_branch[0].kind == StatementKind::Break));
+        assert_eq!(body[1].kind, StatementKind::Continue);
+    }
+    
+    // 测试一元负号：优先级高于乘除、低于乘方
+    #[test]
+    fn test_unary_minus_precedence() {
+        let parse = show_expression;
+        assert_eq!(parse("-x * 2"), "((Sub x) Mul 2)");
+        assert_eq!(parse("-(a + b)"), "(Sub (a Add b))");
+        assert_eq!(parse("-2 ** 2"), "(Sub (2 Pow 2))");
+        assert_eq!(parse("2 ** -x"), "(2 Pow (Sub x))");
+        assert_eq!(parse("a - -b"), "(a Sub (Sub b))");
+        assert_eq!(parse("not -x > 0"), "(Not ((Sub x) Gt 0))");
+        // 以负号开头的语句和print的参数
+        let statements = parse_source("-x * 2\nprint -x");
+        assert!(matches!(&statements[1].kind, StatementKind::Print(exprs) if matches!(exprs[0].kind, ExprKind::UnaryOp(Operator::Sub, _))));
+    }
+}
@@ -0,0 +1,262 @@
+// 交互式环境（REPL）的求值引擎：逐次执行用户输入的Nexa代码
+// 变量和函数在多次输入之间保留；检查新输入时，之前定义的变量和函数作为前置声明，
+// 因此名称解析和类型检查能看到它们。顶层表达式的值会回显出来
+use crate::diagnostic::Diagnostic;
+use crate::interp::{self, Interpreter, Value};
+use crate::lexer::{self, Span, SpannedToken};
+use crate::parser::{self, DataType, Expr, ExprKind, Statement, StatementKind};
+use crate::session::{CompileError, Stage};
+use crate::typeck::{self, Type};
+use crate::{codegen, resolve};
+use std::collections::HashSet;
+use std::io::Write;
+
+// 把词法和语法诊断包装为编译错误
+fn syntax_error(diagnostics: Vec<Diagnostic>) -> CompileError {
+    CompileError::Diagnostics { stage: Stage::Parse, diagnostics }
+}
+
+// 把检查阶段的诊断包装为编译错误
+fn check_error(diagnostics: Vec<Diagnostic>) -> CompileError {
+    CompileError::Diagnostics { stage: Stage::Check, diagnostics }
+}
+
+// 把变量的当前值转换为带类型注解的变量声明，作为检查新输入时的前置声明
+fn declaration(name: &str, value: &Value) -> Option<Statement> {
+    let (data_type, kind) = match value {
+        Value::Int(n) => (DataType::Int, ExprKind::Number(*n)),
+        Value::Float(x) => (DataType::Float, ExprKind::Float(*x)),
+        Value::Bool(b) => (DataType::Bool, ExprKind::BoolLiteral(*b)),
+        Value::Str(s) => (DataType::String, ExprKind::String(s.clone())),
+        Value::Unit => return None,
+    };
+    let expr = Expr::new(kind, Span::default());
+    Some(Statement::new(StatementKind::Var(name.to_string(), Some(data_type), Box::new(expr)), Span::default()))
+}
+
+// 输入在顶层声明的变量名和函数名，这些名称不再使用之前的定义
+fn declared_names(statements: &[Statement]) -> HashSet<&str> {
+    statements
+        .iter()
+        .filter_map(|stmt| match &stmt.kind {
+            StatementKind::Var(name, _, _) | StatementKind::Function(name, _, _, _) => Some(name.as_str()),
+            _ => None,
+        })
+        .collect()
+}
+
+// 判断输入是否还没有结束，需要继续读取下一行
+// 有未闭合的括号或花括号时继续；以冒号结尾的行开始了缩进代码块，直到输入一个空行为止
+pub fn is_incomplete(input: &str) -> bool {
+    let mut depth = 0i32;
+    let mut in_string = false;
+    let mut opens_block = false;
+    for line in input.lines() {
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' => in_string = !in_string,
+                // 字符串中的转义字符（如\"）不会结束字符串
+                '\\' if in_string => {
+                    chars.next();
+                },
+                '{' | '(' if !in_string => depth += 1,
+                '}' | ')' if !in_string => depth -= 1,
+                _ => {},
+            }
+        }
+        // 字符串不能跨行
+        in_string = false;
+        if line.trim_end().ends_with(':') {
+            opens_block = true;
+        }
+    }
+    if depth > 0 {
+        return true;
+    }
+    // 逐行读取的输入以换行拼接，以换行结尾说明最后输入的是空行
+    let ends_with_blank_line = input.ends_with('\n') || input.lines().last().is_some_and(|line| line.trim().is_empty());
+    opens_block && !ends_with_blank_line
+}
+
+// 交互式环境的状态：持久的解释器和已经定义的函数
+pub struct Repl<W: Write> {
+    // 执行输入的解释器，变量和函数保存在其中
+    interpreter: Interpreter<W>,
+    // 已经定义的函数，检查新输入时作为前置声明
+    functions: Vec<Statement>,
+}
+
+impl<W: Write + Send> Repl<W> {
+    // 创建交互式环境，程序输出和回显的值写入out
+    pub fn new(out: W) -> Self {
+        Repl {
+            interpreter: Interpreter::new(out),
+            functions: Vec::new(),
+        }
+    }
+
+    // 清空所有变量和函数
+    pub fn reset(&mut self) {
+        self.interpreter.reset();
+        self.functions.clear();
+    }
+
+    // 之前定义的、没有被statements重新声明的变量和函数
+    fn prelude(&self, statements: &[Statement]) -> Vec<Statement> {
+        let redeclared = declared_names(statements);
+        let mut globals: Vec<_> = self.interpreter.globals().collect();
+        globals.sort_by(|a, b| a.0.cmp(b.0));
+        let variables = globals
+            .into_iter()
+            .filter(|(name, _)| !redeclared.contains(name.as_str()))
+            .filter_map(|(name, value)| declaration(name, value));
+        let functions = self.functions.iter().filter(|stmt| match &stmt.kind {
+            StatementKind::Function(name, ..) => !redeclared.contains(name.as_str()),
+            _ => false,
+        });
+        variables.chain(functions.cloned()).collect()
+    }
+
+    // 词法分析
+    pub fn tokens(&self, source: &str) -> Result<Vec<SpannedToken>, CompileError> {
+        lexer::tokenize(source).map_err(|errors| syntax_error(errors.into_iter().map(Diagnostic::from).collect()))
+    }
+
+    // 语法分析：先按语句解析，失败时再尝试把整个输入解析为一个表达式（如 1 + 2）
+    pub fn parse(&self, source: &str) -> Result<Vec<Statement>, CompileError> {
+        match parser::parse_source(source) {
+            Ok(statements) => Ok(statements),
+            Err(diagnostics) => match parser::parse_expression_source(source) {
+                Ok(expr) => {
+                    let span = expr.span;
+                    Ok(vec![Statement::new(StatementKind::Expression(Box::new(expr)), span)])
+                },
+                Err(_) => Err(syntax_error(diagnostics)),
+            },
+        }
+    }
+
+    // 解析并检查输入，返回前置声明加上输入的完整程序，以及输入部分的起始下标
+    fn check(&self, source: &str) -> Result<(Vec<Statement>, usize), CompileError> {
+        let statements = self.parse(source)?;
+        let mut program = self.prelude(&statements);
+        let start = program.len();
+        program.extend(statements);
+        resolve::resolve(&program).and_then(|_| typeck::check(&program)).map_err(check_error)?;
+        Ok((program, start))
+    }
+
+    // 生成输入对应的Rust程序，之前定义的变量和函数一起生成
+    pub fn rust(&self, source: &str) -> Result<String, CompileError> {
+        let (program, _) = self.check(source)?;
+        codegen::generate_code(&program).map_err(CompileError::Codegen)
+    }
+
+    // 推断表达式在当前环境中的类型
+    pub fn type_of(&self, source: &str) -> Result<Type, CompileError> {
+        let expr = parser::parse_expression_source(source).map_err(syntax_error)?;
+        let mut program = self.prelude(&[]);
+        let prelude_len = program.len();
+        program.push(Statement::new(StatementKind::Expression(Box::new(expr.clone())), expr.span));
+        resolve::resolve(&program).map_err(check_error)?;
+        typeck::infer_type(&program[..prelude_len], &expr).map_err(check_error)
+    }
+
+    // 执行输入：检查通过后在持久的解释器中执行，顶层表达式的值（非空值）回显出来
+    pub fn eval(&mut self, source: &str) -> Result<(), CompileError> {
+        let (program, start) = self.check(source)?;
+        let statements = &program[start..];
+
+        // 先登记输入中的函数，调用可以出现在定义之前
+        for stmt in statements {
+            if let StatementKind::Function(name, ..) = &stmt.kind {
+                self.functions.retain(|f| !matches!(&f.kind, StatementKind::Function(n, ..) if n == name));
+                self.functions.push(stmt.clone());
+            }
+        }
+        interp::with_call_stack(|| self.execute(statements))
+    }
+
+    // 在解释器中执行检查过的输入
+    fn execute(&mut self, statements: &[Statement]) -> Result<(), CompileError> {
+        self.interpreter.execute(&self.functions).map_err(CompileError::Runtime)?;
+        for stmt in statements {
+            match &stmt.kind {
+                StatementKind::Expression(expr) => {
+                    let value = self.interpreter.evaluate(expr).map_err(CompileError::Runtime)?;
+                    let echo = match value {
+                        Value::Unit => continue,
+                        Value::Str(s) => format!("{:?}\n", s),
+                        value => format!("{}\n", value),
+                    };
+                    self.interpreter.write_output(&echo).map_err(CompileError::Runtime)?;
+                },
+                _ => self.interpreter.execute(std::slice::from_ref(stmt)).map_err(CompileError::Runtime)?,
+            }
+        }
+        self.interpreter.flush().map_err(CompileError::Runtime)
+    }
+}
+
+// 测试模块：交互式环境单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 辅助函数：依次执行多段输入，返回全部输出
+    fn eval_all(inputs: &[&str]) -> String {
+        let mut out = Vec::new();
+        {
+            let mut repl = Repl::new(&mut out);
+            for input in inputs {
+                repl.eval(input).unwrap_or_else(|e| panic!("{}: {}", input, e));
+            }
+        }
+        String::from_utf8(out).unwrap()
+    }
+
+    // 测试变量和函数在多次输入之间保留，表达式的值被回显
+    #[test]
+    fn test_persistent_state_and_echo() {
+        let output = eval_all(&[
+            "var x = 1",
+            "fn double(n: int) -> int:\n    return n * 2\n",
+            "x = double(x + 1)",
+            "x",
+            "(x + 1) * 2",
+            "var x = \"now a string\"",
+            "x",
+            "println(double(5))",
+            "fn depth(n: int) -> int:\n    if n == 0:\n        return 0\n    return depth(n - 1) + 1\n",
+            "depth(3000)",
+        ]);
+        assert_eq!(output, "4\n10\n\"now a string\"\n10\n3000\n");
+    }
+
+    // 测试检查错误和:type、:reset
+    #[test]
+    fn test_errors_type_and_reset() {
+        let mut repl = Repl::new(Vec::new());
+        repl.eval("var count = 2").unwrap();
+        assert_eq!(repl.type_of("count * 1.5").unwrap(), Type::Float);
+        let err = repl.eval("count = \"x\"").unwrap_err();
+        assert_eq!(err.stage(), Stage::Check);
+        assert!(repl.rust("println(count)").unwrap().contains("let mut count: i64 = 2;"));
+        repl.reset();
+        assert!(repl.eval("println(count)").is_err());
+    }
+
+    // 测试多行输入的判断
+    #[test]
+    fn test_is_incomplete() {
+        assert!(!is_incomplete("var x = 1"));
+        assert!(is_incomplete("if x > 1 {"));
+        assert!(!is_incomplete("if x > 1 {\n    print x\n}"));
+        assert!(is_incomplete("while x < 3:"));
+        assert!(is_incomplete("while x < 3:\n    x = x + 1"));
+        assert!(!is_incomplete("while x < 3:\n    x = x + 1\n"));
+        assert!(!is_incomplete("print \"{ (\""));
+        assert!(!is_incomplete("print \"\\\" {\""));
+    }
+}
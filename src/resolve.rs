@@ -0,0 +1,287 @@
+// 名称解析器：在类型检查之前检查变量和函数名的作用域
+// 跟踪If/For/While代码块和循环变量的作用域，报告未声明的名称、
+// 同一作用域内的重复声明以及在定义之前使用变量，错误信息带有Nexa源代码位置
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Span;
+use crate::parser::{Expr, ExprKind, Statement, StatementKind};
+use std::collections::HashMap;
+
+// 一层作用域
+#[derive(Default)]
+struct Scope {
+    // 已经声明的变量及其声明位置
+    declared: HashMap<String, Span>,
+    // 在本代码块中稍后才声明的变量，用于报告"在定义之前使用"
+    pending: HashMap<String, Span>,
+}
+
+// 名称解析器结构体
+struct Resolver {
+    // 作用域栈：每个代码块对应一层，栈顶为最内层作用域
+    scopes: Vec<Scope>,
+    // 所有函数的定义位置（函数定义被提升，在整个程序中可见）
+    functions: HashMap<String, Span>,
+    // 收集到的所有名称错误
+    diagnostics: Vec<Diagnostic>,
+}
+
+// 生成名称解析错误诊断
+fn resolve_error(span: Span, message: String) -> Diagnostic {
+    Diagnostic::error(message, span)
+}
+
+impl Resolver {
+    // 创建名称解析器
+    fn new() -> Self {
+        Resolver {
+            scopes: Vec::new(),
+            functions: HashMap::new(),
+            diagnostics: Vec::new(),
+        }
+    }
+
+    // 进入新的代码块作用域，预先记录块内直接声明的变量
+    fn push_scope(&mut self, statements: &[Statement]) {
+        let mut scope = Scope::default();
+        for stmt in statements {
+            if let StatementKind::Var(name, _, _) = &stmt.kind {
+                scope.pending.entry(name.clone()).or_insert(stmt.span);
+            }
+        }
+        self.scopes.push(scope);
+    }
+
+    // 在当前作用域中声明变量，同一作用域内不能重复声明
+    fn declare(&mut self, name: &str, span: Span) -> Result<(), Diagnostic> {
+        let scope = self.scopes.last_mut().unwrap();
+        if let Some(previous) = scope.declared.get(name) {
+            return Err(resolve_error(
+                span,
+                format!("变量 {} 在同一作用域内重复声明（第{}行已声明）", name, previous.line),
+            ));
+        }
+        scope.declared.insert(name.to_string(), span);
+        scope.pending.remove(name);
+        Ok(())
+    }
+
+    // 检查变量已经声明
+    // 内层代码块稍后声明的同名变量不影响使用外层已声明的变量（如 var x = x + 1 遮蔽外层的x）
+    fn resolve_variable(&self, name: &str, span: Span) -> Result<(), Diagnostic> {
+        if self.scopes.iter().any(|scope| scope.declared.contains_key(name)) {
+            return Ok(());
+        }
+        match self.scopes.iter().rev().find_map(|scope| scope.pending.get(name)) {
+            Some(definition) => Err(resolve_error(
+                span,
+                format!("变量 {} 在定义之前被使用（第{}行定义）", name, definition.line),
+            )
+            .with_help("把变量声明移到第一次使用之前")),
+            None => Err(resolve_error(span, format!("未声明的变量: {}", name)).with_label("在当前作用域中找不到该变量")),
+        }
+    }
+
+    // 递归收集所有函数定义，同名函数只能定义一次
+    fn collect_functions(&mut self, statements: &[Statement]) {
+        for stmt in statements {
+            match &stmt.kind {
+                StatementKind::Function(name, _, _, body) => {
+                    if let Some(previous) = self.functions.get(name) {
+                        let diagnostic = resolve_error(
+                            stmt.span,
+                            format!("函数 {} 重复定义（第{}行已定义）", name, previous.line),
+                        );
+                        self.diagnostics.push(diagnostic);
+                    } else {
+                        self.functions.insert(name.clone(), stmt.span);
+                    }
+                    self.collect_functions(body);
+                },
+                StatementKind::If(_, then_branch, else_branch) => {
+                    self.collect_functions(then_branch);
+                    if let Some(else_branch) = else_branch {
+                        self.collect_functions(else_branch);
+                    }
+                },
+                StatementKind::For(_, _, body) | StatementKind::While(_, body) => {
+                    self.collect_functions(body);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    // 在新的代码块作用域中解析语句列表
+    // 某条语句出错时记录诊断并继续解析后面的语句
+    fn resolve_block(&mut self, statements: &[Statement]) {
+        self.push_scope(statements);
+        for stmt in statements {
+            if let Err(diagnostic) = self.resolve_statement(stmt) {
+                self.diagnostics.push(diagnostic);
+            }
+        }
+        self.scopes.pop();
+    }
+
+    // 解析单条语句
+    fn resolve_statement(&mut self, stmt: &Statement) -> Result<(), Diagnostic> {
+        match &stmt.kind {
+            StatementKind::Var(name, _, expr) => {
+                // 先解析初始值，初始值中不能引用正在声明的变量
+                // 初始值有错误时仍然声明变量，避免后续使用处的连锁错误
+                let result = self.resolve_expression(expr);
+                self.declare(name, stmt.span)?;
+                result?;
+            },
+            StatementKind::Assign(name, expr) => {
+                self.resolve_variable(name, stmt.span)?;
+                self.resolve_expression(expr)?;
+            },
+            StatementKind::Print(exprs) | StatementKind::Println(exprs) => {
+                exprs.iter().try_for_each(|expr| self.resolve_expression(expr))?;
+            },
+            StatementKind::If(condition, then_branch, else_branch) => {
+                self.resolve_expression(condition)?;
+                self.resolve_block(then_branch);
+                if let Some(else_branch) = else_branch {
+                    self.resolve_block(else_branch);
+                }
+            },
+            StatementKind::While(condition, body) => {
+                self.resolve_expression(condition)?;
+                self.resolve_block(body);
+            },
+            StatementKind::For(var_name, iterable, body) => {
+                // 循环变量位于包住循环体的单独作用域中，循环体可以声明同名变量遮蔽它
+                self.resolve_expression(iterable)?;
+                self.scopes.push(Scope::default());
+                self.declare(var_name, stmt.span)?;
+                self.resolve_block(body);
+                self.scopes.pop();
+            },
+            StatementKind::Expression(expr) => self.resolve_expression(expr)?,
+            StatementKind::Function(name, params, _, body) => {
+                // 函数只能定义在顶层：所有后端都把函数定义提升到整个程序可见，
+                // 而生成的Rust代码中代码块内的函数只在该代码块中可见
+                if self.scopes.len() > 1 {
+                    let diagnostic = resolve_error(stmt.span, format!("函数 {} 只能定义在程序顶层", name))
+                        .with_help("把函数定义移到所有代码块和函数体之外");
+                    self.diagnostics.push(diagnostic);
+                }
+                // 函数体只能访问自己的参数，参数名不能重复
+                let outer_scopes = std::mem::take(&mut self.scopes);
+                self.scopes.push(Scope::default());
+                for param in params {
+                    if let Err(diagnostic) = self.declare(&param.name, param.span) {
+                        self.diagnostics.push(diagnostic);
+                    }
+                }
+                self.resolve_block(body);
+                self.scopes = outer_scopes;
+            },
+            StatementKind::Return(value) => {
+                if let Some(expr) = value {
+                    self.resolve_expression(expr)?;
+                }
+            },
+            StatementKind::Break | StatementKind::Continue => {},
+        }
+        Ok(())
+    }
+
+    // 解析表达式中用到的名称
+    fn resolve_expression(&mut self, expr: &Expr) -> Result<(), Diagnostic> {
+        match &expr.kind {
+            ExprKind::Number(_) | ExprKind::Float(_) | ExprKind::String(_) | ExprKind::BoolLiteral(_) => Ok(()),
+            ExprKind::Identifier(name) => self.resolve_variable(name, expr.span),
+            ExprKind::BinaryOp(left, _, right) | ExprKind::Range(left, right) => {
+                self.resolve_expression(left)?;
+                self.resolve_expression(right)
+            },
+            ExprKind::UnaryOp(_, operand) => self.resolve_expression(operand),
+            ExprKind::Call(name, arguments) => {
+                if !self.functions.contains_key(name) {
+                    return Err(resolve_error(expr.span, format!("未定义的函数: {}", name)).with_label("找不到该函数"));
+                }
+                arguments.iter().try_for_each(|argument| self.resolve_expression(argument))
+            },
+        }
+    }
+}
+
+// 检查程序中的名称和作用域，返回所有名称错误
+pub fn resolve(statements: &[Statement]) -> Result<(), Vec<Diagnostic>> {
+    let mut resolver = Resolver::new();
+    resolver.collect_functions(statements);
+    resolver.resolve_block(statements);
+    if resolver.diagnostics.is_empty() {
+        Ok(())
+    } else {
+        resolver.diagnostics.sort_by_key(|d| d.span.start);
+        Err(resolver.diagnostics)
+    }
+}
+
+// 测试模块：名称解析器单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::format_all;
+    use crate::parser::parse_source;
+
+    // 辅助函数：对源代码进行名称解析，所有诊断格式化为文本
+    fn resolve_source(source: &str) -> Result<(), String> {
+        let ast = parse_source(source).map_err(|d| format_all(&d))?;
+        resolve(&ast).map_err(|d| format_all(&d))
+    }
+
+    // 测试合法的作用域用法：遮蔽、循环变量、提升的函数
+    #[test]
+    fn test_valid_scopes() {
+        let source = "println(twice(2))\nvar x = 1\nif x > 0:\n    var x = 2\n    println(x)\nfor i in (1, 3):\n    var i = i * 2\nfn twice(n: int) -> int:\n    return n * 2\n";
+        assert_eq!(resolve_source(source), Ok(()));
+    }
+
+    // 测试未声明的名称
+    #[test]
+    fn test_undeclared_names() {
+        let err = resolve_source("var a = 1\ny = a").unwrap_err();
+        assert_eq!(err, "第2行第1列: 错误: 未声明的变量: y");
+        let err = resolve_source("if true:\n    var t = 1\nprintln(t)").unwrap_err();
+        assert!(err.starts_with("第3行第9列") && err.contains("未声明的变量: t"), "{}", err);
+        let err = resolve_source("var total = 0\nfn f() -> int:\n    return total").unwrap_err();
+        assert!(err.contains("未声明的变量: total"), "{}", err);
+        let err = resolve_source("g(1)").unwrap_err();
+        assert!(err.contains("未定义的函数: g"), "{}", err);
+    }
+
+    // 测试重复声明和在定义之前使用
+    #[test]
+    fn test_duplicates_and_use_before_definition() {
+        let err = resolve_source("var a = 1\nvar a = 2").unwrap_err();
+        assert!(err.starts_with("第2行") && err.contains("重复声明（第1行已声明）"), "{}", err);
+        let err = resolve_source("println(b)\nvar b = 1").unwrap_err();
+        assert_eq!(err, "第1行第9列: 错误: 变量 b 在定义之前被使用（第2行定义）\n  = 帮助: 把变量声明移到第一次使用之前");
+        let err = resolve_source("fn f(a: int, a: int):\n    print(a)").unwrap_err();
+        assert!(err.contains("变量 a 在同一作用域内重复声明"), "{}", err);
+    }
+
+    // 测试函数只能定义在顶层，代码块和函数体内的函数定义都会报错
+    #[test]
+    fn test_nested_function_rejected() {
+        let err = resolve_source("if true {\n    fn inner(a: int) -> int {\n        return a + 1\n    }\n}\nprintln(inner(1))").unwrap_err();
+        assert!(err.starts_with("第2行第5列") && err.contains("函数 inner 只能定义在程序顶层"), "{}", err);
+        assert!(!err.contains("未定义的函数"), "{}", err);
+        let err = resolve_source("fn outer():\n    fn helper():\n        print 1\n    helper()").unwrap_err();
+        assert!(err.contains("函数 helper 只能定义在程序顶层"), "{}", err);
+    }
+
+    // 测试一次报告所有名称错误
+    #[test]
+    fn test_reports_all_errors() {
+        let ast = parse_source("var a = x\nprintln(a, y)\nz = 1\n").unwrap();
+        let errors = resolve(&ast).unwrap_err();
+        let lines: Vec<usize> = errors.iter().map(|d| d.span.line).collect();
+        assert_eq!(lines, vec![1, 2, 3]);
+    }
+}
@@ -0,0 +1,347 @@
+// 编译会话：把Nexa的编译流程拆成可以单独调用的阶段
+// 词法分析 → 语法分析 → 检查（名称解析和类型检查） → 代码生成 → 执行
+// 其他Rust程序通过Session嵌入编译器，每个阶段都返回带类型的CompileError
+use crate::bytecode::{self, Program};
+use crate::cache::{CompileCache, DEFAULT_MAX_SIZE};
+use crate::codegen::CodegenError;
+use crate::diagnostic::{format_all, Diagnostic};
+use crate::exec::ExecutionLimits;
+use crate::lexer::{self, SpannedToken};
+use crate::parser::{self, Statement};
+use crate::{codegen, exec, interp, resolve, typeck, vm};
+use std::fmt;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+// 执行程序使用的后端
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    // 树遍历解释器
+    #[default]
+    Interpreter,
+    // 字节码虚拟机
+    Vm,
+    // 生成Rust代码，用rustc编译后运行
+    Rustc,
+}
+
+// 编译选项
+#[derive(Debug, Clone)]
+pub struct CompileOptions {
+    // 源文件名，渲染诊断时显示在位置前面
+    pub file_name: String,
+    // 执行阶段使用的后端
+    pub backend: Backend,
+    // 是否在代码生成和执行之前进行名称解析和类型检查
+    pub check: bool,
+    // rustc后端使用的编译缓存目录，None时使用cache::default_dir()
+    pub cache_dir: Option<PathBuf>,
+}
+
+impl Default for CompileOptions {
+    fn default() -> Self {
+        CompileOptions {
+            file_name: "<input>".to_string(),
+            backend: Backend::default(),
+            check: true,
+            cache_dir: None,
+        }
+    }
+}
+
+// 编译阶段
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Tokenize,
+    Parse,
+    Check,
+    Generate,
+    Execute,
+}
+
+impl fmt::Display for Stage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Stage::Tokenize => "词法分析",
+            Stage::Parse => "语法分析",
+            Stage::Check => "检查",
+            Stage::Generate => "代码生成",
+            Stage::Execute => "执行",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+// 编译和执行过程中的错误
+#[derive(Debug)]
+pub enum CompileError {
+    // 词法分析、语法分析或检查阶段报告的所有诊断
+    Diagnostics { stage: Stage, diagnostics: Vec<Diagnostic> },
+    // 生成Rust代码失败
+    Codegen(CodegenError),
+    // 编译字节码失败
+    Bytecode(String),
+    // 解释器或虚拟机运行时错误
+    Runtime(String),
+    // rustc编译或运行生成的程序失败
+    Rustc(String),
+}
+
+impl CompileError {
+    // 出错的编译阶段
+    pub fn stage(&self) -> Stage {
+        match self {
+            CompileError::Diagnostics { stage, .. } => *stage,
+            CompileError::Codegen(_) | CompileError::Bytecode(_) => Stage::Generate,
+            CompileError::Runtime(_) | CompileError::Rustc(_) => Stage::Execute,
+        }
+    }
+
+    // 错误附带的诊断，非诊断类错误返回空切片
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        match self {
+            CompileError::Diagnostics { diagnostics, .. } => diagnostics,
+            _ => &[],
+        }
+    }
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileError::Diagnostics { diagnostics, .. } => write!(f, "{}", format_all(diagnostics)),
+            CompileError::Codegen(error) => write!(f, "{}", error),
+            CompileError::Bytecode(message) | CompileError::Runtime(message) | CompileError::Rustc(message) => {
+                write!(f, "{}", message)
+            },
+        }
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+// 编译会话：持有一份源代码和编译选项
+// 每个阶段方法都会先运行它之前的阶段
+#[derive(Debug, Clone)]
+pub struct Session {
+    source: String,
+    options: CompileOptions,
+}
+
+impl Session {
+    // 使用默认选项创建会话
+    pub fn new(source: impl Into<String>) -> Self {
+        Self::with_options(source, CompileOptions::default())
+    }
+
+    // 使用指定选项创建会话
+    pub fn with_options(source: impl Into<String>, options: CompileOptions) -> Self {
+        Session {
+            source: source.into(),
+            options,
+        }
+    }
+
+    // 会话的源代码
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    // 会话的编译选项
+    pub fn options(&self) -> &CompileOptions {
+        &self.options
+    }
+
+    // 词法分析：把源代码转换为带位置信息的词法单元序列
+    pub fn tokenize(&self) -> Result<Vec<SpannedToken>, CompileError> {
+        lexer::tokenize(&self.source).map_err(|errors| CompileError::Diagnostics {
+            stage: Stage::Tokenize,
+            diagnostics: errors.into_iter().map(Diagnostic::from).collect(),
+        })
+    }
+
+    // 语法分析：生成抽象语法树，词法错误和语法错误一起报告
+    pub fn parse(&self) -> Result<Vec<Statement>, CompileError> {
+        parser::parse_source(&self.source).map_err(|diagnostics| CompileError::Diagnostics {
+            stage: Stage::Parse,
+            diagnostics,
+        })
+    }
+
+    // 检查：名称解析通过后再进行类型检查，返回检查过的抽象语法树
+    pub fn check(&self) -> Result<Vec<Statement>, CompileError> {
+        let ast = self.parse()?;
+        if self.options.check {
+            resolve::resolve(&ast)
+                .and_then(|_| typeck::check(&ast))
+                .map_err(|diagnostics| CompileError::Diagnostics {
+                    stage: Stage::Check,
+                    diagnostics,
+                })?;
+        }
+        Ok(ast)
+    }
+
+    // 代码生成：把程序转换为Rust源代码
+    pub fn generate(&self) -> Result<String, CompileError> {
+        let ast = self.check()?;
+        codegen::generate_code(&ast).map_err(CompileError::Codegen)
+    }
+
+    // 构建：生成Rust代码并用rustc编译为可执行文件output
+    pub fn build(&self, output: &Path) -> Result<(), CompileError> {
+        let code = self.generate()?;
+        exec::compile_rust_code(&code, output, &self.compile_cache()).map_err(CompileError::Rustc)
+    }
+
+    // 选项指定的编译缓存
+    fn compile_cache(&self) -> CompileCache {
+        match &self.options.cache_dir {
+            Some(dir) => CompileCache::new(dir, DEFAULT_MAX_SIZE),
+            None => CompileCache::default(),
+        }
+    }
+
+    // 把程序编译为字节码
+    pub fn compile_bytecode(&self) -> Result<Program, CompileError> {
+        let ast = self.check()?;
+        bytecode::compile(&ast).map_err(CompileError::Bytecode)
+    }
+
+    // 执行：用选项中的后端运行程序，输出写入out
+    pub fn execute_to<W: Write + Send>(&self, mut out: W) -> Result<(), CompileError> {
+        match self.options.backend {
+            Backend::Interpreter => {
+                let ast = self.check()?;
+                interp::run(&ast, out).map_err(CompileError::Runtime)
+            },
+            Backend::Vm => {
+                let program = self.compile_bytecode()?;
+                vm::run(&program, out).map_err(CompileError::Runtime)
+            },
+            Backend::Rustc => {
+                let code = self.generate()?;
+                let result = exec::execute_rust_code_with(&code, &ExecutionLimits::default(), &self.compile_cache())
+                    .map_err(CompileError::Rustc)?;
+                out.write_all(result.stdout.as_bytes())
+                    .map_err(|e| CompileError::Rustc(format!("写入输出错误: {}", e)))?;
+                if result.success() {
+                    Ok(())
+                } else {
+                    Err(CompileError::Rustc(result.error_message()))
+                }
+            },
+        }
+    }
+
+    // 执行程序并返回它的全部输出
+    pub fn execute(&self) -> Result<String, CompileError> {
+        let mut out = Vec::new();
+        self.execute_to(&mut out)?;
+        Ok(String::from_utf8_lossy(&out).into_owned())
+    }
+
+    // 按rustc的格式渲染错误，诊断会带上源代码片段
+    pub fn render_error(&self, error: &CompileError, color: bool) -> String {
+        match error {
+            CompileError::Diagnostics { diagnostics, .. } => diagnostics
+                .iter()
+                .map(|d| d.render(&self.options.file_name, &self.source, color))
+                .collect::<Vec<_>>()
+                .join("\n"),
+            CompileError::Codegen(codegen_error) => {
+                Diagnostic::from(codegen_error.clone()).render(&self.options.file_name, &self.source, color)
+            },
+            _ => format!("{}\n", error),
+        }
+    }
+}
+
+// 测试模块：编译会话单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::exec::WorkDir;
+
+    // 测试各阶段依次运行以及不同后端的执行结果一致
+    #[test]
+    fn test_stages_and_backends() {
+        let source = "var total = 0\nfor i in (1, 4):\n    total = total + i\nprintln(total)\n";
+        let session = Session::new(source);
+        assert!(session.tokenize().unwrap().len() > 10);
+        assert_eq!(session.check().unwrap().len(), 3);
+        assert!(session.generate().unwrap().contains("fn main()"));
+        assert_eq!(session.execute().unwrap(), "10\n");
+        let options = CompileOptions { backend: Backend::Vm, ..CompileOptions::default() };
+        assert_eq!(Session::with_options(source, options).execute().unwrap(), "10\n");
+    }
+
+    // 辅助函数：用所有后端运行程序，检查输出都等于预期
+    fn assert_parity(source: &str, expected: &str) {
+        // 编译缓存放在临时目录中，不写入用户的缓存目录
+        let dir = WorkDir::new().unwrap();
+        for backend in [Backend::Interpreter, Backend::Vm, Backend::Rustc] {
+            let options = CompileOptions {
+                backend,
+                cache_dir: Some(dir.path.join("cache")),
+                ..CompileOptions::default()
+            };
+            let output = Session::with_options(source, options).execute().unwrap();
+            assert_eq!(output, expected, "{:?}", backend);
+        }
+    }
+
+    // 测试赋值给浮点变量的整数在所有后端中都转换为浮点数
+    #[test]
+    fn test_float_assign_parity() {
+        let source = "var f: float = 1\nf = 2\nprintln(f / 4)\nf += 1\nprintln(f / 4)\nvar g = 1.5\ng = 3\nprintln(g / 2)\nfn half(x: float) -> float:\n    return x / 2\nvar h = half(3)\nh = 1\nprintln(h / 4)\n";
+        assert_parity(source, "0.5\n0.75\n1.5\n0.25\n");
+    }
+
+    // 测试所有后端使用相同的64位整数：超出i32范围的值不会溢出
+    #[test]
+    fn test_int_width_parity() {
+        let source = "var big = 2000000000\nprintln(big + big)\nfn triple(n: int) -> int:\n    return n * 3\nprintln(triple(big) // 7, big * big % 999, 0 - big * 2)\n";
+        assert_parity(source, "4000000000\n857142857\n4\n-4000000000\n");
+    }
+
+    // 测试字符串转义序列在所有后端中输出相同的内容
+    #[test]
+    fn test_string_escape_parity() {
+        let source = "println(\"x\\\\\")\nprintln(\"a\\tb \\\"q\\\" {}\")\nprint \"line\\n\"\nfor c in \"\\\\\\t\":\n    print(c)\n";
+        assert_parity(source, "x\\\na\tb \"q\" {}\nline\n\\\t");
+    }
+
+    // 测试深层递归在所有后端中都能运行，无限递归在解释器和虚拟机中报告相同的错误
+    #[test]
+    fn test_deep_recursion_parity() {
+        let source = "fn depth(n: int) -> int:\n    if n == 0:\n        return 0\n    return depth(n - 1) + 1\nprintln(depth(5000))\n";
+        assert_parity(source, "5000\n");
+        let errors: Vec<String> = [Backend::Interpreter, Backend::Vm]
+            .into_iter()
+            .map(|backend| {
+                let options = CompileOptions { backend, ..CompileOptions::default() };
+                Session::with_options("fn f() -> int:\n    return f()\nf()\n", options).execute().unwrap_err().to_string()
+            })
+            .collect();
+        assert_eq!(errors[0], errors[1]);
+        assert!(errors[0].contains("函数调用嵌套超过 10000 层"), "{}", errors[0]);
+    }
+
+    // 测试错误带有出错阶段
+    #[test]
+    fn test_error_stages() {
+        let err = Session::new("var a = @").tokenize().unwrap_err();
+        assert_eq!(err.stage(), Stage::Tokenize);
+        let err = Session::new("var a = \nprintln(a)").execute().unwrap_err();
+        assert_eq!(err.stage(), Stage::Parse);
+        let err = Session::new("println(y)").generate().unwrap_err();
+        assert_eq!(err.stage(), Stage::Check);
+        assert_eq!(err.diagnostics().len(), 1);
+        let err = Session::new("var z = 0\nprintln(1 / z)").execute().unwrap_err();
+        assert!(matches!(err, CompileError::Runtime(_)), "{}", err);
+        // 关闭检查后直接生成代码
+        let options = CompileOptions { check: false, ..CompileOptions::default() };
+        assert!(Session::with_options("println(y)", options).generate().is_ok());
+    }
+}
@@ -0,0 +1,447 @@
+// 静态类型检查器：在语法分析之后、代码生成之前检查程序的类型
+// 推断每个表达式的类型，检查类型注解、赋值、条件和运算符操作数，
+// 让类型错误以Nexa源代码位置报告，而不是在rustc编译生成的代码时才失败
+use crate::diagnostic::Diagnostic;
+use crate::lexer::Span;
+use crate::parser::{DataType, Expr, ExprKind, Operator, Statement, StatementKind};
+use std::collections::HashMap;
+use std::fmt;
+
+// 类型检查使用的类型：在DataType基础上增加表示"没有值"的Unit
+#[derive(Debug, Clone, PartialEq)]
+pub enum Type {
+    Int,
+    Float,
+    Bool,
+    String,
+    // 没有返回类型的函数调用的结果
+    Unit,
+}
+
+impl From<&DataType> for Type {
+    fn from(data_type: &DataType) -> Self {
+        match data_type {
+            DataType::Int => Type::Int,
+            DataType::Float => Type::Float,
+            DataType::Bool => Type::Bool,
+            DataType::String => Type::String,
+        }
+    }
+}
+
+// 类型名使用Nexa源代码中的写法
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Type::Int => "int",
+            Type::Float => "float",
+            Type::Bool => "bool",
+            Type::String => "string",
+            Type::Unit => "()",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl Type {
+    // 是否是数值类型
+    fn is_numeric(&self) -> bool {
+        matches!(self, Type::Int | Type::Float)
+    }
+
+    // 该类型的值能否赋给target类型：类型相同，或者整数赋给浮点数
+    fn assignable_to(&self, target: &Type) -> bool {
+        self == target || (*self == Type::Int && *target == Type::Float)
+    }
+}
+
+// 函数签名
+struct Signature {
+    params: Vec<Type>,
+    return_type: Type,
+}
+
+// 类型检查器结构体
+struct TypeChecker {
+    // 变量类型作用域栈：每个代码块对应一层
+    scopes: Vec<HashMap<String, Type>>,
+    // 所有函数的签名（函数定义被提升，调用可以出现在定义之前）
+    functions: HashMap<String, Signature>,
+    // 当前所在函数的返回类型，顶层代码为None
+    return_type: Option<Type>,
+    // 当前所在函数内嵌套的循环层数，用于检查break和continue
+    loop_depth: usize,
+}
+
+// 生成类型错误诊断
+fn type_error(span: Span, message: String) -> Diagnostic {
+    Diagnostic::error(message, span)
+}
+
+// 判断语句列表是否在所有执行路径上都以return结束
+fn always_returns(statements: &[Statement]) -> bool {
+    statements.iter().any(|stmt| match &stmt.kind {
+        StatementKind::Return(_) => true,
+        StatementKind::If(_, then_branch, Some(else_branch)) => always_returns(then_branch) && always_returns(else_branch),
+        _ => false,
+    })
+}
+
+impl TypeChecker {
+    // 创建类型检查器
+    fn new() -> Self {
+        TypeChecker {
+            scopes: vec![HashMap::new()],
+            functions: HashMap::new(),
+            return_type: None,
+            loop_depth: 0,
+        }
+    }
+
+    // 在当前作用域中声明变量
+    fn declare(&mut self, name: &str, var_type: Type) {
+        self.scopes.last_mut().unwrap().insert(name.to_string(), var_type);
+    }
+
+    // 查找变量的类型，从最内层作用域开始
+    fn lookup(&self, name: &str) -> Option<&Type> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    // 递归收集所有函数签名（包括嵌套在代码块中的函数）
+    fn collect_functions(&mut self, statements: &[Statement]) {
+        for stmt in statements {
+            match &stmt.kind {
+                StatementKind::Function(name, params, return_type, body) => {
+                    let signature = Signature {
+                        params: params.iter().map(|p| Type::from(&p.data_type)).collect(),
+                        return_type: return_type.as_ref().map(Type::from).unwrap_or(Type::Unit),
+                    };
+                    self.functions.insert(name.clone(), signature);
+                    self.collect_functions(body);
+                },
+                StatementKind::If(_, then_branch, else_branch) => {
+                    self.collect_functions(then_branch);
+                    if let Some(else_branch) = else_branch {
+                        self.collect_functions(else_branch);
+                    }
+                },
+                StatementKind::For(_, _, body) | StatementKind::While(_, body) => {
+                    self.collect_functions(body);
+                },
+                _ => {},
+            }
+        }
+    }
+
+    // 在新的块作用域中检查语句列表
+    fn check_block(&mut self, statements: &[Statement]) -> Result<(), Diagnostic> {
+        self.scopes.push(HashMap::new());
+        let result = statements.iter().try_for_each(|stmt| self.check_statement(stmt));
+        self.scopes.pop();
+        result
+    }
+
+    // 检查循环体，循环体内可以使用break和continue
+    fn check_loop_body(&mut self, body: &[Statement]) -> Result<(), Diagnostic> {
+        self.loop_depth += 1;
+        let result = self.check_block(body);
+        self.loop_depth -= 1;
+        result
+    }
+
+    // 检查表达式的类型是否能赋给期望的类型
+    fn expect_assignable(&mut self, expr: &Expr, expected: &Type, what: &str) -> Result<(), Diagnostic> {
+        let actual = self.infer(expr)?;
+        if actual.assignable_to(expected) {
+            Ok(())
+        } else {
+            Err(type_error(expr.span, format!("{}需要 {} 类型，但得到 {}", what, expected, actual))
+                .with_label(format!("该表达式的类型是 {}", actual)))
+        }
+    }
+
+    // 检查条件表达式必须是bool类型
+    fn expect_condition(&mut self, expr: &Expr) -> Result<(), Diagnostic> {
+        match self.infer(expr)? {
+            Type::Bool => Ok(()),
+            other => Err(type_error(expr.span, format!("条件必须是bool类型，但得到 {}", other))),
+        }
+    }
+
+    // 检查表达式有值（不是没有返回值的函数调用）
+    fn expect_value(&mut self, expr: &Expr) -> Result<Type, Diagnostic> {
+        match self.infer(expr)? {
+            Type::Unit => Err(type_error(expr.span, "该表达式没有值".to_string())),
+            other => Ok(other),
+        }
+    }
+
+    // 检查单条语句
+    fn check_statement(&mut self, stmt: &Statement) -> Result<(), Diagnostic> {
+        match &stmt.kind {
+            StatementKind::Var(name, data_type, expr) => {
+                let var_type = match data_type {
+                    Some(dt) => {
+                        let declared = Type::from(dt);
+                        self.expect_assignable(expr, &declared, &format!("变量 {} ", name))?;
+                        declared
+                    },
+                    None => self.expect_value(expr)?,
+                };
+                self.declare(name, var_type);
+            },
+            StatementKind::Assign(name, expr) => {
+                let var_type = self
+                    .lookup(name)
+                    .cloned()
+                    .ok_or_else(|| type_error(stmt.span, format!("未定义的变量: {}", name)))?;
+                self.expect_assignable(expr, &var_type, &format!("给变量 {} 赋值", name))?;
+            },
+            StatementKind::Print(exprs) | StatementKind::Println(exprs) => {
+                for expr in exprs {
+                    self.expect_value(expr)?;
+                }
+            },
+            StatementKind::If(condition, then_branch, else_branch) => {
+                self.expect_condition(condition)?;
+                self.check_block(then_branch)?;
+                if let Some(else_branch) = else_branch {
+                    self.check_block(else_branch)?;
+                }
+            },
+            StatementKind::While(condition, body) => {
+                self.expect_condition(condition)?;
+                self.check_loop_body(body)?;
+            },
+            StatementKind::For(var_name, iterable, body) => {
+                // 范围循环的循环变量是int，字符串循环的循环变量是单个字符组成的string
+                let var_type = match &iterable.kind {
+                    ExprKind::Range(start, end) => {
+                        self.expect_assignable(start, &Type::Int, "范围起点")?;
+                        self.expect_assignable(end, &Type::Int, "范围终点")?;
+                        Type::Int
+                    },
+                    _ => match self.infer(iterable)? {
+                        Type::String => Type::String,
+                        other => return Err(type_error(iterable.span, format!("无法遍历 {} 类型的值", other))),
+                    },
+                };
+                self.scopes.push(HashMap::new());
+                self.declare(var_name, var_type);
+                let result = self.check_loop_body(body);
+                self.scopes.pop();
+                result?;
+            },
+            StatementKind::Expression(expr) => {
+                self.infer(expr)?;
+            },
+            StatementKind::Function(name, params, return_type, body) => {
+                let declared_return = return_type.as_ref().map(Type::from).unwrap_or(Type::Unit);
+                // 函数体只能访问自己的参数
+                let param_scope = params.iter().map(|p| (p.name.clone(), Type::from(&p.data_type))).collect();
+                let outer_scopes = std::mem::replace(&mut self.scopes, vec![param_scope]);
+                let outer_return = self.return_type.replace(declared_return.clone());
+                // 函数体中的break和continue不能跳出函数外的循环
+                let outer_loop_depth = std::mem::take(&mut self.loop_depth);
+                let result = self.check_block(body);
+                self.loop_depth = outer_loop_depth;
+                self.scopes = outer_scopes;
+                self.return_type = outer_return;
+                result?;
+                if declared_return != Type::Unit && !always_returns(body) {
+                    return Err(type_error(
+                        stmt.span,
+                        format!("函数 {} 声明返回 {}，但并非所有路径都有return语句", name, declared_return),
+                    ));
+                }
+            },
+            StatementKind::Return(value) => {
+                let expected = self
+                    .return_type
+                    .clone()
+                    .ok_or_else(|| type_error(stmt.span, "return语句只能出现在函数体内".to_string()))?;
+                match (value, &expected) {
+                    (None, Type::Unit) => {},
+                    (None, _) => return Err(type_error(stmt.span, format!("函数需要返回 {} 类型的值", expected))),
+                    (Some(expr), Type::Unit) => {
+                        return Err(type_error(expr.span, "没有声明返回类型的函数不能返回值".to_string()));
+                    },
+                    (Some(expr), _) => self.expect_assignable(expr, &expected, "返回值")?,
+                }
+            },
+            StatementKind::Break | StatementKind::Continue if self.loop_depth == 0 => {
+                let keyword = if stmt.kind == StatementKind::Break { "break" } else { "continue" };
+                return Err(type_error(stmt.span, format!("{}语句只能出现在循环体内", keyword)));
+            },
+            StatementKind::Break | StatementKind::Continue => {},
+        }
+        Ok(())
+    }
+
+    // 推断表达式的类型
+    fn infer(&mut self, expr: &Expr) -> Result<Type, Diagnostic> {
+        let span = expr.span;
+        match &expr.kind {
+            ExprKind::Number(_) => Ok(Type::Int),
+            ExprKind::Float(_) => Ok(Type::Float),
+            ExprKind::String(_) => Ok(Type::String),
+            ExprKind::BoolLiteral(_) => Ok(Type::Bool),
+            ExprKind::Identifier(name) => {
+                self.lookup(name).cloned().ok_or_else(|| type_error(span, format!("未定义的变量: {}", name)))
+            },
+            ExprKind::BinaryOp(left, op, right) => {
+                let left_type = self.infer(left)?;
+                let right_type = self.infer(right)?;
+                let operand_error = || {
+                    type_error(span, format!("运算符 {:?} 不能用于 {} 和 {}", op, left_type, right_type))
+                };
+                match op {
+                    // 算术运算：两侧都是数值，有一侧是float时结果为float
+                    Operator::Add | Operator::Sub | Operator::Mul | Operator::Div | Operator::Mod | Operator::FloorDiv | Operator::Pow => {
+                        if !left_type.is_numeric() || !right_type.is_numeric() {
+                            return Err(operand_error());
+                        }
+                        if left_type == Type::Float || right_type == Type::Float {
+                            Ok(Type::Float)
+                        } else {
+                            Ok(Type::Int)
+                        }
+                    },
+                    // 相等和不等比较：类型相同或都是数值
+                    Operator::Eq | Operator::Ne => {
+                        if (left_type == right_type && left_type != Type::Unit) || (left_type.is_numeric() && right_type.is_numeric()) {
+                            Ok(Type::Bool)
+                        } else {
+                            Err(operand_error())
+                        }
+                    },
+                    // 大小比较：都是数值或都是字符串
+                    Operator::Lt | Operator::Le | Operator::Gt | Operator::Ge => {
+                        if (left_type.is_numeric() && right_type.is_numeric())
+                            || (left_type == Type::String && right_type == Type::String)
+                        {
+                            Ok(Type::Bool)
+                        } else {
+                            Err(operand_error())
+                        }
+                    },
+                    // 逻辑运算：两侧都是bool
+                    Operator::And | Operator::Or => {
+                        if left_type == Type::Bool && right_type == Type::Bool {
+                            Ok(Type::Bool)
+                        } else {
+                            Err(operand_error())
+                        }
+                    },
+                    // not只能作为一元运算符
+                    Operator::Not => Err(operand_error()),
+                }
+            },
+            ExprKind::UnaryOp(op, operand) => {
+                let operand_type = self.infer(operand)?;
+                match op {
+                    Operator::Sub if operand_type.is_numeric() => Ok(operand_type),
+                    Operator::Not if operand_type == Type::Bool => Ok(Type::Bool),
+                    _ => Err(type_error(span, format!("一元运算符 {:?} 不能用于 {}", op, operand_type))),
+                }
+            },
+            ExprKind::Range(..) => Err(type_error(span, "范围表达式只能用于for循环".to_string())),
+            ExprKind::Call(name, arguments) => {
+                let signature = self
+                    .functions
+                    .get(name)
+                    .ok_or_else(|| type_error(span, format!("未定义的函数: {}", name)))?;
+                if signature.params.len() != arguments.len() {
+                    return Err(type_error(
+                        span,
+                        format!("函数 {} 需要 {} 个参数，但传入了 {} 个", name, signature.params.len(), arguments.len()),
+                    ));
+                }
+                let params = signature.params.clone();
+                let return_type = signature.return_type.clone();
+                for (i, (argument, param_type)) in arguments.iter().zip(&params).enumerate() {
+                    self.expect_assignable(argument, param_type, &format!("函数 {} 的第{}个参数", name, i + 1))?;
+                }
+                Ok(return_type)
+            },
+        }
+    }
+}
+
+// 检查程序的类型
+// 类型错误会影响后续表达式的类型推断，因此遇到第一个类型错误就停止，避免连锁错误
+pub fn check(statements: &[Statement]) -> Result<(), Vec<Diagnostic>> {
+    let mut checker = TypeChecker::new();
+    checker.collect_functions(statements);
+    statements.iter().try_for_each(|stmt| checker.check_statement(stmt)).map_err(|d| vec![d])
+}
+
+// 在程序执行完之后的顶层环境中推断表达式的类型（用于交互式环境的:type命令）
+pub fn infer_type(statements: &[Statement], expr: &Expr) -> Result<Type, Vec<Diagnostic>> {
+    let mut checker = TypeChecker::new();
+    checker.collect_functions(statements);
+    statements.iter().try_for_each(|stmt| checker.check_statement(stmt)).map_err(|d| vec![d])?;
+    checker.infer(expr).map_err(|d| vec![d])
+}
+
+// 测试模块：类型检查器单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diagnostic::format_all;
+    use crate::parser::parse_source;
+
+    // 辅助函数：对源代码进行类型检查，所有诊断格式化为文本
+    fn check_source(source: &str) -> Result<(), String> {
+        let ast = parse_source(source).map_err(|d| format_all(&d))?;
+        check(&ast).map_err(|d| format_all(&d))
+    }
+
+    // 测试合法程序通过检查
+    #[test]
+    fn test_well_typed_program() {
+        let source = "fn area(r: float) -> float:\n    return r * r * 3\nvar x: float = 2\nvar s = \"ab\"\nfor c in s:\n    print(c)\nfor i in (1, 3):\n    x = x + i\nif x > 1:\n    println(area(x))\n";
+        assert_eq!(check_source(source), Ok(()));
+    }
+
+    // 测试类型注解和赋值的类型错误
+    #[test]
+    fn test_annotation_and_assignment_errors() {
+        let err = check_source("var x: int = \"hi\"").unwrap_err();
+        assert_eq!(err, "第1行第14列: 错误: 变量 x 需要 int 类型，但得到 string");
+        let err = check_source("var x = 1\nx = 2.5").unwrap_err();
+        assert!(err.starts_with("第2行第5列") && err.contains("需要 int 类型，但得到 float"), "{}", err);
+    }
+
+    // 测试条件、运算符操作数和函数调用的类型错误
+    #[test]
+    fn test_condition_operand_and_call_errors() {
+        let err = check_source("while 1:\n    print(1)").unwrap_err();
+        assert!(err.contains("条件必须是bool类型，但得到 int"), "{}", err);
+        let err = check_source("var b = true + 1").unwrap_err();
+        assert!(err.contains("不能用于 bool 和 int"), "{}", err);
+        let err = check_source("var b = 1 > 0 and 2").unwrap_err();
+        assert!(err.contains("不能用于 bool 和 int"), "{}", err);
+        let err = check_source("var b = not 1").unwrap_err();
+        assert!(err.contains("一元运算符 Not 不能用于 int"), "{}", err);
+        let err = check_source("var b = true != 1").unwrap_err();
+        assert!(err.contains("不能用于 bool 和 int"), "{}", err);
+        let err = check_source("fn f(a: int) -> int:\n    return a\nprintln(f(\"x\"))").unwrap_err();
+        assert!(err.starts_with("第3行第11列") && err.contains("第1个参数需要 int 类型"), "{}", err);
+        let err = check_source("fn f(a: int) -> int:\n    if a > 0:\n        return a").unwrap_err();
+        assert!(err.contains("并非所有路径都有return语句"), "{}", err);
+    }
+
+    // 测试break和continue只能出现在循环体内，函数体不能跳出外层的循环
+    #[test]
+    fn test_break_continue_outside_loop() {
+        assert!(check_source("while true:\n    if true:\n        break\n    continue\nfor i in (1, 3):\n    break").is_ok());
+        let err = check_source("var x = 1\nbreak").unwrap_err();
+        assert!(err.starts_with("第2行第1列") && err.contains("break语句只能出现在循环体内"), "{}", err);
+        let err = check_source("if true:\n    continue").unwrap_err();
+        assert!(err.contains("continue语句只能出现在循环体内"), "{}", err);
+        let err = check_source("while true:\n    fn f():\n        break\n    break").unwrap_err();
+        assert!(err.starts_with("第3行第9列") && err.contains("break语句只能出现在循环体内"), "{}", err);
+    }
+}
@@ -0,0 +1,248 @@
+// 栈式虚拟机：执行bytecode模块编译出的字节码程序
+// 局部变量存放在值栈上，每个调用帧记录自己的局部变量起始位置
+use crate::bytecode::{Instruction, Program};
+use crate::interp::{binary_op, call_depth_error, runtime_error, Value, MAX_CALL_DEPTH};
+use std::io::Write;
+
+// 调用帧
+struct Frame {
+    // 正在执行的函数编号
+    function: usize,
+    // 下一条要执行的指令地址
+    ip: usize,
+    // 局部变量在值栈中的起始位置
+    base: usize,
+}
+
+// 虚拟机结构体：保存值栈、调用帧和输出目标
+pub struct Vm<'a, W: Write> {
+    program: &'a Program,
+    // 值栈：局部变量和运算的中间结果
+    stack: Vec<Value>,
+    // 调用帧栈，栈顶为当前执行的函数
+    frames: Vec<Frame>,
+    // 程序输出的目标（标准输出或内存缓冲区）
+    out: W,
+}
+
+impl<'a, W: Write> Vm<'a, W> {
+    // 创建虚拟机，程序输出写入out
+    pub fn new(program: &'a Program, out: W) -> Self {
+        Vm {
+            program,
+            stack: Vec::new(),
+            frames: Vec::new(),
+            out,
+        }
+    }
+
+    // 从顶层代码开始执行程序
+    pub fn run(&mut self) -> Result<(), String> {
+        let program = self.program;
+        self.push_frame(0, 0);
+        loop {
+            let frame = self.frames.last_mut().unwrap();
+            let function = &program.functions[frame.function];
+            let ip = frame.ip;
+            let base = frame.base;
+            frame.ip += 1;
+            let span = function.spans[ip];
+
+            match &function.code[ip] {
+                Instruction::Constant(index) => self.stack.push(program.constants[*index].clone()),
+                Instruction::LoadLocal(slot) => {
+                    let value = self.stack[base + slot].clone();
+                    self.stack.push(value);
+                },
+                Instruction::StoreLocal(slot) => {
+                    let value = self.pop();
+                    self.stack[base + slot] = value;
+                },
+                Instruction::Pop => {
+                    self.pop();
+                },
+                Instruction::Binary(op) => {
+                    let right = self.pop();
+                    let left = self.pop();
+                    let value = binary_op(op, left, right).map_err(|e| runtime_error(span, e))?;
+                    self.stack.push(value);
+                },
+                Instruction::Negate => {
+                    let value = match self.pop() {
+                        Value::Int(n) => n
+                            .checked_neg()
+                            .map(Value::Int)
+                            .ok_or_else(|| runtime_error(span, "整数溢出".to_string()))?,
+                        Value::Float(f) => Value::Float(-f),
+                        other => return Err(runtime_error(span, format!("无法对 {} 类型的值取负", other.type_name()))),
+                    };
+                    self.stack.push(value);
+                },
+                Instruction::Not => match self.pop() {
+                    Value::Bool(b) => self.stack.push(Value::Bool(!b)),
+                    other => return Err(runtime_error(span, format!("无法对 {} 类型的值取反", other.type_name()))),
+                },
+                Instruction::ToFloat => {
+                    if let Some(Value::Int(n)) = self.stack.last() {
+                        let value = Value::Float(*n as f64);
+                        *self.stack.last_mut().unwrap() = value;
+                    }
+                },
+                Instruction::StrLen => match self.pop() {
+                    Value::Str(s) => self.stack.push(Value::Int(s.len() as i64)),
+                    other => return Err(runtime_error(span, format!("无法遍历 {} 类型的值", other.type_name()))),
+                },
+                Instruction::StrChar => {
+                    let index = self.pop();
+                    let string = self.pop();
+                    match (string, index) {
+                        (Value::Str(s), Value::Int(i)) => {
+                            let i = i as usize;
+                            let c = s[i..].chars().next().expect("字节下标由循环保证在范围内");
+                            self.stack.push(Value::Str(c.to_string()));
+                            self.stack.push(Value::Int((i + c.len_utf8()) as i64));
+                        },
+                        (s, i) => {
+                            return Err(runtime_error(span, format!("无法用 {} 索引 {}", i.type_name(), s.type_name())));
+                        },
+                    }
+                },
+                Instruction::Jump(target) => self.frames.last_mut().unwrap().ip = *target,
+                Instruction::JumpIfFalse(target) => match self.pop() {
+                    Value::Bool(true) => {},
+                    Value::Bool(false) => self.frames.last_mut().unwrap().ip = *target,
+                    other => {
+                        return Err(runtime_error(span, format!("条件必须是bool类型，但得到 {}", other.type_name())));
+                    },
+                },
+                Instruction::Print(count) => {
+                    let values = self.stack.split_off(self.stack.len() - count);
+                    let parts: Vec<String> = values.iter().map(|v| v.to_string()).collect();
+                    self.write_output(&parts.join(" "))?;
+                },
+                Instruction::Println => {
+                    let value = self.pop();
+                    self.write_output(&format!("{}\n", value))?;
+                },
+                Instruction::Call(id, argc) => {
+                    let callee = &program.functions[*id];
+                    if callee.arity != *argc {
+                        return Err(runtime_error(
+                            span,
+                            format!("函数 {} 需要 {} 个参数，但传入了 {} 个", callee.name, callee.arity, argc),
+                        ));
+                    }
+                    // 顶层代码占用一个调用帧，函数调用的嵌套深度与解释器的计算方式一致
+                    if self.frames.len() > MAX_CALL_DEPTH {
+                        return Err(call_depth_error(span));
+                    }
+                    let base = self.stack.len() - argc;
+                    self.push_frame(*id, base);
+                },
+                Instruction::Return => {
+                    let value = self.pop();
+                    let frame = self.frames.pop().unwrap();
+                    self.stack.truncate(frame.base);
+                    if self.frames.is_empty() {
+                        return self.out.flush().map_err(|e| format!("写入输出失败: {}", e));
+                    }
+                    self.stack.push(value);
+                },
+            }
+        }
+    }
+
+    // 压入新的调用帧，并为参数以外的局部变量预留槽位
+    fn push_frame(&mut self, function: usize, base: usize) {
+        let chunk = &self.program.functions[function];
+        let extra = chunk.slot_names.len() - chunk.arity;
+        self.stack.extend(std::iter::repeat_n(Value::Unit, extra));
+        self.frames.push(Frame { function, ip: 0, base });
+    }
+
+    // 弹出栈顶值，编译器保证栈不会下溢
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("值栈下溢")
+    }
+
+    // 把文本写入输出
+    fn write_output(&mut self, text: &str) -> Result<(), String> {
+        self.out.write_all(text.as_bytes()).map_err(|e| format!("写入输出失败: {}", e))
+    }
+}
+
+// 执行字节码程序并把输出写入out
+pub fn run<W: Write>(program: &Program, out: W) -> Result<(), String> {
+    Vm::new(program, out).run()
+}
+
+// 测试模块：字节码编译器和虚拟机单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::{compile, disassemble};
+    use crate::diagnostic::format_all;
+    use crate::parser::parse_source;
+
+    // 辅助函数：编译并在虚拟机中运行源代码，返回输出
+    fn run_source(source: &str) -> Result<String, String> {
+        let ast = parse_source(source).map_err(|d| format_all(&d))?;
+        let program = compile(&ast)?;
+        let mut out = Vec::new();
+        run(&program, &mut out)?;
+        Ok(String::from_utf8(out).unwrap())
+    }
+
+    // 测试循环、打印和块作用域
+    #[test]
+    fn test_loops_and_print() {
+        let source = "var total = 0\nfor i in (1, 4):\n    total = total + i\nfor c in \"ab\":\n    println(c)\nvar n = 3\nwhile n > 0:\n    print(n)\n    n = n - 1\nprintln(\"\")\nprintln(total)\n";
+        assert_eq!(run_source(source).unwrap(), "a\nb\n321\n10\n");
+        // 多字节字符按字符遍历
+        assert_eq!(run_source("for c in \"é中a\":\n    println(c)\n").unwrap(), "é\n中\na\n");
+    }
+
+    // 测试递归函数调用
+    #[test]
+    fn test_recursive_function() {
+        let source = "fn fib(n: int) -> int:\n    if n < 2:\n        return n\n    return fib(n - 1) + fib(n - 2)\nprintln(fib(15))\n";
+        assert_eq!(run_source(source).unwrap(), "610\n");
+    }
+
+    // 测试逻辑运算符短路求值：右侧的除以零不会被计算
+    #[test]
+    fn test_logical_short_circuit() {
+        let source = "var x = 0\nprintln(x != 0 and 10 / x > 1)\nprintln(x == 0 or 10 / x > 1)\nprintln(not x > 1)\nprintln(!true || x < 1)\n";
+        assert_eq!(run_source(source).unwrap(), "false\ntrue\ntrue\ntrue\n");
+    }
+
+    // 测试while和for循环中的break和continue，以及循环体外的break编译错误
+    #[test]
+    fn test_break_continue() {
+        let source = "var i = 0\nwhile true:\n    i += 1\n    if i % 2 == 0:\n        continue\n    if i > 7:\n        break\n    print i\nfor n in (1, 10):\n    if n == 3 { continue }\n    for m in (1, 10):\n        if m > 2: break\n        print n * m\n    if n >= 5: break\nfor c in \"hello\":\n    if c == \"l\": continue\n    print c\n";
+        assert_eq!(run_source(source).unwrap(), "1357122448510heo");
+        let err = run_source("while true:\n    fn f():\n        break\n    break").unwrap_err();
+        assert!(err.starts_with("第3行第9列") && err.contains("break语句只能出现在循环体内"), "{}", err);
+    }
+
+    // 测试运行时错误和编译错误都带有源代码位置
+    #[test]
+    fn test_runtime_and_compile_errors() {
+        let err = run_source("var x = 1 / 0\n").unwrap_err();
+        assert!(err.contains("除数不能为零"), "{}", err);
+        let err = run_source("fn f() -> int:\n    return f()\nprintln(f())\n").unwrap_err();
+        assert!(err.contains("无限递归"), "{}", err);
+        let err = run_source("println(y)\n").unwrap_err();
+        assert!(err.starts_with("第1行第9列"), "{}", err);
+    }
+
+    // 测试字节码清单的格式
+    #[test]
+    fn test_disassemble_listing() {
+        let program = compile(&parse_source("var x = 1\nprintln(x + 2)\n").unwrap()).unwrap();
+        let listing = disassemble(&program);
+        assert!(listing.contains("== 函数 #0 main (参数: 0, 局部变量: 1) =="), "{}", listing);
+        assert!(listing.contains("STORE_LOCAL  0    (x)"), "{}", listing);
+        assert!(listing.contains("BINARY       Add"), "{}", listing);
+    }
+}
@@ -1,7 +1,13 @@
+use crate::workspace::{self, WorkspaceError, Workspaces};
 use actix_web::{web, App, HttpResponse, HttpServer};
 use std::fs;
-use std::io::Write;
 use std::path::Path;
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use std::time::Instant;
+use nexa_lang::cache::CompileCache;
+use nexa_lang::diagnostic::{format_all, Diagnostic};
+use nexa_lang::exec::{execute_rust_code_with, ExecutionLimits};
+use nexa_lang::{CompileError, CompileOptions, Session, Stage};
 
 // 处理编辑器页面请求
 async fn editor() -> HttpResponse {
@@ -14,130 +20,284 @@ async fn editor() -> HttpResponse {
         .body(html)
 }
 
-// 处理代码保存请求
-async fn save_code(data: web::Json<SaveRequest>) -> HttpResponse {
-    let content = &data.content;
-    
-    // 保存到临时文件
-    if let Ok(mut file) = fs::File::create("temp.nexa") {
-        if file.write_all(content.as_bytes()).is_ok() {
-            return HttpResponse::Ok().body("Code saved successfully");
-        }
+// 所有会话的工作区，在各个请求处理线程之间共享
+type SharedWorkspaces = web::Data<Mutex<Workspaces>>;
+
+// 锁定工作区，某个请求处理时崩溃不影响其他请求
+fn lock(workspaces: &SharedWorkspaces) -> MutexGuard<'_, Workspaces> {
+    workspaces.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+// 工作区操作失败的响应：会话或文件不存在返回404，其他错误返回400
+fn workspace_error(error: WorkspaceError) -> HttpResponse {
+    let body = ErrorResponse { error: error.to_string() };
+    match error {
+        WorkspaceError::UnknownSession | WorkspaceError::UnknownFile(_) => HttpResponse::NotFound().json(body),
+        _ => HttpResponse::BadRequest().json(body),
     }
-    
-    HttpResponse::InternalServerError().body("Failed to save code")
 }
 
-// 定义保存请求的数据结构
-#[derive(serde::Deserialize)]
-struct SaveRequest {
+// 定义错误响应的数据结构
+#[derive(serde::Serialize)]
+struct ErrorResponse {
+    error: String,
+}
+
+// 定义会话响应的数据结构
+#[derive(serde::Serialize)]
+struct SessionResponse {
+    session_id: String,
+    files: Vec<String>,
+}
+
+// 处理创建会话请求：返回新的会话id和其中的文件
+async fn create_session(workspaces: SharedWorkspaces) -> HttpResponse {
+    let now = Instant::now();
+    let mut workspaces = lock(&workspaces);
+    let session_id = workspaces.create(now);
+    match workspaces.list(&session_id, now) {
+        Ok(files) => HttpResponse::Ok().json(SessionResponse { session_id, files }),
+        Err(e) => workspace_error(e),
+    }
+}
+
+// 处理文件列表请求
+async fn list_files(workspaces: SharedWorkspaces, path: web::Path<String>) -> HttpResponse {
+    let session_id = path.into_inner();
+    match lock(&workspaces).list(&session_id, Instant::now()) {
+        Ok(files) => HttpResponse::Ok().json(SessionResponse { session_id, files }),
+        Err(e) => workspace_error(e),
+    }
+}
+
+// 定义文件内容的数据结构
+#[derive(serde::Serialize, serde::Deserialize)]
+struct FileContent {
     content: String,
 }
 
-// 处理代码加载请求
-async fn load_code() -> HttpResponse {
-    // 读取保存的代码
-    let content = match fs::read_to_string("temp.nexa") {
-        Ok(content) => content,
-        Err(_) => String::from("// Nexa语言示例代码\nvar i = 1;\nwhile i < 5:\n    println(i);\n    i += 1;\n"),
-    };
-    
-    HttpResponse::Ok().json(LoadResponse {
-        content,
-    })
+// 处理文件读取请求
+async fn read_file(workspaces: SharedWorkspaces, path: web::Path<(String, String)>) -> HttpResponse {
+    let (session_id, name) = path.into_inner();
+    match lock(&workspaces).read(&session_id, &name, Instant::now()) {
+        Ok(content) => HttpResponse::Ok().json(FileContent { content }),
+        Err(e) => workspace_error(e),
+    }
 }
 
-// 定义加载响应的数据结构
+// 处理文件保存请求，文件不存在时创建
+async fn write_file(
+    workspaces: SharedWorkspaces,
+    path: web::Path<(String, String)>,
+    data: web::Json<FileContent>,
+) -> HttpResponse {
+    let (session_id, name) = path.into_inner();
+    match lock(&workspaces).write(&session_id, &name, data.into_inner().content, Instant::now()) {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => workspace_error(e),
+    }
+}
+
+// 处理文件删除请求
+async fn delete_file(workspaces: SharedWorkspaces, path: web::Path<(String, String)>) -> HttpResponse {
+    let (session_id, name) = path.into_inner();
+    match lock(&workspaces).delete(&session_id, &name, Instant::now()) {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => workspace_error(e),
+    }
+}
+
+// 返回给编辑器的结构化诊断，编辑器可以按错误代码和位置标记出错的代码
 #[derive(serde::Serialize)]
-struct LoadResponse {
-    content: String,
+struct DiagnosticInfo {
+    code: Option<&'static str>,
+    severity: String,
+    message: String,
+    line: usize,
+    column: usize,
 }
 
-// 处理代码执行请求
-async fn run_code() -> HttpResponse {
-    // 读取保存的代码
-    if let Ok(content) = fs::read_to_string("temp.nexa") {
-        // 执行词法分析
-        match crate::lexer::tokenize(&content) {
-            Ok(tokens) => {
-                // 执行语法分析
-                match crate::parser::parse(&tokens) {
-                    Ok(ast) => {
-                        // 执行代码生成
-                                match crate::codegen::generate_code(&ast) {
-                                    Ok(rust_code) => {
-                                // 保存生成的Rust代码
-                                if let Ok(mut file) = fs::File::create("temp.rs") {
-                                    if file.write_all(rust_code.as_bytes()).is_ok() {
-                                        return HttpResponse::Ok().json(Response {
-                                            success: true,
-                                            output: rust_code,
-                                            error: None,
-                                        });
-                                    }
-                                }
-                                return HttpResponse::InternalServerError().json(Response {
-                                    success: false,
-                                    output: String::new(),
-                                    error: Some("Failed to save Rust code".to_string()),
-                                });
-                            },
-                            Err(e) => {
-                                return HttpResponse::Ok().json(Response {
-                                    success: false,
-                                    output: String::new(),
-                                    error: Some(format!("Code generation error: {}", e)),
-                                });
-                            },
-                        }
-                    },
-                    Err(e) => {
-                        return HttpResponse::Ok().json(Response {
-                            success: false,
-                            output: String::new(),
-                            error: Some(format!("Parsing error: {}", e)),
-                        });
-                    },
-                }
-            },
-            Err(e) => {
-                return HttpResponse::Ok().json(Response {
-                    success: false,
-                    output: String::new(),
-                    error: Some(format!("Lexical error: {}", e)),
-                });
-            },
+impl From<&Diagnostic> for DiagnosticInfo {
+    fn from(diagnostic: &Diagnostic) -> Self {
+        DiagnosticInfo {
+            code: diagnostic.code,
+            severity: diagnostic.severity.to_string(),
+            message: diagnostic.message.clone(),
+            line: diagnostic.span.line,
+            column: diagnostic.span.column,
         }
     }
-    
-    HttpResponse::InternalServerError().json(Response {
-        success: false,
-        output: String::new(),
-        error: Some("Failed to read code".to_string()),
-    })
+}
+
+// 生成编译失败的响应，error中每条诊断占一行，diagnostics中是结构化的诊断
+fn diagnostics_response(stage: &str, diagnostics: &[Diagnostic]) -> Response {
+    Response {
+        error: Some(format!("{}:\n{}", stage, format_all(diagnostics))),
+        diagnostics: diagnostics.iter().map(DiagnosticInfo::from).collect(),
+        ..Response::default()
+    }
+}
+
+// 检查并生成Rust代码，然后在沙箱中编译运行（阻塞调用），诊断中显示文件名file_name
+// 编译得到的可执行文件放在编译缓存cache中
+fn run_program(content: &str, file_name: &str, cache: &CompileCache) -> Response {
+    let options = CompileOptions {
+        file_name: file_name.to_string(),
+        ..CompileOptions::default()
+    };
+    let rust_code = match Session::with_options(content, options).generate() {
+        Ok(rust_code) => rust_code,
+        Err(CompileError::Codegen(e)) => return diagnostics_response("Code generation error", &[Diagnostic::from(e)]),
+        Err(e) => {
+            let stage = match e.stage() {
+                Stage::Tokenize | Stage::Parse => "Syntax error",
+                _ => "Check error",
+            };
+            return diagnostics_response(stage, e.diagnostics());
+        },
+    };
+    // 在沙箱中执行：独立的工作目录、运行时间和输出大小都有限制
+    match execute_rust_code_with(&rust_code, &ExecutionLimits::default(), cache) {
+        Ok(result) => Response {
+            success: result.success(),
+            error: (!result.success()).then(|| result.error_message()),
+            exit_code: result.exit_code,
+            timed_out: result.timed_out,
+            truncated: result.truncated,
+            duration_ms: Some(result.duration.as_millis() as u64),
+            stdout: result.stdout,
+            stderr: result.stderr,
+            rust_code: Some(rust_code),
+            ..Response::default()
+        },
+        Err(e) => Response {
+            error: Some(e),
+            rust_code: Some(rust_code),
+            ..Response::default()
+        },
+    }
+}
+
+// 定义执行请求的数据结构：要运行的代码和它的文件名（可选）
+#[derive(serde::Deserialize)]
+struct RunRequest {
+    code: String,
+    file: Option<String>,
+}
+
+// 处理代码执行请求，运行请求中的代码
+async fn run_code(data: web::Json<RunRequest>) -> HttpResponse {
+    let RunRequest { code, file } = data.into_inner();
+    let file_name = file.unwrap_or_else(|| workspace::DEFAULT_FILE.to_string());
+    // 编译和运行会阻塞，放到线程池中执行
+    match web::block(move || run_program(&code, &file_name, &CompileCache::default())).await {
+        Ok(response) => HttpResponse::Ok().json(response),
+        Err(e) => HttpResponse::InternalServerError().json(Response {
+            error: Some(format!("Failed to run code: {}", e)),
+            ..Response::default()
+        }),
+    }
 }
 
 // 定义响应的数据结构
-#[derive(serde::Serialize)]
+// 编译失败时error和diagnostics说明原因；运行后各字段分别是程序的输出、退出状态和运行时间
+#[derive(serde::Serialize, Default)]
 struct Response {
     success: bool,
-    output: String,
+    // 程序的标准输出
+    stdout: String,
+    // 程序的标准错误
+    stderr: String,
+    // 退出码，没有运行或被终止时为null
+    exit_code: Option<i32>,
+    // 是否因为超时被终止
+    timed_out: bool,
+    // 输出是否超过大小限制被截断
+    truncated: bool,
+    // 运行时间（毫秒），不含编译时间
+    duration_ms: Option<u64>,
+    // 生成的Rust代码
+    rust_code: Option<String>,
     error: Option<String>,
+    diagnostics: Vec<DiagnosticInfo>,
+}
+
+// 注册编辑器页面、会话工作区和代码执行的路由
+fn routes(cfg: &mut web::ServiceConfig) {
+    cfg.route("/", web::get().to(editor))
+        .route("/sessions", web::post().to(create_session))
+        .route("/sessions/{id}/files", web::get().to(list_files))
+        .route("/sessions/{id}/files/{name}", web::get().to(read_file))
+        .route("/sessions/{id}/files/{name}", web::put().to(write_file))
+        .route("/sessions/{id}/files/{name}", web::delete().to(delete_file))
+        .route("/run", web::post().to(run_code));
 }
 
-// 启动Web服务器
-pub async fn run() -> std::io::Result<()> {
-    println!("Starting web editor server at http://localhost:8080");
+// 启动Web服务器，监听本机的port端口
+pub async fn run(port: u16) -> std::io::Result<()> {
+    println!("Starting web editor server at http://localhost:{}", port);
     
-    HttpServer::new(|| {
+    let workspaces = web::Data::new(Mutex::new(Workspaces::new()));
+    HttpServer::new(move || {
         App::new()
-            .route("/", web::get().to(editor))
-            .route("/save", web::post().to(save_code))
-            .route("/load", web::get().to(load_code))
-            .route("/run", web::post().to(run_code))
+            .app_data(workspaces.clone())
+            .configure(routes)
             .service(actix_files::Files::new("/static", "./static"))
     })
-    .bind("127.0.0.1:8080")?
+    .bind(("127.0.0.1", port))?
     .run()
     .await
-}
\ No newline at end of file
+}
+
+// 测试模块：Web编辑器单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test as actix_test;
+
+    // 测试编译错误返回诊断，正常程序返回运行结果和生成的Rust代码
+    #[test]
+    fn test_run_program() {
+        // 编译缓存放在临时目录中，不写入用户的缓存目录
+        let dir = std::env::temp_dir().join(format!("nexa-web-editor-test-{}", std::process::id()));
+        let cache = CompileCache::new(dir.join("cache"), nexa_lang::cache::DEFAULT_MAX_SIZE);
+        let response = run_program("println(y)", "main.nexa", &cache);
+        assert!(!response.success && response.rust_code.is_none());
+        assert_eq!(response.diagnostics.len(), 1);
+        assert!(response.error.unwrap().starts_with("Check error"));
+
+        let response = run_program("var x = 6\nprintln(x * 7)\n", "main.nexa", &cache);
+        assert!(response.success, "{:?}", response.error);
+        assert_eq!(response.stdout, "42\n");
+        assert_eq!(response.exit_code, Some(0));
+        assert!(response.duration_ms.is_some());
+        assert!(response.rust_code.unwrap().contains("fn main()"));
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    // 测试通过会话接口保存、读取和删除文件，会话之间互不影响
+    #[actix_web::test]
+    async fn test_session_routes() {
+        let workspaces = web::Data::new(Mutex::new(Workspaces::new()));
+        let app = actix_test::init_service(App::new().app_data(workspaces).configure(routes)).await;
+
+        let request = actix_test::TestRequest::post().uri("/sessions").to_request();
+        let session: serde_json::Value = actix_test::call_and_read_body_json(&app, request).await;
+        let id = session["session_id"].as_str().unwrap().to_string();
+        assert_eq!(session["files"], serde_json::json!(["main.nexa"]));
+
+        let uri = format!("/sessions/{}/files/util.nexa", id);
+        let request = actix_test::TestRequest::put().uri(&uri).set_json(serde_json::json!({"content": "println(1)"})).to_request();
+        assert_eq!(actix_test::call_service(&app, request).await.status(), 204);
+        let request = actix_test::TestRequest::get().uri(&uri).to_request();
+        let file: serde_json::Value = actix_test::call_and_read_body_json(&app, request).await;
+        assert_eq!(file["content"], "println(1)");
+
+        let request = actix_test::TestRequest::delete().uri(&uri).to_request();
+        assert_eq!(actix_test::call_service(&app, request).await.status(), 204);
+        let request = actix_test::TestRequest::get().uri(&uri).to_request();
+        assert_eq!(actix_test::call_service(&app, request).await.status(), 404);
+        let request = actix_test::TestRequest::get().uri("/sessions/unknown/files").to_request();
+        assert_eq!(actix_test::call_service(&app, request).await.status(), 404);
+    }
+}
@@ -0,0 +1,186 @@
+// Web编辑器的会话工作区：每个浏览器会话有自己的会话id和多个命名文件
+// 工作区保存在内存中，长时间没有访问的会话会被清理
+use std::collections::hash_map::RandomState;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, Instant};
+
+// 会话闲置多久后被清理
+pub const SESSION_TTL: Duration = Duration::from_secs(60 * 60);
+// 同时保留的最多会话数，超过时清理最久没有访问的会话
+pub const MAX_SESSIONS: usize = 1000;
+// 每个会话最多的文件数
+pub const MAX_FILES: usize = 32;
+// 单个文件的最大字节数
+pub const MAX_FILE_SIZE: usize = 256 * 1024;
+// 新会话中的默认文件名
+pub const DEFAULT_FILE: &str = "main.nexa";
+// 新会话中默认文件的示例代码
+const DEFAULT_CODE: &str = "var i = 1\nwhile i < 5:\n    println(i)\n    i = i + 1\n";
+
+// 工作区操作的错误
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WorkspaceError {
+    // 会话不存在或已被清理
+    UnknownSession,
+    // 文件不存在
+    UnknownFile(String),
+    // 文件名不合法
+    InvalidFileName(String),
+    // 超过文件数或文件大小限制
+    LimitExceeded(String),
+}
+
+impl std::fmt::Display for WorkspaceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            WorkspaceError::UnknownSession => write!(f, "会话不存在或已过期"),
+            WorkspaceError::UnknownFile(name) => write!(f, "文件不存在: {}", name),
+            WorkspaceError::InvalidFileName(name) => write!(f, "无效的文件名: {}", name),
+            WorkspaceError::LimitExceeded(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+// 检查文件名：1到64个字母、数字、点、下划线或连字符，不能以点开头
+fn validate_file_name(name: &str) -> Result<(), WorkspaceError> {
+    let valid = !name.is_empty()
+        && name.len() <= 64
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if valid {
+        Ok(())
+    } else {
+        Err(WorkspaceError::InvalidFileName(name.to_string()))
+    }
+}
+
+// 生成随机的会话id（32个十六进制字符），RandomState每次使用系统随机数初始化
+fn new_session_id() -> String {
+    let random = || RandomState::new().build_hasher().finish();
+    format!("{:016x}{:016x}", random(), random())
+}
+
+// 一个会话的工作区
+#[derive(Debug)]
+struct Workspace {
+    // 文件名到内容，按文件名排序
+    files: BTreeMap<String, String>,
+    // 最后一次访问的时间
+    last_used: Instant,
+}
+
+// 所有会话的工作区
+#[derive(Debug, Default)]
+pub struct Workspaces {
+    sessions: HashMap<String, Workspace>,
+}
+
+impl Workspaces {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    // 清理闲置超过SESSION_TTL的会话
+    pub fn prune(&mut self, now: Instant) {
+        self.sessions.retain(|_, workspace| now.duration_since(workspace.last_used) < SESSION_TTL);
+    }
+
+    // 创建新会话，其中有一个示例文件，返回会话id
+    pub fn create(&mut self, now: Instant) -> String {
+        self.prune(now);
+        // 会话过多时清理最久没有访问的会话
+        if self.sessions.len() >= MAX_SESSIONS {
+            let oldest = self.sessions.iter().min_by_key(|(_, workspace)| workspace.last_used).map(|(id, _)| id.clone());
+            if let Some(oldest) = oldest {
+                self.sessions.remove(&oldest);
+            }
+        }
+        let id = new_session_id();
+        let files = BTreeMap::from([(DEFAULT_FILE.to_string(), DEFAULT_CODE.to_string())]);
+        self.sessions.insert(id.clone(), Workspace { files, last_used: now });
+        id
+    }
+
+    // 取得会话的工作区并更新访问时间
+    fn workspace(&mut self, id: &str, now: Instant) -> Result<&mut Workspace, WorkspaceError> {
+        self.prune(now);
+        let workspace = self.sessions.get_mut(id).ok_or(WorkspaceError::UnknownSession)?;
+        workspace.last_used = now;
+        Ok(workspace)
+    }
+
+    // 会话中的文件名列表
+    pub fn list(&mut self, id: &str, now: Instant) -> Result<Vec<String>, WorkspaceError> {
+        Ok(self.workspace(id, now)?.files.keys().cloned().collect())
+    }
+
+    // 读取文件内容
+    pub fn read(&mut self, id: &str, name: &str, now: Instant) -> Result<String, WorkspaceError> {
+        let workspace = self.workspace(id, now)?;
+        workspace.files.get(name).cloned().ok_or_else(|| WorkspaceError::UnknownFile(name.to_string()))
+    }
+
+    // 写入文件，文件不存在时创建
+    pub fn write(&mut self, id: &str, name: &str, content: String, now: Instant) -> Result<(), WorkspaceError> {
+        validate_file_name(name)?;
+        if content.len() > MAX_FILE_SIZE {
+            return Err(WorkspaceError::LimitExceeded(format!("文件超过 {} 字节", MAX_FILE_SIZE)));
+        }
+        let workspace = self.workspace(id, now)?;
+        if !workspace.files.contains_key(name) && workspace.files.len() >= MAX_FILES {
+            return Err(WorkspaceError::LimitExceeded(format!("每个会话最多 {} 个文件", MAX_FILES)));
+        }
+        workspace.files.insert(name.to_string(), content);
+        Ok(())
+    }
+
+    // 删除文件
+    pub fn delete(&mut self, id: &str, name: &str, now: Instant) -> Result<(), WorkspaceError> {
+        let workspace = self.workspace(id, now)?;
+        workspace.files.remove(name).map(|_| ()).ok_or_else(|| WorkspaceError::UnknownFile(name.to_string()))
+    }
+}
+
+// 测试模块：会话工作区单元测试
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 测试会话之间的文件互相独立
+    #[test]
+    fn test_sessions_are_isolated() {
+        let now = Instant::now();
+        let mut workspaces = Workspaces::new();
+        let a = workspaces.create(now);
+        let b = workspaces.create(now);
+        assert_ne!(a, b);
+        assert_eq!(a.len(), 32);
+
+        workspaces.write(&a, DEFAULT_FILE, "println(1)".to_string(), now).unwrap();
+        workspaces.write(&a, "lib.nexa", "println(2)".to_string(), now).unwrap();
+        assert_eq!(workspaces.list(&a, now).unwrap(), ["lib.nexa", "main.nexa"]);
+        assert_eq!(workspaces.read(&a, DEFAULT_FILE, now).unwrap(), "println(1)");
+        assert_eq!(workspaces.read(&b, DEFAULT_FILE, now).unwrap(), DEFAULT_CODE);
+        assert_eq!(workspaces.read(&b, "lib.nexa", now), Err(WorkspaceError::UnknownFile("lib.nexa".to_string())));
+
+        workspaces.delete(&a, "lib.nexa", now).unwrap();
+        assert_eq!(workspaces.list(&a, now).unwrap(), ["main.nexa"]);
+        assert!(matches!(workspaces.write(&a, "../x", String::new(), now), Err(WorkspaceError::InvalidFileName(_))));
+        assert_eq!(workspaces.list("missing", now), Err(WorkspaceError::UnknownSession));
+    }
+
+    // 测试闲置的会话被清理，访问会延长会话
+    #[test]
+    fn test_idle_sessions_are_pruned() {
+        let start = Instant::now();
+        let mut workspaces = Workspaces::new();
+        let idle = workspaces.create(start);
+        let active = workspaces.create(start);
+        let later = start + SESSION_TTL / 2;
+        workspaces.list(&active, later).unwrap();
+        let expired = start + SESSION_TTL;
+        assert_eq!(workspaces.list(&idle, expired), Err(WorkspaceError::UnknownSession));
+        assert!(workspaces.list(&active, expired).is_ok());
+    }
+}